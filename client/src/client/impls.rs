@@ -0,0 +1,430 @@
+use std::time::Duration;
+
+use ecs_task_spawner_models::{
+    audit::models::{AuditQuery, AuditRecord},
+    dispatch::models::{QueueEntry, QueueStatus},
+    ecs::models::{EcsTag, RequestId, TaskFamily, TaskInfo, TaskRequest},
+    health::models::{Health, ReadinessReport},
+    jobs::models::Job,
+    logging::models::{LogLevel, REQUEST_ID_HEADER},
+    subnets::models::SubnetHealth,
+};
+use reqwest::{header::RETRY_AFTER, Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::models::{RetryOn, RetryPolicy, SpawnerClient};
+use crate::errors::models::ClientError;
+
+impl SpawnerClient {
+    pub fn new(base_url: &str, api_key: &str) -> Self {
+        SpawnerClient {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // For timeouts, proxies and the like.
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    // Only tried again when the spawner turned the spawn away, a 5xx or a timeout may have
+    // launched the worker already. An API error carries the request id to look for it with
+    // `tasks_started_by`.
+    pub async fn spawn(&self, request: &TaskRequest) -> Result<TaskInfo, ClientError> {
+        let response = self
+            .send(Method::POST, "/spawn-worker", RetryOn::Rejections, |req| {
+                req.json(request)
+            })
+            .await?;
+        decode(response).await
+    }
+
+    pub async fn task_family(&self, task_family: &str) -> Result<Vec<TaskInfo>, ClientError> {
+        let body = TaskFamily {
+            task_family: task_family.to_string(),
+        };
+        self.json(Method::POST, "/task-family", |req| req.json(&body))
+            .await
+    }
+
+    pub async fn tasks_with_tag(&self, tag: &EcsTag) -> Result<Vec<TaskInfo>, ClientError> {
+        self.json(Method::POST, "/task-tag", |req| req.json(tag))
+            .await
+    }
+
+    // Running and recently stopped tasks launched by the request with this x-request-id.
+    pub async fn tasks_started_by(&self, request_id: &str) -> Result<Vec<TaskInfo>, ClientError> {
+        let body = RequestId {
+            request_id: request_id.to_string(),
+        };
+        self.json(Method::POST, "/task-request", |req| req.json(&body))
+            .await
+    }
+
+    pub async fn job(&self, id: &str) -> Result<Job, ClientError> {
+        self.json(Method::GET, &format!("/jobs/{}", id), |req| req)
+            .await
+    }
+
+    pub async fn queue(&self) -> Result<QueueStatus, ClientError> {
+        self.json(Method::GET, "/admin/queue", |req| req).await
+    }
+
+    // By the spawn's id or the x-request-id of its request.
+    pub async fn queue_entry(&self, id: &str) -> Result<QueueEntry, ClientError> {
+        self.json(Method::GET, &format!("/queue/{}", id), |req| req)
+            .await
+    }
+
+    pub async fn subnets(&self) -> Result<Vec<SubnetHealth>, ClientError> {
+        self.json(Method::GET, "/admin/subnets", |req| req).await
+    }
+
+    pub async fn log_level(&self) -> Result<LogLevel, ClientError> {
+        self.json(Method::GET, "/admin/log-level", |req| req).await
+    }
+
+    pub async fn set_log_level(&self, filter: &str) -> Result<LogLevel, ClientError> {
+        let body = LogLevel {
+            filter: filter.to_string(),
+        };
+        self.json(Method::PUT, "/admin/log-level", |req| req.json(&body))
+            .await
+    }
+
+    pub async fn audit(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, ClientError> {
+        self.json(Method::GET, "/admin/audit", |req| req.query(query))
+            .await
+    }
+
+    pub async fn health(&self) -> Result<Health, ClientError> {
+        self.json(Method::GET, "/health/live", |req| req).await
+    }
+
+    // The report of a spawner that isn't ready comes with a 503, and is returned all the same.
+    pub async fn ready(&self) -> Result<ReadinessReport, ClientError> {
+        let response = self
+            .send(Method::GET, "/health/ready", RetryOn::Failures, |req| req)
+            .await?;
+        if response.status() == StatusCode::SERVICE_UNAVAILABLE {
+            return Ok(response.json().await?);
+        }
+        decode(response).await
+    }
+
+    // In the Prometheus text format.
+    pub async fn metrics(&self) -> Result<String, ClientError> {
+        let response = self
+            .send(Method::GET, "/metrics", RetryOn::Failures, |req| req)
+            .await?;
+        if !response.status().is_success() {
+            return Err(ClientError::from_response(response).await);
+        }
+        Ok(response.text().await?)
+    }
+
+    pub async fn openapi(&self) -> Result<serde_json::Value, ClientError> {
+        self.json(Method::GET, "/openapi.json", |req| req).await
+    }
+
+    // Polls the task's request until ECS reports the task STOPPED, and returns it as it
+    // stopped, with its exit code and stop reason.
+    pub async fn wait_until_stopped(
+        &self,
+        task: &TaskInfo,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<TaskInfo, ClientError> {
+        let request_id = task
+            .request_id
+            .as_deref()
+            .ok_or_else(|| ClientError::MissingRequestId(task.task_arn.clone()))?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            // A task that was just launched may not be listed yet.
+            let current = self
+                .tasks_started_by(request_id)
+                .await?
+                .into_iter()
+                .find(|current| current.task_arn == task.task_arn);
+            if let Some(current) = &current {
+                if current.status == "STOPPED" {
+                    return Ok(current.clone());
+                }
+            }
+            if Instant::now() + poll_interval > deadline {
+                return Err(match current {
+                    Some(_) => ClientError::WaitTimeout {
+                        task_arn: task.task_arn.clone(),
+                        timeout,
+                    },
+                    None => ClientError::TaskNotFound(task.task_arn.clone()),
+                });
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    async fn json<R: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<R, ClientError> {
+        decode(self.send(method, path, RetryOn::Failures, build).await?).await
+    }
+
+    // Sends the request, trying again while the policy allows. The last response is returned
+    // whatever its status.
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        retry_on: RetryOn,
+        build: impl Fn(RequestBuilder) -> RequestBuilder,
+    ) -> Result<Response, ClientError> {
+        let url = format!("{}{}", self.base_url, path);
+        let request_id = Uuid::new_v4().to_string();
+        let mut attempt = 1;
+        loop {
+            let request = build(self.http.request(method.clone(), &url))
+                .bearer_auth(&self.api_key)
+                .header(REQUEST_ID_HEADER, &request_id);
+            let last = attempt >= self.retry.max_attempts;
+            let delay = match request.send().await {
+                Ok(response) if last || !retry_on.status(response.status()) => return Ok(response),
+                Ok(response) => retry_after(&response).unwrap_or(self.retry.backoff(attempt)),
+                Err(err) if !last && retry_on.error(&err) => self.retry.backoff(attempt),
+                Err(err) => return Err(err.into()),
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Before the try after the given one: initial_backoff, doubling up to max_backoff.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+}
+
+impl RetryOn {
+    fn status(self, status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS
+            || (self == RetryOn::Failures && status.is_server_error())
+    }
+
+    fn error(self, err: &reqwest::Error) -> bool {
+        err.is_connect() || (self == RetryOn::Failures && err.is_timeout())
+    }
+}
+
+// Only the delay-seconds form, the spawner doesn't send dates.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+async fn decode<R: DeserializeOwned>(response: Response) -> Result<R, ClientError> {
+    if !response.status().is_success() {
+        return Err(ClientError::from_response(response).await);
+    }
+    Ok(response.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::{get, post},
+        Json, Router,
+    };
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::errors::models::ErrorKind;
+
+    fn task(status: &str) -> Value {
+        json!({
+            "task_arn": "arn:aws:ecs:us-east-1:123456789012:task/default/abc",
+            "status": status,
+            "created_at": "2026-01-01T00:00:00Z",
+            "image": "public.ecr.aws/soi/bloomberg-worker:latest",
+            "tags": [],
+            "request_id": "req-1",
+            "containers": [],
+        })
+    }
+
+    fn request() -> TaskRequest {
+        serde_json::from_value(json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+        }))
+        .unwrap()
+    }
+
+    async fn serve(router: Router) -> SpawnerClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        SpawnerClient::new(&format!("http://{}/", address), "key").with_retry(RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        })
+    }
+
+    #[tokio::test]
+    async fn retries_throttled_calls_with_the_same_request_id() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let calls = seen.clone();
+        let client = serve(Router::new().route(
+            "/spawn-worker",
+            post(move |headers: HeaderMap| {
+                let calls = calls.clone();
+                async move {
+                    let mut calls = calls.lock().unwrap();
+                    calls.push((
+                        headers["authorization"].to_str().unwrap().to_string(),
+                        headers[REQUEST_ID_HEADER].to_str().unwrap().to_string(),
+                    ));
+                    if calls.len() == 1 {
+                        return (StatusCode::TOO_MANY_REQUESTS, Json(json!({})));
+                    }
+                    (StatusCode::OK, Json(task("PROVISIONING")))
+                }
+            }),
+        ))
+        .await;
+
+        let spawned = client.spawn(&request()).await.unwrap();
+        assert_eq!(spawned.status, "PROVISIONING");
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0].0, "Bearer key");
+        assert_eq!(seen[0].1, seen[1].1);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_spawns_that_failed_on_the_server() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (spawns, lives) = (calls.clone(), calls.clone());
+        let client = serve(
+            Router::new()
+                .route(
+                    "/spawn-worker",
+                    post(move || {
+                        spawns.lock().unwrap().push("spawn");
+                        async { (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({}))) }
+                    }),
+                )
+                .route(
+                    "/health/live",
+                    get(move || {
+                        lives.lock().unwrap().push("live");
+                        async { (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({}))) }
+                    }),
+                ),
+        )
+        .await;
+
+        assert!(client.spawn(&request()).await.is_err());
+        assert!(client.health().await.is_err());
+        assert_eq!(*calls.lock().unwrap(), ["spawn", "live", "live", "live"]);
+    }
+
+    #[tokio::test]
+    async fn decodes_the_error_envelope() {
+        let client = serve(Router::new().route(
+            "/spawn-worker",
+            post(|| async {
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": {
+                        "type": "UNSUPPORTED_VENDOR",
+                        "message": "Unsupported vendor: acme",
+                        "request_id": "req-2",
+                    } })),
+                )
+            }),
+        ))
+        .await;
+
+        match client.spawn(&request()).await {
+            Err(ClientError::Api {
+                status,
+                kind,
+                request_id,
+                ..
+            }) => {
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+                assert_eq!(kind, ErrorKind::UnsupportedVendor);
+                assert_eq!(request_id.as_deref(), Some("req-2"));
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_until_the_task_has_stopped() {
+        let polls = Arc::new(Mutex::new(0));
+        let counter = polls.clone();
+        let client = serve(Router::new().route(
+            "/task-request",
+            post(move |Json(body): Json<RequestId>| {
+                let counter = counter.clone();
+                async move {
+                    assert_eq!(body.request_id, "req-1");
+                    let mut polls = counter.lock().unwrap();
+                    *polls += 1;
+                    let status = if *polls < 3 { "RUNNING" } else { "STOPPED" };
+                    Json(json!([task(status)]))
+                }
+            }),
+        ))
+        .await;
+
+        let running: TaskInfo = serde_json::from_value(task("RUNNING")).unwrap();
+        let stopped = client
+            .wait_until_stopped(&running, Duration::from_millis(1), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(stopped.status, "STOPPED");
+        assert_eq!(*polls.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let retry = RetryPolicy::default();
+        assert_eq!(retry.backoff(1), Duration::from_millis(250));
+        assert_eq!(retry.backoff(3), Duration::from_secs(1));
+        assert_eq!(retry.backoff(10), Duration::from_secs(10));
+    }
+}
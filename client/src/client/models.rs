@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+// Calls the spawner with its API key. Cheap to clone, clones share connections.
+#[derive(Debug, Clone)]
+pub struct SpawnerClient {
+    pub http: reqwest::Client,
+    // e.g. http://ecs-task-spawner:3000, without a trailing slash.
+    pub base_url: String,
+    pub api_key: String,
+    pub retry: RetryPolicy,
+}
+
+// Calls answered with 429 or a 5xx, or that could not connect or timed out, are tried again
+// after an exponential backoff, or after the Retry-After the spawner asked for. Spawns are
+// only tried again on 429 and connect errors, see `RetryOn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // Including the first try.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+// Which failed tries are worth another. A spawn that got a 5xx or timed out may have launched
+// its worker anyway, trying it again could launch a second one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryOn {
+    // 429, 5xx, connect errors and timeouts.
+    Failures,
+    // 429 and connect errors, where the spawner never took the request on.
+    Rejections,
+}
@@ -0,0 +1,103 @@
+use std::fmt;
+
+use ecs_task_spawner_models::errors::models::ErrorResponse;
+use reqwest::Response;
+
+use super::models::{ClientError, ErrorKind};
+
+const KINDS: &[(&str, ErrorKind)] = &[
+    ("TASK_SPAWN_ERROR", ErrorKind::TaskSpawn),
+    ("AWS_SDK_ERROR", ErrorKind::AwsSdk),
+    ("VALIDATION_ERROR", ErrorKind::Validation),
+    ("NOT_FOUND_ERROR", ErrorKind::NotFound),
+    ("UNAUTHORIZED_ERROR", ErrorKind::Unauthorized),
+    ("FORBIDDEN_ERROR", ErrorKind::Forbidden),
+    ("INTERNAL_SERVER_ERROR", ErrorKind::InternalServer),
+    ("LOG_CONFIGURATION_ERROR", ErrorKind::LogConfiguration),
+    (
+        "REGISTER_TASK_DEFINITION_ERROR",
+        ErrorKind::RegisterTaskDefinition,
+    ),
+    ("RUN_TASK_ERROR", ErrorKind::RunTask),
+    ("LIST_TASKS_ERROR", ErrorKind::ListTasks),
+    ("DESCRIBE_TASK_ERROR", ErrorKind::DescribeTask),
+    ("STOP_TASK_ERROR", ErrorKind::StopTask),
+    ("SIMULATE_POLICY_ERROR", ErrorKind::SimulatePolicy),
+    ("CUSTOM_ERROR", ErrorKind::Custom),
+    ("UNSUPPORTED_VENDOR", ErrorKind::UnsupportedVendor),
+    ("QUOTA_EXCEEDED_ERROR", ErrorKind::QuotaExceeded),
+    ("CONCURRENCY_CONFLICT_ERROR", ErrorKind::ConcurrencyConflict),
+];
+
+impl ErrorKind {
+    pub fn parse(error_type: &str) -> Self {
+        KINDS
+            .iter()
+            .find(|(name, _)| *name == error_type)
+            .map(|(_, kind)| kind.clone())
+            .unwrap_or_else(|| ErrorKind::Other(error_type.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ErrorKind::Other(error_type) => error_type,
+            kind => KINDS
+                .iter()
+                .find(|(_, known)| known == kind)
+                .map(|(name, _)| *name)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl ClientError {
+    // Decodes the error envelope of a failed call.
+    pub async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(err) => return ClientError::Http(err),
+        };
+        match serde_json::from_str::<ErrorResponse>(&body) {
+            Ok(ErrorResponse { error }) => ClientError::Api {
+                status,
+                kind: ErrorKind::parse(&error.error_type),
+                message: error.message,
+                request_id: error.request_id,
+            },
+            Err(_) => ClientError::UnexpectedResponse { status, body },
+        }
+    }
+
+    // The spawner's error type, for errors it answered with.
+    pub fn kind(&self) -> Option<&ErrorKind> {
+        match self {
+            ClientError::Api { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_known_type_and_keeps_unknown_ones() {
+        for (name, kind) in KINDS {
+            assert_eq!(ErrorKind::parse(name), *kind);
+            assert_eq!(kind.as_str(), *name);
+        }
+        assert_eq!(
+            ErrorKind::parse("NEW_ERROR"),
+            ErrorKind::Other("NEW_ERROR".to_string())
+        );
+        assert_eq!(ErrorKind::parse("NEW_ERROR").to_string(), "NEW_ERROR");
+    }
+}
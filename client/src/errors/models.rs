@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    // The spawner's error envelope, decoded.
+    #[error("{kind} ({status}): {message}")]
+    Api {
+        status: StatusCode,
+        kind: ErrorKind,
+        message: String,
+        request_id: Option<String>,
+    },
+    // A response that isn't the error envelope, e.g. from a load balancer.
+    #[error("Unexpected response ({status}): {body}")]
+    UnexpectedResponse { status: StatusCode, body: String },
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{task_arn} did not stop within {timeout:?}")]
+    WaitTimeout { task_arn: String, timeout: Duration },
+    // ECS only lists stopped tasks for about an hour.
+    #[error("{0} is not among the tasks of its request")]
+    TaskNotFound(String),
+    #[error("{0} has no request id to find it by")]
+    MissingRequestId(String),
+}
+
+// The `error.type` of the spawner's error responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    TaskSpawn,
+    AwsSdk,
+    Validation,
+    NotFound,
+    Unauthorized,
+    Forbidden,
+    InternalServer,
+    LogConfiguration,
+    RegisterTaskDefinition,
+    RunTask,
+    ListTasks,
+    DescribeTask,
+    StopTask,
+    SimulatePolicy,
+    Custom,
+    UnsupportedVendor,
+    QuotaExceeded,
+    ConcurrencyConflict,
+    // A type this client doesn't know yet.
+    Other(String),
+}
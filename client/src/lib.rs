@@ -0,0 +1,16 @@
+// Typed client for the spawner's HTTP API. Requests and responses are the models the server
+// uses too, so they can't drift apart.
+pub mod client;
+pub mod errors;
+
+pub use client::models::{RetryPolicy, SpawnerClient};
+pub use ecs_task_spawner_models::{
+    audit::models::{AuditAction, AuditOutcome, AuditQuery, AuditRecord},
+    dispatch::models::{QueueEntry, QueueStatus},
+    ecs::models::{EcsTag, TaskFamily, TaskInfo, TaskRequest},
+    health::models::{Health, ReadinessReport},
+    jobs::models::Job,
+    logging::models::LogLevel,
+    subnets::models::SubnetHealth,
+};
+pub use errors::models::{ClientError, ErrorKind};
@@ -0,0 +1,26 @@
+use super::models::{AuditQuery, AuditRecord, DEFAULT_QUERY_LIMIT, MAX_QUERY_LIMIT};
+
+impl AuditQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.principal
+            .as_ref()
+            .is_none_or(|principal| record.principal == *principal)
+            && self.action.is_none_or(|action| record.action == action)
+            && self
+                .request_id
+                .as_ref()
+                .is_none_or(|id| record.request_id.as_ref() == Some(id))
+            && self
+                .task_arn
+                .as_ref()
+                .is_none_or(|arn| record.task_arns.contains(arn))
+            && self.since.is_none_or(|since| record.at >= since)
+            && self.until.is_none_or(|until| record.at < until)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_QUERY_LIMIT)
+            .clamp(1, MAX_QUERY_LIMIT)
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// Queries return at most this many records, the most recent ones.
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+// Who did what to which tasks, and whether it worked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub id: String,
+    pub at: DateTime<Utc>,
+    pub principal: String,
+    pub request_id: Option<String>,
+    pub action: AuditAction,
+    // With secrets redacted.
+    pub payload: serde_json::Value,
+    pub outcome: AuditOutcome,
+    pub error: Option<String>,
+    pub task_arns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Spawn,
+    Stop,
+    ConfigChange,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Succeeded,
+    Failed,
+}
+
+// Filters of GET /admin/audit. Every one that is set has to match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditQuery {
+    pub principal: Option<String>,
+    pub action: Option<AuditAction>,
+    pub request_id: Option<String>,
+    pub task_arn: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
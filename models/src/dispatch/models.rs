@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ecs::models::Priority;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub id: String,
+    pub position: usize,
+    pub clientid: String,
+    pub vendor: String,
+    pub soiid: String,
+    // x-request-id of the request the spawn is for, which callers can look the entry up by.
+    pub request_id: Option<String>,
+    pub priority: Priority,
+    pub enqueued_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueStatus {
+    pub depth: usize,
+    pub in_flight: usize,
+    pub by_client: HashMap<String, usize>,
+    pub by_priority: HashMap<Priority, usize>,
+    pub entries: Vec<QueueEntry>,
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::sizing::models::Architecture;
+
+// New stuff
+// To spawna a task we need the following:
+// - s3 url
+// - soiid
+// - clientid
+// - worker type
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskRequest {
+    pub data_location: String,
+    pub soiid: String,
+    pub clientid: String,
+    pub vendor: String,
+    // For vendors without a concurrency key template. Otherwise it has to be the vendor's key.
+    #[serde(default)]
+    pub concurrency_key: Option<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    // Resources for the worker, bounded by the vendor's configuration. A missing cpu or
+    // memory is filled in with the smallest value that makes a valid Fargate pair.
+    #[serde(default)]
+    pub cpu: Option<u32>,
+    #[serde(default)]
+    pub memory: Option<u32>,
+    #[serde(default)]
+    pub ephemeral_storage_gib: Option<u32>,
+    #[serde(default)]
+    pub architecture: Option<Architecture>,
+    // Overrides the vendor's capacity provider strategy.
+    #[serde(default)]
+    pub capacity_providers: Option<Vec<CapacityProviderItem>>,
+    // Added to the worker's environment, on top of APP_DATA_URL.
+    #[serde(default)]
+    pub env: Vec<EcsEnvVar>,
+    // Replaces the command of the worker image.
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    // One of the vendor's allowed task roles.
+    #[serde(default)]
+    pub task_role_arn: Option<String>,
+}
+
+// One entry of a capacity provider strategy, e.g. FARGATE_SPOT with weight 3.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct CapacityProviderItem {
+    pub provider: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub base: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+// Queued spawns of a higher priority are always dispatched first.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskFamily {
+    pub task_family: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RequestId {
+    pub request_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EcsTag {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub struct EcsEnvVar {
+    pub name: String,
+    pub value: String,
+}
+
+// How serde writes the Duration of TaskInfo, for the OpenAPI document.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RunningDuration {
+    pub secs: u64,
+    pub nanos: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskInfo {
+    pub task_arn: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = Option<RunningDuration>)]
+    pub running_duration: Option<Duration>,
+    pub image: String,
+    pub cpu_usage: Option<f64>,
+    pub memory_usage: Option<f64>,
+    pub tags: Vec<EcsTag>,
+    pub job_id: Option<String>,
+    // Id of the request that launched the task.
+    pub request_id: Option<String>,
+    // FARGATE or FARGATE_SPOT, whichever the task actually landed on, or the launch type
+    // (e.g. EC2) of tasks launched without a capacity provider.
+    pub capacity_provider: Option<String>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub stop_code: Option<String>,
+    pub stopped_reason: Option<String>,
+    // Exit code and stop reason of the worker container once it has stopped.
+    pub exit_code: Option<i32>,
+    pub container_reason: Option<String>,
+    pub containers: Vec<ContainerInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ContainerInfo {
+    pub name: String,
+    pub status: String,
+    // UNKNOWN for containers without a health check.
+    pub health_status: Option<String>,
+    pub exit_code: Option<i32>,
+    pub reason: Option<String>,
+}
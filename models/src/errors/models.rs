@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Body of every error response.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    // What went wrong, e.g. VALIDATION_ERROR or QUOTA_EXCEEDED_ERROR.
+    #[serde(rename = "type")]
+    pub error_type: String,
+    pub message: String,
+    // The x-request-id of the response, to find the request in our logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
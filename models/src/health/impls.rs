@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use super::models::ReadinessCheck;
+
+impl ReadinessCheck {
+    pub fn new(name: &str, result: Result<String, String>, duration: Duration) -> Self {
+        let (passed, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(detail) => (false, detail),
+        };
+        ReadinessCheck {
+            name: name.to_string(),
+            passed,
+            detail,
+            duration_ms: duration.as_millis() as u64,
+        }
+    }
+}
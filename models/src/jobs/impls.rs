@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::models::{Attempt, AttemptOutcome, Job, JobStatus};
+use crate::{
+    ecs::models::{TaskInfo, TaskRequest},
+    sizing::models::TaskSize,
+};
+
+impl Job {
+    // The spawner fills in the request id and trace context of the request that submitted it.
+    pub fn new(request: TaskRequest, size: TaskSize) -> Self {
+        Job {
+            id: Uuid::new_v4().to_string(),
+            request,
+            status: JobStatus::Launching,
+            size,
+            on_demand: false,
+            created_at: Utc::now(),
+            next_attempt_at: None,
+            last_error: None,
+            attempts: Vec::new(),
+            trace_context: HashMap::new(),
+            request_id: None,
+        }
+    }
+
+    pub fn next_attempt(&self) -> u32 {
+        self.attempts.len() as u32 + 1
+    }
+
+    // Attempts that count towards the retry policy's max_attempts. Resizes and Spot
+    // interruptions have their own limits.
+    pub fn retry_attempts(&self) -> u32 {
+        self.attempts
+            .iter()
+            .filter(|attempt| {
+                !matches!(
+                    attempt.outcome,
+                    Some(AttemptOutcome::Resized | AttemptOutcome::Interrupted)
+                )
+            })
+            .count() as u32
+    }
+
+    pub fn interruptions(&self) -> u32 {
+        self.attempts
+            .iter()
+            .filter(|attempt| attempt.outcome == Some(AttemptOutcome::Interrupted))
+            .count() as u32
+    }
+
+    pub fn size_key(&self) -> String {
+        format!("{}:{}", self.request.vendor, self.request.soiid)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.status, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+impl Attempt {
+    pub fn new(number: u32, size: TaskSize, task: &TaskInfo) -> Self {
+        Attempt {
+            number,
+            task_arn: task.task_arn.clone(),
+            size,
+            capacity_provider: task.capacity_provider.clone(),
+            started_at: task.created_at,
+            stopped_at: None,
+            exit_code: None,
+            stop_code: None,
+            stopped_reason: None,
+            container_reason: None,
+            outcome: None,
+        }
+    }
+
+    pub fn record_stop(&mut self, task: &TaskInfo) {
+        self.stopped_at = task.stopped_at.or_else(|| Some(Utc::now()));
+        self.exit_code = task.exit_code;
+        self.stop_code = task.stop_code.clone();
+        self.stopped_reason = task.stopped_reason.clone();
+        self.container_reason = task.container_reason.clone();
+        self.capacity_provider = task
+            .capacity_provider
+            .clone()
+            .or(self.capacity_provider.take());
+    }
+}
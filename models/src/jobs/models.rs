@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{ecs::models::TaskRequest, sizing::models::TaskSize};
+
+// A spawn request and every task launched to fulfil it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub request: TaskRequest,
+    pub status: JobStatus,
+    // Size the next attempt is launched with.
+    pub size: TaskSize,
+    // Set once Spot interruptions have pushed the job onto on-demand Fargate.
+    pub on_demand: bool,
+    pub created_at: DateTime<Utc>,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    // Why the job failed, or why its last relaunch could not be spawned.
+    pub last_error: Option<String>,
+    pub attempts: Vec<Attempt>,
+    // traceparent and tracestate of the request that submitted the job. Every attempt is
+    // launched in a span under it.
+    #[serde(default)]
+    pub trace_context: HashMap<String, String>,
+    // Id of the request that submitted the job. Its attempts are started by it.
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    RetryScheduled,
+    Launching,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attempt {
+    pub number: u32,
+    pub task_arn: String,
+    pub size: TaskSize,
+    pub capacity_provider: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub exit_code: Option<i32>,
+    pub stop_code: Option<String>,
+    pub stopped_reason: Option<String>,
+    pub container_reason: Option<String>,
+    pub outcome: Option<AttemptOutcome>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttemptOutcome {
+    Succeeded,
+    Retried,
+    // Ran out of memory and was retried one size up.
+    Resized,
+    // Reclaimed by Fargate Spot and relaunched.
+    Interrupted,
+    Failed,
+}
@@ -0,0 +1,10 @@
+// Requests and responses of the spawner's HTTP API, shared by the server and the client.
+pub mod audit;
+pub mod dispatch;
+pub mod ecs;
+pub mod errors;
+pub mod health;
+pub mod jobs;
+pub mod logging;
+pub mod sizing;
+pub mod subnets;
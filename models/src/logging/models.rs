@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+// Header a caller can set to have its own id on our log lines. The id is sent back in it.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogLevel {
+    // An EnvFilter directive, as in the log_level setting.
+    pub filter: String,
+}
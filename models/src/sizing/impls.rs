@@ -0,0 +1,102 @@
+use std::fmt;
+
+use super::models::{Architecture, TaskSize, FARGATE_MEMORY};
+
+impl TaskSize {
+    // Every cpu/memory combination Fargate accepts, smallest memory first.
+    pub fn fargate_sizes() -> Vec<TaskSize> {
+        let mut sizes: Vec<TaskSize> = FARGATE_MEMORY
+            .iter()
+            .flat_map(|&(cpu, min, max, step)| {
+                (min..=max)
+                    .step_by(step as usize)
+                    .map(move |memory| TaskSize { cpu, memory })
+            })
+            .collect();
+        sizes.sort_by_key(|size| (size.memory, size.cpu));
+        sizes
+    }
+
+    pub fn is_valid_fargate(&self) -> bool {
+        FARGATE_MEMORY.iter().any(|&(cpu, min, max, step)| {
+            self.cpu == cpu
+                && (min..=max).contains(&self.memory)
+                && (self.memory - min).is_multiple_of(step)
+        })
+    }
+
+    // Fills in whichever of cpu and memory is missing with the smallest value that makes a
+    // valid pair, falling back to `default` when there is none.
+    pub fn complete(cpu: Option<u32>, memory: Option<u32>, default: &TaskSize) -> Option<TaskSize> {
+        match (cpu, memory) {
+            (Some(cpu), Some(memory)) => Some(TaskSize { cpu, memory }),
+            (Some(cpu), None) => Some(
+                TaskSize::fargate_sizes()
+                    .into_iter()
+                    .find(|size| size.cpu == cpu)
+                    .unwrap_or(TaskSize {
+                        cpu,
+                        memory: default.memory,
+                    }),
+            ),
+            (None, Some(memory)) => Some(
+                TaskSize::fargate_sizes()
+                    .into_iter()
+                    .filter(|size| size.memory == memory)
+                    .min_by_key(|size| size.cpu)
+                    .unwrap_or(TaskSize {
+                        cpu: default.cpu,
+                        memory,
+                    }),
+            ),
+            (None, None) => None,
+        }
+    }
+
+    // Up to `count` valid sizes between `min` and `max` closest to this one.
+    pub fn nearest_fargate(&self, min: &TaskSize, max: &TaskSize, count: usize) -> Vec<TaskSize> {
+        let distance = |size: &TaskSize| {
+            let cpu = (f64::from(size.cpu) / f64::from(self.cpu.max(1)))
+                .ln()
+                .abs();
+            let memory = (f64::from(size.memory) / f64::from(self.memory.max(1)))
+                .ln()
+                .abs();
+            cpu + memory
+        };
+        let mut sizes: Vec<TaskSize> = TaskSize::fargate_sizes()
+            .into_iter()
+            .filter(|size| min.fits_within(size) && size.fits_within(max))
+            .collect();
+        sizes.sort_by(|a, b| distance(a).total_cmp(&distance(b)));
+        sizes.truncate(count);
+        sizes
+    }
+
+    pub fn fits_within(&self, ceiling: &TaskSize) -> bool {
+        self.cpu <= ceiling.cpu && self.memory <= ceiling.memory
+    }
+
+    // The smallest valid size with more memory and at least as much cpu, if the ceiling
+    // leaves room for one.
+    pub fn next_step(&self, ceiling: &TaskSize) -> Option<TaskSize> {
+        TaskSize::fargate_sizes().into_iter().find(|size| {
+            size.memory > self.memory && size.cpu >= self.cpu && size.fits_within(ceiling)
+        })
+    }
+}
+
+impl fmt::Display for TaskSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cpu {} / memory {}", self.cpu, self.memory)
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Architecture::X86_64 => write!(f, "x86_64"),
+            Architecture::Arm64 => write!(f, "arm64"),
+        }
+    }
+}
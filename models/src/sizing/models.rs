@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Task-level cpu units and memory in MiB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TaskSize {
+    pub cpu: u32,
+    pub memory: u32,
+}
+
+impl Default for TaskSize {
+    fn default() -> Self {
+        TaskSize {
+            cpu: 256,
+            memory: 512,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Architecture {
+    #[default]
+    X86_64,
+    Arm64,
+}
+
+// Fargate's bounds on ephemeral storage, in GiB. Tasks get 20 GiB when none is requested.
+pub const EPHEMERAL_STORAGE_GIB: (u32, u32) = (21, 200);
+
+// The memory Fargate allows for each cpu value: (cpu, min memory, max memory, step).
+pub const FARGATE_MEMORY: &[(u32, u32, u32, u32)] = &[
+    (256, 512, 2048, 512),
+    (512, 1024, 4096, 1024),
+    (1024, 2048, 8192, 1024),
+    (2048, 4096, 16384, 1024),
+    (4096, 8192, 30720, 1024),
+    (8192, 16384, 61440, 4096),
+    (16384, 32768, 122880, 8192),
+];
@@ -1,13 +1,17 @@
 use crate::ecs::models::EcsTaskRepo;
 
-use super::handlers::{get_task_family, get_tasks, spawn};
+use super::{
+    handlers::{get_task_family, get_tasks, get_tasks_started_by, spawn},
+    models::AppState,
+};
 
 use axum::{routing::post, Router};
 
-pub fn router<T: EcsTaskRepo>(state: T) -> Router {
+pub fn router<T: EcsTaskRepo>(state: AppState<T>) -> Router {
     Router::new()
         .route("/spawn-worker", post(spawn::<T>))
         .route("/task-family", post(get_task_family::<T>))
         .route("/task-tag", post(get_tasks::<T>))
+        .route("/task-request", post(get_tasks_started_by::<T>))
         .with_state(state)
 }
@@ -1,7 +1,8 @@
 use axum::{extract::State, Json};
 
+use super::models::AppState;
 use crate::{
-    ecs::models::{EcsTag, EcsTaskDefinition, EcsTaskRepo, TaskFamily, TaskInfo, TaskRequest},
+    ecs::models::{EcsTag, EcsTaskRepo, RequestId, TaskFamily, TaskInfo, TaskRequest},
     errors::models::AppError,
 };
 
@@ -13,27 +14,86 @@ use crate::{
 //     Ok(Json(TaskResponse { task_id }))
 // }
 
+// Launches a worker for the request, once it is through the dispatch queue and limits.
+#[utoipa::path(
+    post,
+    path = "/spawn-worker",
+    tag = "tasks",
+    request_body = TaskRequest,
+    responses(
+        (status = 200, description = "The worker was launched", body = TaskInfo),
+        (status = 400, description = "The request is invalid or its vendor unknown", body = ErrorResponse),
+        (status = 401, description = "The API key is missing or wrong", body = ErrorResponse),
+        (status = 409, description = "A task with the same concurrency key is active", body = ErrorResponse),
+        (status = 429, description = "A quota is used up", body = ErrorResponse),
+        (status = 500, description = "ECS could not launch the worker", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn spawn<T: EcsTaskRepo>(
-    State(state): State<T>,
+    State(state): State<AppState<T>>,
     Json(task): Json<TaskRequest>,
 ) -> Result<Json<TaskInfo>, AppError> {
-    let taskdef = EcsTaskDefinition::new(task)?;
-    let res = state.spawn(taskdef).await?;
+    let res = state.submit(task).await?;
     Ok(Json(res))
 }
 
+#[utoipa::path(
+    post,
+    path = "/task-family",
+    tag = "tasks",
+    request_body = TaskFamily,
+    responses(
+        (status = 200, description = "Tasks of the family", body = Vec<TaskInfo>),
+        (status = 401, description = "The API key is missing or wrong", body = ErrorResponse),
+        (status = 500, description = "ECS could not be queried", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn get_task_family<T: EcsTaskRepo>(
-    State(state): State<T>,
+    State(state): State<AppState<T>>,
     Json(task_family): Json<TaskFamily>,
 ) -> Result<Json<Vec<TaskInfo>>, AppError> {
-    let res = state.get_task_family(task_family).await?;
+    let res = state.repo.get_task_family(task_family).await?;
     Ok(Json(res))
 }
 
+#[utoipa::path(
+    post,
+    path = "/task-tag",
+    tag = "tasks",
+    request_body = EcsTag,
+    responses(
+        (status = 200, description = "Running tasks with the tag", body = Vec<TaskInfo>),
+        (status = 401, description = "The API key is missing or wrong", body = ErrorResponse),
+        (status = 500, description = "ECS could not be queried", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
 pub async fn get_tasks<T: EcsTaskRepo>(
-    State(state): State<T>,
+    State(state): State<AppState<T>>,
     Json(tag): Json<EcsTag>,
 ) -> Result<Json<Vec<TaskInfo>>, AppError> {
-    let res = state.get_tasks(tag).await?;
+    let res = state.repo.get_tasks(tag).await?;
+    Ok(Json(res))
+}
+
+#[utoipa::path(
+    post,
+    path = "/task-request",
+    tag = "tasks",
+    request_body = RequestId,
+    responses(
+        (status = 200, description = "Running and recently stopped tasks launched by the request", body = Vec<TaskInfo>),
+        (status = 401, description = "The API key is missing or wrong", body = ErrorResponse),
+        (status = 500, description = "ECS could not be queried", body = ErrorResponse),
+    ),
+    security(("api_key" = []))
+)]
+pub async fn get_tasks_started_by<T: EcsTaskRepo>(
+    State(state): State<AppState<T>>,
+    Json(request): Json<RequestId>,
+) -> Result<Json<Vec<TaskInfo>>, AppError> {
+    let res = state.repo.started_by(&request.request_id).await?;
     Ok(Json(res))
 }
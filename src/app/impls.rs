@@ -0,0 +1,335 @@
+use serde_json::{json, Value};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::models::AppState;
+use crate::{
+    audit::{self, models::AuditAction},
+    ecs::models::{
+        EcsEnvVar, EcsTag, EcsTaskDefinition, EcsTaskRepo, TaskInfo, TaskRequest, WorkerNetworkMode,
+    },
+    errors::models::AppError,
+    jobs::models::{Attempt, Job, JobStatus},
+    logging::{
+        impls::{current_request_id, current_trace_context, trace_context_parent},
+        models::{TRACEPARENT_ENV, TRACESTATE_ENV},
+    },
+    metrics::impls::metrics,
+    secrets::models::REDACTED,
+    subnets::impls::subnet_failure,
+};
+
+impl<T: EcsTaskRepo> AppState<T> {
+    // Launches the first attempt of a new job for the request.
+    pub async fn submit(&self, tr: TaskRequest) -> Result<TaskInfo, AppError> {
+        // launch records and audits the outcome of requests that get that far.
+        let mut job = match self.prepare(&tr).await {
+            Ok(job) => job,
+            Err(err) => {
+                let result = Err(err);
+                metrics().record_spawn(&tr, &result);
+                audit::impls::record(
+                    AuditAction::Spawn,
+                    json!({ "request": audited_request(&tr) }),
+                    Vec::new(),
+                    result.as_ref().err(),
+                )
+                .await;
+                return result;
+            }
+        };
+
+        let task = self.launch(&job).await?;
+
+        job.attempts.push(Attempt::new(1, job.size, &task));
+        job.status = JobStatus::Running;
+        self.jobs.insert(job).await?;
+
+        Ok(task)
+    }
+
+    // A new job for the request, once it passed the vendor's checks.
+    async fn prepare(&self, tr: &TaskRequest) -> Result<Job, AppError> {
+        let config = self.config.current();
+        let vendor = config.vendor(&tr.vendor)?;
+        let requested = vendor.validate_resources(tr)?;
+        vendor.capacity_providers(tr)?;
+        vendor.validate_overrides(tr)?;
+        let mut job = Job {
+            trace_context: current_trace_context(),
+            request_id: current_request_id(),
+            ..Job::new(tr.clone(), requested.unwrap_or(vendor.size))
+        };
+        // Unless the request picked a size, start at the one that last worked for the soiid
+        // so it doesn't OOM its way up again.
+        if requested.is_none() {
+            if let Some(size) = self.jobs.remembered_size(&job.size_key()).await? {
+                if vendor.max_size.is_some_and(|max| size.fits_within(&max)) {
+                    job.size = size;
+                }
+            }
+        }
+        Ok(job)
+    }
+
+    // Spawns the next attempt of a job once it is through the dispatch queue and limits.
+    pub async fn launch(&self, job: &Job) -> Result<TaskInfo, AppError> {
+        let span = tracing::info_span!("launch", job_id = %job.id, attempt = job.next_attempt());
+        span.set_parent(trace_context_parent(&job.trace_context));
+        let result = self.try_launch(job).instrument(span).await;
+        metrics().record_spawn(&job.request, &result);
+        audit::impls::record(
+            AuditAction::Spawn,
+            json!({
+                "job_id": job.id,
+                "attempt": job.next_attempt(),
+                "request": audited_request(&job.request),
+            }),
+            result.iter().map(|task| task.task_arn.clone()).collect(),
+            result.as_ref().err(),
+        )
+        .await;
+        result
+    }
+
+    async fn try_launch(&self, job: &Job) -> Result<TaskInfo, AppError> {
+        let tr = &job.request;
+        let config = self.config.current();
+        let vendor = config.vendor(&tr.vendor)?;
+
+        // Waiting for a key or quota can take minutes, so it happens before the spawn takes
+        // one of the dispatcher's in-flight slots.
+        let key = self.concurrency.acquire(&self.repo, tr, vendor).await?;
+        let _permit = self.quotas.acquire(&self.repo, tr).await?;
+        let mut ticket = self.dispatcher.enqueue(tr, job.request_id.as_deref());
+        ticket.dispatched().await?;
+
+        let mut taskdef = EcsTaskDefinition::new(tr.clone(), &config)?;
+        taskdef.size = job.size;
+        if job.on_demand {
+            taskdef.capacity_providers.clear();
+        }
+        taskdef.tags.push(EcsTag {
+            key: "job_id".to_string(),
+            value: job.id.clone(),
+        });
+        taskdef.tags.push(EcsTag {
+            key: "attempt".to_string(),
+            value: job.next_attempt().to_string(),
+        });
+        // Relaunches are started by the request that submitted the job too.
+        if let Some(request_id) = &job.request_id {
+            taskdef.started_by = Some(request_id.clone());
+            taskdef.tags.push(EcsTag {
+                key: "request_id".to_string(),
+                value: request_id.clone(),
+            });
+        }
+        // The worker's spans go under this launch. tracestate can hold commas, which tags
+        // can't, so only traceparent is tagged.
+        let trace_context = current_trace_context();
+        if let Some(traceparent) = trace_context.get("traceparent") {
+            taskdef.tags.push(EcsTag {
+                key: "traceparent".to_string(),
+                value: traceparent.clone(),
+            });
+        }
+        for (header, name) in [
+            ("traceparent", TRACEPARENT_ENV),
+            ("tracestate", TRACESTATE_ENV),
+        ] {
+            if let Some(value) = trace_context.get(header).filter(|value| !value.is_empty()) {
+                taskdef.env_vars.push(EcsEnvVar {
+                    name: name.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+
+        let task = self.spawn(job, taskdef).await?;
+        key.settle(&self.repo, &task, vendor.conflict_timeout_secs)
+            .await;
+        Ok(task)
+    }
+
+    async fn spawn(&self, job: &Job, mut taskdef: EcsTaskDefinition) -> Result<TaskInfo, AppError> {
+        // Bridge and host tasks use the instance's network, so there is no subnet to pick.
+        if taskdef.network_mode != WorkerNetworkMode::Awsvpc {
+            let mut task = self.repo.spawn(taskdef).await?;
+            task.job_id = Some(job.id.clone());
+            return Ok(task);
+        }
+
+        // Move on to the next subnet when one is out of IPs/ENIs or its AZ is out of capacity.
+        let mut last_err = None;
+        for subnet_id in self.subnets.candidates() {
+            taskdef.subnet_id = subnet_id.clone();
+            match self.repo.spawn(taskdef.clone()).await {
+                Ok(mut task) => {
+                    self.subnets.record_success(&subnet_id);
+                    task.job_id = Some(job.id.clone());
+                    return Ok(task);
+                }
+                Err(err) => {
+                    let Some(reason) = subnet_failure(&err) else {
+                        return Err(err);
+                    };
+                    tracing::warn!("launch in {} failed: {}", subnet_id, reason);
+                    self.subnets.record_failure(&subnet_id, &reason);
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            AppError::InternalServerError("No subnets are configured".to_string())
+        }))
+    }
+}
+
+// The request as the audit log keeps it. Env values are often credentials, so only their
+// names are kept.
+fn audited_request(tr: &TaskRequest) -> Value {
+    let mut request = json!(tr);
+    if let Some(env) = request.get_mut("env").and_then(Value::as_array_mut) {
+        for envvar in env.iter_mut() {
+            envvar["value"] = json!(REDACTED);
+        }
+    }
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use aws_config::{BehaviorVersion, SdkConfig};
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::{
+        app::models::AppState,
+        config::models::AppConfig,
+        dispatch::models::Dispatcher,
+        ecs::fake::FakeRepo,
+        jobs::models::InMemoryJobStore,
+        limits::models::{ConcurrencyManager, QuotaManager},
+        ssm::models::{ConfigHandle, ParameterResolver},
+        subnets::models::SubnetPool,
+    };
+
+    fn state(repo: FakeRepo, config: Value) -> AppState<FakeRepo> {
+        let mut cfg = json!({ "api_key": "key", "log_level": "info" });
+        cfg.as_object_mut()
+            .unwrap()
+            .extend(config.as_object().unwrap().clone());
+        let cfg: AppConfig = serde_json::from_value(cfg).unwrap();
+
+        let sdk_config = SdkConfig::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .build();
+        let (sender, _) = watch::channel(Arc::new(cfg.clone()));
+        let dispatcher = Dispatcher::new(cfg.dispatch.clone());
+        dispatcher.start();
+        AppState {
+            repo,
+            config: ConfigHandle {
+                sender: Arc::new(sender),
+                resolver: ParameterResolver::new(&sdk_config, None),
+                refresh_interval: Duration::ZERO,
+            },
+            quotas: QuotaManager::new(cfg.quotas.clone()),
+            concurrency: ConcurrencyManager::new(),
+            dispatcher,
+            jobs: Arc::new(InMemoryJobStore::default()),
+            subnets: SubnetPool::new(&cfg.ecs),
+        }
+    }
+
+    fn request(clientid: &str, soiid: &str) -> TaskRequest {
+        serde_json::from_value(json!({
+            "data_location": "s3://bucket/key",
+            "soiid": soiid,
+            "clientid": clientid,
+            "vendor": "bloomberg",
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_spawn_right_after_another_sees_it_although_listing_lags() {
+        let repo = FakeRepo {
+            list_lag: Duration::from_secs(3),
+            ..Default::default()
+        };
+        let state = state(
+            repo.clone(),
+            json!({ "vendors": { "bloomberg": {
+                "image": "public.ecr.aws/soi/worker:latest",
+                "on_conflict": "reject",
+            } } }),
+        );
+
+        let first = state.submit(request("client-1", "soi-1")).await.unwrap();
+        assert!(matches!(
+            state.submit(request("client-1", "soi-1")).await,
+            Err(AppError::ConcurrencyConflictError(_))
+        ));
+        let active = repo.list_active(&[]).await.unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].task_arn, first.task_arn);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_spawn_waiting_for_its_key_leaves_the_dispatcher_to_others() {
+        let repo = FakeRepo::default();
+        repo.run("arn-0", &[("concurrency_key", "bloomberg:soi-1")]);
+        let state = state(
+            repo,
+            json!({
+                "dispatch": { "max_in_flight": 1 },
+                "vendors": { "bloomberg": {
+                    "image": "public.ecr.aws/soi/worker:latest",
+                    "on_conflict": "queue",
+                } },
+            }),
+        );
+
+        let blocked = {
+            let state = state.clone();
+            tokio::spawn(async move { state.submit(request("client-1", "soi-1")).await })
+        };
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let started = tokio::time::Instant::now();
+        assert!(state.submit(request("client-2", "soi-2")).await.is_ok());
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert!(!blocked.is_finished());
+        assert_eq!(state.dispatcher.status().in_flight, 0);
+    }
+
+    #[test]
+    fn keeps_env_values_out_of_the_audit_log() {
+        let tr: TaskRequest = serde_json::from_value(json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+            "env": [
+                { "name": "API_TOKEN", "value": "hunter2" },
+                { "name": "BATCH_SIZE", "value": "10" },
+            ],
+        }))
+        .unwrap();
+
+        let request = audited_request(&tr);
+        assert_eq!(
+            request["env"],
+            json!([
+                { "name": "API_TOKEN", "value": REDACTED },
+                { "name": "BATCH_SIZE", "value": REDACTED },
+            ])
+        );
+        assert_eq!(request["soiid"], "soi-1");
+    }
+}
@@ -1,3 +1,4 @@
 pub mod api;
 pub mod handlers;
+pub mod impls;
 pub mod models;
@@ -9,3 +9,23 @@
 // pub struct TaskResponse {
 //     pub task_id: String,
 // }
+
+use crate::{
+    dispatch::models::Dispatcher,
+    ecs::models::EcsTaskRepo,
+    jobs::models::ArcJobStore,
+    limits::models::{ConcurrencyManager, QuotaManager},
+    ssm::models::ConfigHandle,
+    subnets::models::SubnetPool,
+};
+
+#[derive(Clone)]
+pub struct AppState<T: EcsTaskRepo> {
+    pub repo: T,
+    pub config: ConfigHandle,
+    pub quotas: QuotaManager,
+    pub concurrency: ConcurrencyManager,
+    pub dispatcher: Dispatcher,
+    pub jobs: ArcJobStore,
+    pub subnets: SubnetPool,
+}
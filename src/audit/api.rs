@@ -0,0 +1,9 @@
+use super::{handlers::get_audit, models::ArcAuditSink};
+
+use axum::{routing::get, Router};
+
+pub fn router(sink: ArcAuditSink) -> Router {
+    Router::new()
+        .route("/admin/audit", get(get_audit))
+        .with_state(sink)
+}
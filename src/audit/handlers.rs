@@ -0,0 +1,14 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use super::models::{ArcAuditSink, AuditQuery, AuditRecord};
+use crate::errors::models::AppError;
+
+pub async fn get_audit(
+    State(sink): State<ArcAuditSink>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditRecord>>, AppError> {
+    Ok(Json(sink.query(&query).await?))
+}
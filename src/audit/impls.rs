@@ -0,0 +1,362 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde_json::Value;
+use uuid::Uuid;
+
+use super::models::{
+    ArcAuditSink, AuditAction, AuditOutcome, AuditQuery, AuditRecord, AuditSink, JsonLinesFile,
+    JsonLinesSink, AUDIT, PRINCIPAL, SYSTEM_PRINCIPAL,
+};
+use crate::{
+    config::models::AuditConfig, errors::models::AppError, logging::impls::current_request_id,
+    secrets::impls::redact,
+};
+
+// Sends every record from then on to the sink.
+pub fn install(sink: ArcAuditSink) -> Result<(), AppError> {
+    AUDIT.set(sink).map_err(|_| {
+        AppError::InternalServerError("the audit sink is already installed".to_string())
+    })
+}
+
+// Who the current request acts for, or the spawner itself outside of requests.
+pub fn current_principal() -> String {
+    PRINCIPAL
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| SYSTEM_PRINCIPAL.to_string())
+}
+
+// Records an action of the current principal. The operation has already happened, so a
+// record that can't be written is logged rather than failing it.
+pub async fn record(
+    action: AuditAction,
+    payload: Value,
+    task_arns: Vec<String>,
+    error: Option<&AppError>,
+) {
+    let record = audit_record(action, payload, task_arns, error);
+    let Some(sink) = AUDIT.get() else {
+        return;
+    };
+    if let Err(err) = sink.append(&record).await {
+        tracing::error!(
+            "could not write audit record {}: {:?}",
+            serde_json::to_string(&record).unwrap_or_default(),
+            err
+        );
+    }
+}
+
+// A record of an action of the current principal.
+pub fn audit_record(
+    action: AuditAction,
+    payload: Value,
+    task_arns: Vec<String>,
+    error: Option<&AppError>,
+) -> AuditRecord {
+    AuditRecord {
+        id: Uuid::new_v4().to_string(),
+        at: Utc::now(),
+        principal: current_principal(),
+        request_id: current_request_id(),
+        action,
+        payload: redact_value(payload),
+        outcome: match error {
+            Some(_) => AuditOutcome::Failed,
+            None => AuditOutcome::Succeeded,
+        },
+        error: error.map(|err| redact(&err.to_string())),
+        task_arns,
+    }
+}
+
+fn redact_value(value: Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(redact(&text)),
+        Value::Array(values) => Value::Array(values.into_iter().map(redact_value).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key, redact_value(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+impl JsonLinesSink {
+    pub fn open(cfg: &AuditConfig) -> Result<Arc<Self>, AppError> {
+        let path = PathBuf::from(&cfg.path);
+        let file = open_append(&path).map_err(|err| {
+            AppError::InternalServerError(format!(
+                "could not open the audit log {}: {}",
+                path.display(),
+                err
+            ))
+        })?;
+
+        Ok(Arc::new(JsonLinesSink {
+            path,
+            max_file_bytes: cfg.max_file_bytes,
+            max_files: cfg.max_files,
+            file: Arc::new(Mutex::new(file)),
+        }))
+    }
+
+    fn write(&self, line: &[u8]) -> io::Result<()> {
+        let mut current = self.file.lock().unwrap();
+        if current.len > 0 && current.len + line.len() as u64 > self.max_file_bytes {
+            self.rotate(&mut current)?;
+        }
+        current.file.write_all(line)?;
+        current.len += line.len() as u64;
+        Ok(())
+    }
+
+    // Most recent first. Only opening the files holds up writers: an open file can still be
+    // read once it is rotated or deleted, and the current one is read up to where it was.
+    fn read(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, AppError> {
+        let mut files = Vec::new();
+        {
+            let current = self.file.lock().unwrap();
+            for path in self.rotated().map_err(io_error)? {
+                let file = File::open(&path).map_err(io_error)?;
+                files.push((path, file.take(u64::MAX)));
+            }
+            let file = File::open(&self.path).map_err(io_error)?;
+            files.push((self.path.clone(), file.take(current.len)));
+        }
+
+        let limit = query.limit();
+        let mut records = Vec::new();
+        for (path, file) in files.into_iter().rev() {
+            let lines: Vec<String> = BufReader::new(file)
+                .lines()
+                .collect::<Result<_, _>>()
+                .map_err(io_error)?;
+            for line in lines.iter().rev() {
+                let record: AuditRecord = serde_json::from_str(line).map_err(|err| {
+                    AppError::InternalServerError(format!(
+                        "unreadable audit record in {}: {}",
+                        path.display(),
+                        err
+                    ))
+                })?;
+                if query.matches(&record) {
+                    records.push(record);
+                    if records.len() == limit {
+                        return Ok(records);
+                    }
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn rotate(&self, current: &mut JsonLinesFile) -> io::Result<()> {
+        // Named after when they were rotated, never overwriting an earlier one.
+        let mut rotated;
+        loop {
+            rotated = self.sibling(&Utc::now().format("%Y%m%dT%H%M%S%.9f").to_string());
+            if !rotated.exists() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_micros(1));
+        }
+        fs::rename(&self.path, rotated)?;
+        *current = open_append(&self.path)?;
+
+        if let Some(max_files) = self.max_files {
+            let rotated = self.rotated()?;
+            for path in rotated.iter().take(rotated.len().saturating_sub(max_files)) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // `<stem>.<suffix>.<extension>` in the directory of the current file.
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, suffix, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, suffix),
+        };
+        self.path.with_file_name(name)
+    }
+
+    // Rotated files, oldest first.
+    fn rotated(&self) -> io::Result<Vec<PathBuf>> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let prefix = format!("{}.", stem);
+        let suffix = self
+            .path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+        let current = self.path.file_name().unwrap_or_default();
+
+        let mut rotated: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                let Some(name) = path.file_name() else {
+                    return false;
+                };
+                let name_str = name.to_string_lossy();
+                name != current && name_str.starts_with(&prefix) && name_str.ends_with(&suffix)
+            })
+            .collect();
+        // The timestamps sort in the order the files were rotated.
+        rotated.sort();
+        Ok(rotated)
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<JsonLinesFile> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let len = file.metadata()?.len();
+    Ok(JsonLinesFile { file, len })
+}
+
+fn io_error(err: io::Error) -> AppError {
+    AppError::InternalServerError(format!("audit log: {}", err))
+}
+
+async fn blocking<R: Send + 'static>(
+    work: impl FnOnce() -> Result<R, AppError> + Send + 'static,
+) -> Result<R, AppError> {
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|err| AppError::InternalServerError(format!("audit log: {}", err)))?
+}
+
+#[async_trait]
+impl AuditSink for JsonLinesSink {
+    async fn append(&self, record: &AuditRecord) -> Result<(), AppError> {
+        let mut line = serde_json::to_vec(record)
+            .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+        line.push(b'\n');
+
+        let sink = self.clone();
+        blocking(move || sink.write(&line).map_err(io_error)).await
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, AppError> {
+        let (sink, query) = (self.clone(), query.clone());
+        blocking(move || sink.read(&query)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn rotates_and_queries_across_files() {
+        let dir = std::env::temp_dir().join(format!("audit-{}", Uuid::new_v4()));
+        let sink = JsonLinesSink::open(&AuditConfig {
+            path: dir.join("audit.jsonl").to_string_lossy().to_string(),
+            max_file_bytes: 1,
+            max_files: Some(2),
+        })
+        .unwrap();
+
+        for number in 0..4 {
+            let record = PRINCIPAL
+                .scope("api_key".to_string(), async {
+                    audit_record(
+                        AuditAction::Spawn,
+                        json!({ "attempt": number }),
+                        vec![format!("arn-{}", number)],
+                        None,
+                    )
+                })
+                .await;
+            sink.append(&record).await.unwrap();
+        }
+        sink.append(&audit_record(
+            AuditAction::Stop,
+            json!({}),
+            vec!["arn-3".to_string()],
+            Some(&AppError::CustomError("denied".to_string())),
+        ))
+        .await
+        .unwrap();
+
+        // Every record went to a file of its own, and only two rotated files are kept.
+        assert_eq!(sink.rotated().unwrap().len(), 2);
+        let spawns = sink
+            .query(&AuditQuery {
+                action: Some(AuditAction::Spawn),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let arns: Vec<&str> = spawns
+            .iter()
+            .map(|record| record.task_arns[0].as_str())
+            .collect();
+        assert_eq!(arns, ["arn-3", "arn-2"]);
+        assert_eq!(spawns[0].principal, "api_key");
+
+        let stops = sink
+            .query(&AuditQuery {
+                task_arn: Some("arn-3".to_string()),
+                principal: Some(SYSTEM_PRINCIPAL.to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(stops.len(), 1);
+        assert_eq!(stops[0].outcome, AuditOutcome::Failed);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reads_the_current_file_up_to_the_last_whole_record() {
+        let dir = std::env::temp_dir().join(format!("audit-{}", Uuid::new_v4()));
+        let path = dir.join("audit.jsonl");
+        let sink = JsonLinesSink::open(&AuditConfig {
+            path: path.to_string_lossy().to_string(),
+            max_file_bytes: 1 << 20,
+            max_files: None,
+        })
+        .unwrap();
+        sink.append(&audit_record(
+            AuditAction::Spawn,
+            json!({}),
+            Vec::new(),
+            None,
+        ))
+        .await
+        .unwrap();
+
+        // A record another append is halfway through writing.
+        open_append(&path)
+            .unwrap()
+            .file
+            .write_all(b"{\"id\":")
+            .unwrap();
+        let records = sink.query(&AuditQuery::default()).await.unwrap();
+        assert_eq!(records.len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,52 @@
+use std::{
+    fs::File,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use async_trait::async_trait;
+pub use ecs_task_spawner_models::audit::models::{
+    AuditAction, AuditOutcome, AuditQuery, AuditRecord,
+};
+
+use crate::errors::models::AppError;
+
+// Principal of callers that authenticated with the shared api_key.
+pub const API_KEY_PRINCIPAL: &str = "api_key";
+// Principal of what the spawner does on its own, e.g. relaunches and config refreshes.
+pub const SYSTEM_PRINCIPAL: &str = "spawner";
+
+tokio::task_local! {
+    // Who the request being handled acts for, set once it has been authenticated.
+    pub static PRINCIPAL: String;
+}
+
+// The sink every record goes to. Until it is installed, e.g. in tests, records are dropped.
+pub static AUDIT: OnceLock<ArcAuditSink> = OnceLock::new();
+
+// Where audit records are kept. Records are only ever appended.
+#[async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn append(&self, record: &AuditRecord) -> Result<(), AppError>;
+    // The most recent records matching the query, newest first.
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, AppError>;
+}
+
+pub type ArcAuditSink = Arc<dyn AuditSink>;
+
+// One record per line in `path`. Once it reaches max_file_bytes it is renamed to
+// `<stem>.<timestamp>.<extension>` next to it and a new file is started. The file work runs
+// on the blocking pool.
+#[derive(Clone)]
+pub struct JsonLinesSink {
+    pub path: PathBuf,
+    pub max_file_bytes: u64,
+    // Rotated files kept, oldest deleted first. All of them when unset.
+    pub max_files: Option<usize>,
+    pub file: Arc<Mutex<JsonLinesFile>>,
+}
+
+pub struct JsonLinesFile {
+    pub file: File,
+    pub len: u64,
+}
@@ -3,26 +3,42 @@ use axum::{body::Body, extract::Request, middleware::Next, response::Response};
 use axum_extra::headers::{authorization::Bearer, Authorization};
 use axum_extra::TypedHeader;
 use std::sync::Arc;
+use tokio::sync::watch;
 
-use crate::errors::models::AppError;
+use super::models::{Caller, ADMIN_PATH_PREFIX};
+use crate::{audit::models::PRINCIPAL, config::models::AppConfig, errors::models::AppError};
 
+// Checks against the current config, so a rotated key is accepted as soon as it is loaded
+// and the old one stops working.
 pub async fn auth(
     req: Request<Body>,
     next: Next,
-    token: Arc<String>,
+    config: watch::Receiver<Arc<AppConfig>>,
 ) -> Result<Response, AppError> {
     let (mut parts, body) = req.into_parts();
     let auth_header =
         TypedHeader::<Authorization<Bearer>>::from_request_parts(&mut parts, &()).await;
 
-    match auth_header {
-        Ok(TypedHeader(Authorization(bearer))) if bearer.token() == token.as_str() => {
-            // Reconstruct the request and pass it to the next service
-            let req = Request::from_parts(parts, body);
-            Ok(next.run(req).await)
+    let caller = match auth_header {
+        Ok(TypedHeader(Authorization(bearer))) => {
+            Caller::authenticate(&config.borrow(), bearer.token())
         }
-        _ => Err(AppError::UnauthorizedError(
+        Err(_) => None,
+    };
+    let Some(caller) = caller else {
+        return Err(AppError::UnauthorizedError(
             "Invalid token provided.".to_string(),
-        )),
+        ));
+    };
+    if parts.uri.path().starts_with(ADMIN_PATH_PREFIX) && !caller.admin {
+        return Err(AppError::ForbiddenError(format!(
+            "{} needs an admin API key",
+            parts.uri.path()
+        )));
     }
+
+    // Reconstruct the request and pass it to the next service, acting for the key's holder
+    // so what it changes is audited under them.
+    let req = Request::from_parts(parts, body);
+    Ok(PRINCIPAL.scope(caller.principal, next.run(req)).await)
 }
@@ -0,0 +1,125 @@
+use super::models::Caller;
+use crate::{audit::models::API_KEY_PRINCIPAL, config::models::AppConfig};
+
+impl Caller {
+    // The holder of a bearer token, if it is one of the configured keys.
+    pub fn authenticate(cfg: &AppConfig, token: &str) -> Option<Caller> {
+        if token == cfg.api_key {
+            return Some(Caller {
+                principal: API_KEY_PRINCIPAL.to_string(),
+                admin: false,
+            });
+        }
+        cfg.api_keys
+            .iter()
+            .find(|api_key| api_key.key == token)
+            .map(|api_key| Caller {
+                principal: api_key.id.clone(),
+                admin: api_key.admin,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        middleware,
+        routing::get,
+        Router,
+    };
+    use serde_json::json;
+    use tokio::sync::watch;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{audit::models::PRINCIPAL, auth::api::auth};
+
+    #[test]
+    fn tells_callers_apart_by_their_key() {
+        let cfg: AppConfig = serde_json::from_value(json!({
+            "api_key": "shared",
+            "log_level": "info",
+            "api_keys": [
+                { "id": "ops", "key": "ops-key", "admin": true },
+                { "id": "client-1", "key": "client-1-key" },
+            ],
+        }))
+        .unwrap();
+        let caller = |token: &str| {
+            Caller::authenticate(&cfg, token).map(|caller| (caller.principal, caller.admin))
+        };
+
+        assert_eq!(caller("shared"), Some(("api_key".to_string(), false)));
+        assert_eq!(caller("ops-key"), Some(("ops".to_string(), true)));
+        assert_eq!(
+            caller("client-1-key"),
+            Some(("client-1".to_string(), false))
+        );
+        assert_eq!(caller("ops"), None);
+        assert_eq!(caller(""), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_admin_routes_to_admin_keys_and_follows_rotations() {
+        let cfg = |admin_key: &str| -> Arc<AppConfig> {
+            Arc::new(
+                serde_json::from_value(json!({
+                    "api_key": "shared",
+                    "log_level": "info",
+                    "api_keys": [{ "id": "ops", "key": admin_key, "admin": true }],
+                }))
+                .unwrap(),
+            )
+        };
+        let (sender, updates) = watch::channel(cfg("ops-key"));
+        let principal = || async { PRINCIPAL.with(|principal| principal.clone()) };
+        let app = Router::new()
+            .route("/tasks", get(principal))
+            .route("/admin/queue", get(principal))
+            .layer(middleware::from_fn(move |req, next| {
+                auth(req, next, updates.clone())
+            }));
+        let call = |path: &str, token: &str| {
+            let request = Request::get(path)
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, String::from_utf8_lossy(&body).to_string())
+            }
+        };
+
+        assert_eq!(
+            call("/tasks", "shared").await,
+            (StatusCode::OK, "api_key".to_string())
+        );
+        assert_eq!(
+            call("/admin/queue", "ops-key").await,
+            (StatusCode::OK, "ops".to_string())
+        );
+        assert_eq!(
+            call("/admin/queue", "shared").await.0,
+            StatusCode::FORBIDDEN
+        );
+
+        sender.send(cfg("rotated")).unwrap();
+        assert_eq!(
+            call("/admin/queue", "ops-key").await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            call("/admin/queue", "rotated").await,
+            (StatusCode::OK, "ops".to_string())
+        );
+    }
+}
@@ -1 +1,3 @@
 pub mod api;
+pub mod impls;
+pub mod models;
@@ -0,0 +1,10 @@
+// Routes under it need an admin API key.
+pub const ADMIN_PATH_PREFIX: &str = "/admin/";
+
+// Who a request was authenticated as.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller {
+    // Recorded in the audit log for what the caller does.
+    pub principal: String,
+    pub admin: bool,
+}
@@ -0,0 +1,1489 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use config::ConfigError;
+use serde_json::Value;
+
+use super::models::{AppConfig, DispatchConfig, RetryPolicy, VendorConfig};
+use crate::{
+    audit::models::{API_KEY_PRINCIPAL, SYSTEM_PRINCIPAL},
+    ecs::models::{
+        CapacityProviderItem, ContainerDependencyCondition, HealthCheckConfig, LogOption,
+        LogRouting, PlacementConstraintKind, PlacementStrategyKind, PortMappingItem, TaskRequest,
+        WorkerLaunchType, WorkerNetworkMode, FARGATE, FARGATE_SPOT, LOG_ROUTER_CONTAINER,
+    },
+    errors::models::AppError,
+    secrets::models::SecretSource,
+    sizing::models::{TaskSize, EPHEMERAL_STORAGE_GIB},
+};
+
+impl AppConfig {
+    pub fn vendor(&self, vendor: &str) -> Result<&VendorConfig, AppError> {
+        self.vendors
+            .get(vendor)
+            .ok_or_else(|| AppError::UnsupportedVendor(vendor.to_string()))
+    }
+
+    // Catches settings ECS would only reject once a task is launched.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (name, vendor) in self.vendors.iter() {
+            vendor
+                .validate()
+                .map_err(|err| ConfigError::Message(format!("vendors.{}: {}", name, err)))?;
+        }
+        if self.dispatch.max_in_flight == 0 {
+            return Err(ConfigError::Message(
+                "dispatch.max_in_flight must be at least 1".to_string(),
+            ));
+        }
+        self.validate_api_keys().map_err(ConfigError::Message)
+    }
+
+    fn validate_api_keys(&self) -> Result<(), String> {
+        let mut ids = vec![API_KEY_PRINCIPAL, SYSTEM_PRINCIPAL];
+        let mut keys = vec![self.api_key.as_str()];
+        for api_key in self.api_keys.iter() {
+            if api_key.id.trim().is_empty() || ids.contains(&api_key.id.as_str()) {
+                return Err(format!(
+                    "{:?} can't be used as the id of an API key",
+                    api_key.id
+                ));
+            }
+            ids.push(&api_key.id);
+            // Keys aren't named, they may be secrets.
+            if api_key.key.is_empty() || keys.contains(&api_key.key.as_str()) {
+                return Err(format!("API key {} needs a key of its own", api_key.id));
+            }
+            keys.push(&api_key.key);
+        }
+        Ok(())
+    }
+
+    // Paths of the settings that differ from `other`, e.g. "vendors.bloomberg.size.cpu".
+    // Only the paths, the values may be secrets.
+    pub fn changed_settings(&self, other: &AppConfig) -> Vec<String> {
+        let mut changed = Vec::new();
+        match (serde_json::to_value(self), serde_json::to_value(other)) {
+            (Ok(current), Ok(other)) => diff(String::new(), &current, &other, &mut changed),
+            _ => changed.push("*".to_string()),
+        }
+        changed.sort();
+        changed
+    }
+}
+
+fn diff(path: String, current: &Value, other: &Value, changed: &mut Vec<String>) {
+    let (Value::Object(current), Value::Object(other)) = (current, other) else {
+        if current != other {
+            changed.push(path);
+        }
+        return;
+    };
+    let keys: BTreeSet<&String> = current.keys().chain(other.keys()).collect();
+    for key in keys {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", path, key)
+        };
+        match (current.get(key), other.get(key)) {
+            (Some(current), Some(other)) => diff(path, current, other, changed),
+            _ => changed.push(path),
+        }
+    }
+}
+
+impl VendorConfig {
+    fn validate(&self) -> Result<(), String> {
+        match self.launch_type {
+            WorkerLaunchType::Fargate => {
+                if self.network_mode != WorkerNetworkMode::Awsvpc {
+                    return Err("Fargate workers only support the awsvpc network mode".to_string());
+                }
+                if !self.placement_constraints.is_empty() || !self.placement_strategies.is_empty() {
+                    return Err(
+                        "placement constraints and strategies only apply to EC2 workers"
+                            .to_string(),
+                    );
+                }
+            }
+            WorkerLaunchType::Ec2 => {
+                if !self.capacity_providers.is_empty() {
+                    return Err(
+                        "capacity providers are Fargate only and can't be used with EC2 workers"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        // ECS limits from the RunTask API.
+        if self.placement_constraints.len() > 10 {
+            return Err("at most 10 placement constraints are allowed".to_string());
+        }
+        if self.placement_strategies.len() > 5 {
+            return Err("at most 5 placement strategies are allowed".to_string());
+        }
+
+        for constraint in self.placement_constraints.iter() {
+            let expression = constraint.expression.as_deref().unwrap_or_default();
+            match constraint.kind {
+                PlacementConstraintKind::MemberOf if expression.trim().is_empty() => {
+                    return Err("memberOf placement constraints need an expression".to_string());
+                }
+                PlacementConstraintKind::DistinctInstance if !expression.is_empty() => {
+                    return Err(
+                        "distinctInstance placement constraints take no expression".to_string()
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        for strategy in self.placement_strategies.iter() {
+            let field = strategy.field.as_deref().unwrap_or_default();
+            match strategy.kind {
+                PlacementStrategyKind::Binpack if field != "cpu" && field != "memory" => {
+                    return Err(format!(
+                        "binpack placement strategies need a field of cpu or memory, got {:?}",
+                        field
+                    ));
+                }
+                PlacementStrategyKind::Spread if field.is_empty() => {
+                    return Err(
+                        "spread placement strategies need a field, e.g. instanceId".to_string()
+                    );
+                }
+                PlacementStrategyKind::Random if !field.is_empty() => {
+                    return Err("random placement strategies take no field".to_string());
+                }
+                _ => {}
+            }
+        }
+
+        // Architecture is fixed by the task definition, so an outside one can't offer a choice.
+        if self.task_definition.is_some() && self.architectures.len() > 1 {
+            return Err(
+                "a vendor with its own task_definition can only list one architecture".to_string(),
+            );
+        }
+
+        if self.launch_type == WorkerLaunchType::Fargate && !self.size.is_valid_fargate() {
+            return Err(format!("{} is not a valid Fargate size", self.size));
+        }
+
+        self.validate_logging()?;
+        self.validate_secrets()?;
+        self.validate_containers()
+    }
+
+    fn validate_secrets(&self) -> Result<(), String> {
+        if self.task_definition.is_some() && !self.secrets.is_empty() {
+            return Err("secrets can't be added to a vendor's own task_definition".to_string());
+        }
+
+        let mut names = Vec::new();
+        for secret in self.secrets.iter() {
+            if !valid_env_name(&secret.name) || secret.name.starts_with("APP_") {
+                return Err(format!(
+                    "{:?} can't be used as the name of a secret",
+                    secret.name
+                ));
+            }
+            if names.contains(&&secret.name) {
+                return Err(format!("secret {} is declared twice", secret.name));
+            }
+            names.push(&secret.name);
+            SecretSource::parse(&secret.value_from)
+                .map_err(|err| format!("secret {}: {}", secret.name, err))?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_logging(&self) -> Result<(), String> {
+        // Only per-vendor placeholders, since every rendering registers a task definition of
+        // its own. Streams end in the task id, and tasks are tagged with their clientid.
+        let templates: Vec<&str> = match &self.logging {
+            LogRouting::Awslogs { stream_prefix, .. } => vec![stream_prefix.as_str()],
+            LogRouting::Firelens { options, .. } | LogRouting::Splunk { options } => {
+                options.iter().map(|option| option.value.as_str()).collect()
+            }
+            LogRouting::None => Vec::new(),
+        };
+        for template in templates {
+            if template.replace("{vendor}", "").contains('{') {
+                return Err(format!(
+                    "log setting {:?} can only use the {{vendor}} placeholder",
+                    template
+                ));
+            }
+        }
+
+        match &self.logging {
+            LogRouting::Awslogs { stream_prefix, .. } => {
+                if stream_prefix.is_empty() || stream_prefix.contains([':', '*']) {
+                    return Err(format!(
+                        "awslogs stream prefix {:?} can't be empty or contain : or *",
+                        stream_prefix
+                    ));
+                }
+            }
+            LogRouting::Firelens { options, .. } => {
+                if !has_option(options, "Name") {
+                    return Err(
+                        "FireLens logging needs a Name option naming the Fluent Bit output"
+                            .to_string(),
+                    );
+                }
+            }
+            LogRouting::Splunk { options } => {
+                if !has_option(options, "splunk-url") {
+                    return Err("splunk logging needs a splunk-url option".to_string());
+                }
+            }
+            LogRouting::None => {}
+        }
+
+        Ok(())
+    }
+
+    fn validate_containers(&self) -> Result<(), String> {
+        if self.task_definition.is_some() && !self.sidecars.is_empty() {
+            return Err("sidecars can't be added to a vendor's own task_definition".to_string());
+        }
+
+        let router_memory = match &self.logging {
+            LogRouting::Firelens { router_memory, .. } => *router_memory,
+            _ => 0,
+        };
+        // ECS allows up to 255 letters, numbers, hyphens and underscores.
+        let worker_name = self.worker.name.as_str();
+        if worker_name.is_empty()
+            || worker_name.len() > 255
+            || !worker_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "{:?} can't be used as the name of the worker container",
+                worker_name
+            ));
+        }
+
+        let mut names = vec![worker_name];
+        if matches!(self.logging, LogRouting::Firelens { .. }) {
+            names.push(LOG_ROUTER_CONTAINER);
+        }
+        for sidecar in self.sidecars.iter() {
+            if names.contains(&sidecar.name.as_str()) {
+                return Err(format!("container name {} is used twice", sidecar.name));
+            }
+            names.push(&sidecar.name);
+        }
+
+        // (name, essential, depends_on, health_check, port_mappings) of every container.
+        let mut containers = vec![(
+            worker_name,
+            true,
+            &self.worker.depends_on,
+            &self.worker.health_check,
+            &self.worker.port_mappings,
+        )];
+        containers.extend(self.sidecars.iter().map(|sidecar| {
+            (
+                sidecar.name.as_str(),
+                sidecar.essential,
+                &sidecar.depends_on,
+                &sidecar.health_check,
+                &sidecar.port_mappings,
+            )
+        }));
+
+        for (name, _, depends_on, health_check, port_mappings) in containers.iter() {
+            for dependency in depends_on.iter() {
+                let Some((_, essential, _, health_check, _)) = containers
+                    .iter()
+                    .find(|container| container.0 == dependency.container)
+                else {
+                    return Err(format!(
+                        "{} depends on unknown container {}",
+                        name, dependency.container
+                    ));
+                };
+                match dependency.condition {
+                    ContainerDependencyCondition::Healthy if health_check.is_none() => {
+                        return Err(format!(
+                            "{} waits for {} to be HEALTHY, but it has no health check",
+                            name, dependency.container
+                        ));
+                    }
+                    ContainerDependencyCondition::Complete
+                    | ContainerDependencyCondition::Success
+                        if *essential =>
+                    {
+                        return Err(format!(
+                            "{} waits for {} to exit, but it is essential",
+                            name, dependency.container
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(health_check) = health_check {
+                validate_health_check(name, health_check)?;
+            }
+            validate_port_mappings(name, port_mappings, self.network_mode)?;
+        }
+
+        // A cycle means ECS would never start any of the containers in it.
+        for (name, ..) in containers.iter() {
+            let mut seen = Vec::new();
+            let mut pending = vec![*name];
+            while let Some(current) = pending.pop() {
+                let next = containers
+                    .iter()
+                    .filter(|container| container.0 == current)
+                    .flat_map(|container| container.2.iter())
+                    .map(|dependency| dependency.container.as_str());
+                for dependency in next {
+                    if dependency == *name {
+                        return Err(format!(
+                            "{} depends on itself through its dependencies",
+                            name
+                        ));
+                    }
+                    if !seen.contains(&dependency) {
+                        seen.push(dependency);
+                        pending.push(dependency);
+                    }
+                }
+            }
+        }
+
+        // Requests can shrink the task down to min_size and the worker has to fit in what
+        // the sidecars leave of it.
+        let min = self.min_size.unwrap_or_default();
+        let cpu: u32 = self.sidecars.iter().map(|sidecar| sidecar.cpu).sum();
+        let memory: u32 = self
+            .sidecars
+            .iter()
+            .map(|sidecar| sidecar.memory)
+            .sum::<u32>()
+            + router_memory;
+        if (cpu > 0 || memory > 0) && (cpu >= min.cpu || memory >= min.memory) {
+            return Err(format!(
+                "sidecars and the log router take cpu {} / memory {}, leaving nothing for the worker at {}; raise min_size",
+                cpu, memory, min
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn valid_env_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn has_option(options: &[LogOption], name: &str) -> bool {
+    options
+        .iter()
+        .any(|option| option.name.eq_ignore_ascii_case(name))
+}
+
+fn validate_health_check(name: &str, health_check: &HealthCheckConfig) -> Result<(), String> {
+    if health_check.command.is_empty() {
+        return Err(format!("the health check of {} has no command", name));
+    }
+    // Limits from the ECS HealthCheck API.
+    if !(5..=300).contains(&health_check.interval_secs)
+        || !(2..=60).contains(&health_check.timeout_secs)
+        || !(1..=10).contains(&health_check.retries)
+        || health_check.start_period_secs > 300
+    {
+        return Err(format!(
+            "the health check of {} needs an interval of 5-300s, a timeout of 2-60s, 1-10 retries and a start period of at most 300s",
+            name
+        ));
+    }
+    Ok(())
+}
+
+fn validate_port_mappings(
+    name: &str,
+    port_mappings: &[PortMappingItem],
+    network_mode: WorkerNetworkMode,
+) -> Result<(), String> {
+    for mapping in port_mappings.iter() {
+        let same_port = mapping
+            .host_port
+            .is_none_or(|host_port| host_port == mapping.container_port);
+        if network_mode != WorkerNetworkMode::Bridge && !same_port {
+            return Err(format!(
+                "{} maps port {} to {}, but the host port has to match in {:?} mode",
+                name,
+                mapping.container_port,
+                mapping.host_port.unwrap_or_default(),
+                network_mode
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl VendorConfig {
+    // The vendor's log routing, with its placeholders filled in.
+    pub fn logging(&self, vendor: &str, default_group: &str) -> LogRouting {
+        let render = |template: &str| template.replace("{vendor}", vendor);
+        let render_options = |options: &[LogOption]| {
+            options
+                .iter()
+                .map(|option| LogOption {
+                    name: option.name.clone(),
+                    value: render(&option.value),
+                })
+                .collect()
+        };
+
+        match &self.logging {
+            LogRouting::Awslogs {
+                group,
+                region,
+                stream_prefix,
+            } => LogRouting::Awslogs {
+                group: Some(group.clone().unwrap_or_else(|| default_group.to_string())),
+                region: region.clone(),
+                stream_prefix: render(stream_prefix),
+            },
+            LogRouting::Firelens {
+                router_image,
+                router_memory,
+                options,
+            } => LogRouting::Firelens {
+                router_image: router_image.clone(),
+                router_memory: *router_memory,
+                options: render_options(options),
+            },
+            LogRouting::Splunk { options } => LogRouting::Splunk {
+                options: render_options(options),
+            },
+            LogRouting::None => LogRouting::None,
+        }
+    }
+
+    // The key the request's task is mutually exclusive on, if any. An explicit key on the
+    // request wins over the vendor's template.
+    pub fn concurrency_key(&self, tr: &TaskRequest) -> Result<Option<String>, AppError> {
+        let templated = self
+            .concurrency_key
+            .replace("{vendor}", &tr.vendor)
+            .replace("{soiid}", &tr.soiid)
+            .replace("{clientid}", &tr.clientid);
+        // A key of the caller's own would let it run a second task for the same data.
+        let key = match &tr.concurrency_key {
+            Some(key) if templated.is_empty() => key.clone(),
+            Some(key) if *key != templated => {
+                return Err(AppError::ValidationError(format!(
+                    "concurrency key {:?} doesn't match the vendor's key {:?}",
+                    key, templated
+                )))
+            }
+            _ => templated,
+        };
+        if key.is_empty() {
+            return Ok(None);
+        }
+
+        // The key is stored as an ECS tag value, so it has to follow the tag rules.
+        let valid = key.len() <= 256
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || " +-=._:/@".contains(c));
+        if !valid {
+            return Err(AppError::ValidationError(format!(
+                "concurrency key {:?} must be at most 256 letters, digits, spaces or + - = . _ : / @",
+                key
+            )));
+        }
+
+        Ok(Some(key))
+    }
+}
+
+impl VendorConfig {
+    // Checks the resources a request asks for against Fargate and the vendor's bounds, and
+    // returns the size it asked for, if any.
+    pub fn validate_resources(&self, tr: &TaskRequest) -> Result<Option<TaskSize>, AppError> {
+        let fargate = self.launch_type == WorkerLaunchType::Fargate;
+        if !fargate && tr.ephemeral_storage_gib.is_some() {
+            return Err(AppError::ValidationError(format!(
+                "{} workers run on EC2, where ephemeral storage can't be set",
+                tr.vendor
+            )));
+        }
+        if let Some(gib) = tr.ephemeral_storage_gib {
+            let (min, max) = EPHEMERAL_STORAGE_GIB;
+            let max = max.min(self.max_ephemeral_storage_gib);
+            if !(min..=max).contains(&gib) {
+                return Err(AppError::ValidationError(format!(
+                    "ephemeral_storage_gib must be between {} and {} for {} workers, got {}",
+                    min, max, tr.vendor, gib
+                )));
+            }
+        }
+
+        if let Some(architecture) = tr.architecture {
+            if !self.architectures.contains(&architecture) {
+                let supported: Vec<String> =
+                    self.architectures.iter().map(ToString::to_string).collect();
+                return Err(AppError::ValidationError(format!(
+                    "{} workers are built for {}, not {}",
+                    tr.vendor,
+                    supported.join(", "),
+                    architecture
+                )));
+            }
+        }
+
+        let Some(size) = TaskSize::complete(tr.cpu, tr.memory, &self.size) else {
+            return Ok(None);
+        };
+        let min = self.min_size.unwrap_or_default();
+        let max = self.max_size.unwrap_or(self.size);
+        let nearest: Vec<String> = size
+            .nearest_fargate(&min, &max, 3)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        // EC2 tasks can have any size that fits on an instance.
+        if fargate && !size.is_valid_fargate() {
+            return Err(AppError::ValidationError(format!(
+                "{} is not a valid Fargate combination; nearest valid: {}",
+                size,
+                nearest.join(", ")
+            )));
+        }
+        if !min.fits_within(&size) || !size.fits_within(&max) {
+            return Err(AppError::ValidationError(format!(
+                "{} is outside the {} to {} allowed for {} workers; nearest valid: {}",
+                size,
+                min,
+                max,
+                tr.vendor,
+                nearest.join(", ")
+            )));
+        }
+
+        Ok(Some(size))
+    }
+}
+
+impl VendorConfig {
+    // Checks what a request changes about the worker's container and role.
+    pub fn validate_overrides(&self, tr: &TaskRequest) -> Result<(), AppError> {
+        for envvar in tr.env.iter() {
+            if !valid_env_name(&envvar.name) {
+                return Err(AppError::ValidationError(format!(
+                    "{:?} is not a valid environment variable name",
+                    envvar.name
+                )));
+            }
+            // APP_ variables are set by the spawner itself.
+            if envvar.name.starts_with("APP_") {
+                return Err(AppError::ValidationError(format!(
+                    "{} is reserved and can't be set by a request",
+                    envvar.name
+                )));
+            }
+            if self.secrets.iter().any(|secret| secret.name == envvar.name) {
+                return Err(AppError::ValidationError(format!(
+                    "{} is set from a secret and can't be set by a request",
+                    envvar.name
+                )));
+            }
+        }
+
+        if tr
+            .command
+            .as_ref()
+            .is_some_and(|command| command.is_empty())
+        {
+            return Err(AppError::ValidationError(
+                "command can't be empty".to_string(),
+            ));
+        }
+
+        if let Some(role) = &tr.task_role_arn {
+            if !self.task_role_arns.contains(role) {
+                return Err(AppError::ValidationError(format!(
+                    "{} workers can't run as {}",
+                    tr.vendor, role
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl VendorConfig {
+    // The request's own strategy if it has one, else the vendor's.
+    pub fn capacity_providers(
+        &self,
+        tr: &TaskRequest,
+    ) -> Result<Vec<CapacityProviderItem>, AppError> {
+        let strategy = tr
+            .capacity_providers
+            .clone()
+            .unwrap_or_else(|| self.capacity_providers.clone());
+
+        if self.launch_type == WorkerLaunchType::Ec2 && !strategy.is_empty() {
+            return Err(AppError::ValidationError(format!(
+                "{} workers run on EC2 and can't use capacity providers",
+                tr.vendor
+            )));
+        }
+
+        if let Some(item) = strategy
+            .iter()
+            .find(|item| item.provider != FARGATE && item.provider != FARGATE_SPOT)
+        {
+            return Err(AppError::ValidationError(format!(
+                "unknown capacity provider {}, expected {} or {}",
+                item.provider, FARGATE, FARGATE_SPOT
+            )));
+        }
+        if !strategy.is_empty() && strategy.iter().all(|item| item.weight == 0) {
+            return Err(AppError::ValidationError(
+                "at least one capacity provider needs a weight above 0".to_string(),
+            ));
+        }
+        if strategy.iter().filter(|item| item.base > 0).count() > 1 {
+            return Err(AppError::ValidationError(
+                "only one capacity provider can have a base".to_string(),
+            ));
+        }
+
+        Ok(strategy)
+    }
+}
+
+impl DispatchConfig {
+    pub fn weight(&self, clientid: &str) -> f64 {
+        self.clients
+            .iter()
+            .find(|c| c.clientid == clientid)
+            .and_then(|c| c.weight)
+            .unwrap_or(self.default_weight)
+    }
+
+    pub fn rate_per_minute(&self, clientid: &str) -> Option<u32> {
+        self.clients
+            .iter()
+            .find(|c| c.clientid == clientid)
+            .and_then(|c| c.rate_per_minute)
+            .or(self.default_rate_per_minute)
+    }
+}
+
+impl RetryPolicy {
+    // Delay before attempt `attempt + 1`, after `attempt` attempts have failed.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1) as i32;
+        let secs = self.initial_backoff_secs as f64 * self.backoff_multiplier.powi(exponent);
+        Duration::from_secs_f64(secs.min(self.max_backoff_secs as f64).max(0.0))
+    }
+
+    pub fn is_retryable(&self, exit_code: Option<i32>, reasons: &[&str]) -> bool {
+        if exit_code.is_some_and(|code| self.retryable_exit_codes.contains(&code)) {
+            return true;
+        }
+        self.retryable_stop_reasons.iter().any(|pattern| {
+            let pattern = pattern.to_lowercase();
+            reasons
+                .iter()
+                .any(|reason| reason.to_lowercase().contains(&pattern))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn vendor(config: Value) -> VendorConfig {
+        let mut vendor = json!({ "image": "public.ecr.aws/soi/worker:latest" });
+        vendor
+            .as_object_mut()
+            .unwrap()
+            .extend(config.as_object().unwrap().clone());
+        serde_json::from_value(vendor).unwrap()
+    }
+
+    fn request(resources: Value) -> TaskRequest {
+        let mut request = json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+        });
+        request
+            .as_object_mut()
+            .unwrap()
+            .extend(resources.as_object().unwrap().clone());
+        serde_json::from_value(request).unwrap()
+    }
+
+    fn error(result: Result<Option<TaskSize>, AppError>) -> String {
+        match result {
+            Err(AppError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_takes_the_callers_concurrency_key_without_a_template() {
+        let templated = vendor(json!({}));
+        assert_eq!(
+            templated.concurrency_key(&request(json!({}))).unwrap(),
+            Some("bloomberg:soi-1".to_string())
+        );
+        assert!(templated
+            .concurrency_key(&request(json!({ "concurrency_key": "bloomberg:soi-1" })))
+            .is_ok());
+        assert!(matches!(
+            templated.concurrency_key(&request(json!({ "concurrency_key": "mine" }))),
+            Err(AppError::ValidationError(_))
+        ));
+
+        let untemplated = vendor(json!({ "concurrency_key": "" }));
+        assert_eq!(
+            untemplated
+                .concurrency_key(&request(json!({ "concurrency_key": "mine" })))
+                .unwrap(),
+            Some("mine".to_string())
+        );
+        assert_eq!(
+            untemplated.concurrency_key(&request(json!({}))).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn leaves_the_size_to_the_vendor_when_none_is_asked_for() {
+        let vendor = vendor(json!({}));
+        assert_eq!(
+            vendor.validate_resources(&request(json!({}))).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn completes_a_size_with_the_smallest_valid_partner() {
+        let vendor = vendor(json!({ "max_size": { "cpu": 4096, "memory": 16384 } }));
+
+        let size = vendor
+            .validate_resources(&request(json!({ "cpu": 1024 })))
+            .unwrap();
+        assert_eq!(
+            size,
+            Some(TaskSize {
+                cpu: 1024,
+                memory: 2048
+            })
+        );
+
+        let size = vendor
+            .validate_resources(&request(json!({ "memory": 8192 })))
+            .unwrap();
+        assert_eq!(
+            size,
+            Some(TaskSize {
+                cpu: 1024,
+                memory: 8192
+            })
+        );
+    }
+
+    #[test]
+    fn suggests_the_nearest_valid_combinations() {
+        let vendor = vendor(json!({ "max_size": { "cpu": 4096, "memory": 16384 } }));
+        let message =
+            error(vendor.validate_resources(&request(json!({ "cpu": 1024, "memory": 1024 }))));
+        assert!(message.starts_with("cpu 1024 / memory 1024 is not a valid Fargate combination"));
+        assert!(message.contains("cpu 1024 / memory 2048"), "{}", message);
+    }
+
+    #[test]
+    fn keeps_requests_within_the_vendor_bounds() {
+        let bounded = vendor(json!({
+            "min_size": { "cpu": 512, "memory": 1024 },
+            "max_size": { "cpu": 1024, "memory": 4096 },
+        }));
+
+        let message =
+            error(bounded.validate_resources(&request(json!({ "cpu": 256, "memory": 512 }))));
+        assert!(message.contains("outside the cpu 512 / memory 1024 to cpu 1024 / memory 4096"));
+
+        let message =
+            error(bounded.validate_resources(&request(json!({ "cpu": 2048, "memory": 4096 }))));
+        assert!(
+            message.contains("nearest valid: cpu 1024 / memory 4096"),
+            "{}",
+            message
+        );
+
+        // Without a max_size, requests can't go above the vendor's size.
+        let unbounded = vendor(json!({}));
+        assert!(unbounded
+            .validate_resources(&request(json!({ "cpu": 512, "memory": 1024 })))
+            .is_err());
+    }
+
+    #[test]
+    fn checks_ephemeral_storage_and_architecture() {
+        let vendor = vendor(json!({ "max_ephemeral_storage_gib": 50 }));
+
+        assert!(vendor
+            .validate_resources(&request(json!({ "ephemeral_storage_gib": 21 })))
+            .is_ok());
+        let message =
+            error(vendor.validate_resources(&request(json!({ "ephemeral_storage_gib": 20 }))));
+        assert_eq!(
+            message,
+            "ephemeral_storage_gib must be between 21 and 50 for bloomberg workers, got 20"
+        );
+        assert!(vendor
+            .validate_resources(&request(json!({ "ephemeral_storage_gib": 51 })))
+            .is_err());
+
+        let message =
+            error(vendor.validate_resources(&request(json!({ "architecture": "arm64" }))));
+        assert_eq!(message, "bloomberg workers are built for x86_64, not arm64");
+    }
+
+    #[test]
+    fn lets_a_request_override_the_vendor_strategy() {
+        let vendor = vendor(json!({
+            "capacity_providers": [
+                { "provider": "FARGATE_SPOT", "weight": 3 },
+                { "provider": "FARGATE", "weight": 1, "base": 1 },
+            ],
+        }));
+
+        let strategy = vendor.capacity_providers(&request(json!({}))).unwrap();
+        assert_eq!(strategy, vendor.capacity_providers);
+        assert_eq!(strategy[0].base, 0);
+
+        let strategy = vendor
+            .capacity_providers(&request(json!({
+                "capacity_providers": [{ "provider": "FARGATE" }],
+            })))
+            .unwrap();
+        assert_eq!(
+            strategy,
+            vec![CapacityProviderItem {
+                provider: FARGATE.to_string(),
+                weight: 1,
+                base: 0,
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_strategies_ecs_would_refuse() {
+        let vendor = vendor(json!({}));
+        let rejected = |strategy: Value| match vendor
+            .capacity_providers(&request(json!({ "capacity_providers": strategy })))
+        {
+            Err(AppError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        };
+
+        assert_eq!(
+            rejected(json!([{ "provider": "EC2_SPOT" }])),
+            "unknown capacity provider EC2_SPOT, expected FARGATE or FARGATE_SPOT"
+        );
+        assert!(rejected(json!([{ "provider": "FARGATE_SPOT", "weight": 0 }])).contains("weight"));
+        assert!(rejected(json!([
+            { "provider": "FARGATE_SPOT", "base": 1 },
+            { "provider": "FARGATE", "base": 1 },
+        ]))
+        .contains("only one capacity provider can have a base"));
+    }
+
+    // A vendor like refinitiv, on the cluster's EC2 instances.
+    fn ec2(config: Value) -> VendorConfig {
+        let mut ec2 = json!({
+            "launch_type": "ec2",
+            "network_mode": "bridge",
+            "size": { "cpu": 4096, "memory": 30000 },
+        });
+        ec2.as_object_mut()
+            .unwrap()
+            .extend(config.as_object().unwrap().clone());
+        vendor(ec2)
+    }
+
+    #[test]
+    fn accepts_ec2_workers_with_placement() {
+        let vendor = ec2(json!({
+            "placement_constraints": [
+                { "type": "memberOf", "expression": "attribute:ecs.instance-type =~ r6i.*" },
+                { "type": "distinctInstance" },
+            ],
+            "placement_strategies": [
+                { "type": "spread", "field": "attribute:ecs.availability-zone" },
+                { "type": "binpack", "field": "memory" },
+            ],
+        }));
+        assert_eq!(vendor.validate(), Ok(()));
+    }
+
+    #[test]
+    fn keeps_ec2_settings_off_fargate_workers() {
+        assert_eq!(
+            vendor(json!({ "network_mode": "bridge" })).validate(),
+            Err("Fargate workers only support the awsvpc network mode".to_string())
+        );
+        assert_eq!(
+            vendor(json!({ "placement_strategies": [{ "type": "random" }] })).validate(),
+            Err("placement constraints and strategies only apply to EC2 workers".to_string())
+        );
+        assert_eq!(
+            vendor(json!({ "size": { "cpu": 4096, "memory": 30000 } })).validate(),
+            Err("cpu 4096 / memory 30000 is not a valid Fargate size".to_string())
+        );
+        assert_eq!(
+            ec2(json!({ "capacity_providers": [{ "provider": "FARGATE_SPOT" }] })).validate(),
+            Err(
+                "capacity providers are Fargate only and can't be used with EC2 workers"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn checks_each_placement_against_its_type() {
+        let invalid = |placement: Value| ec2(placement).validate().unwrap_err();
+
+        assert!(
+            invalid(json!({ "placement_constraints": [{ "type": "memberOf" }] }))
+                .starts_with("memberOf placement constraints need an expression")
+        );
+        assert!(invalid(json!({
+            "placement_constraints": [{ "type": "distinctInstance", "expression": "x" }],
+        }))
+        .starts_with("distinctInstance placement constraints take no expression"));
+        assert_eq!(
+            invalid(
+                json!({ "placement_strategies": [{ "type": "binpack", "field": "instanceId" }] })
+            ),
+            "binpack placement strategies need a field of cpu or memory, got \"instanceId\""
+        );
+        assert!(
+            invalid(json!({ "placement_strategies": [{ "type": "spread" }] }))
+                .starts_with("spread placement strategies need a field")
+        );
+        assert!(invalid(json!({
+            "placement_strategies": [{ "type": "random", "field": "memory" }],
+        }))
+        .starts_with("random placement strategies take no field"));
+
+        let strategies = vec![json!({ "type": "binpack", "field": "cpu" }); 6];
+        assert_eq!(
+            invalid(json!({ "placement_strategies": strategies })),
+            "at most 5 placement strategies are allowed"
+        );
+    }
+
+    #[test]
+    fn sizes_ec2_requests_without_the_fargate_table() {
+        let vendor = ec2(json!({ "max_size": { "cpu": 8192, "memory": 60000 } }));
+
+        let size = vendor
+            .validate_resources(&request(json!({ "cpu": 4096, "memory": 45000 })))
+            .unwrap();
+        assert_eq!(
+            size,
+            Some(TaskSize {
+                cpu: 4096,
+                memory: 45000
+            })
+        );
+
+        let message =
+            error(vendor.validate_resources(&request(json!({ "ephemeral_storage_gib": 30 }))));
+        assert_eq!(
+            message,
+            "bloomberg workers run on EC2, where ephemeral storage can't be set"
+        );
+
+        let spot = request(json!({ "capacity_providers": [{ "provider": "FARGATE_SPOT" }] }));
+        assert!(matches!(
+            vendor.capacity_providers(&spot),
+            Err(AppError::ValidationError(message)) if message.contains("can't use capacity providers")
+        ));
+    }
+
+    #[test]
+    fn limits_what_a_request_can_override() {
+        let vendor = vendor(json!({
+            "task_role_arns": ["arn:aws:iam::123456789012:role/bloomberg-reader"],
+        }));
+        let overriding = |overrides: Value| vendor.validate_overrides(&request(overrides));
+
+        assert!(overriding(json!({
+            "env": [{ "name": "_DEBUG", "value": "1" }, { "name": "BATCH_SIZE", "value": "10" }],
+            "command": ["worker", "--dry-run"],
+            "task_role_arn": "arn:aws:iam::123456789012:role/bloomberg-reader",
+        }))
+        .is_ok());
+
+        let rejected = |overrides: Value| match overriding(overrides) {
+            Err(AppError::ValidationError(message)) => message,
+            other => panic!("expected a validation error, got {:?}", other),
+        };
+        assert_eq!(
+            rejected(json!({ "env": [{ "name": "1ST", "value": "x" }] })),
+            "\"1ST\" is not a valid environment variable name"
+        );
+        assert!(
+            rejected(json!({ "env": [{ "name": "BATCH-SIZE", "value": "x" }] }))
+                .contains("BATCH-SIZE")
+        );
+        assert_eq!(
+            rejected(json!({ "env": [{ "name": "APP_DATA_URL", "value": "s3://other" }] })),
+            "APP_DATA_URL is reserved and can't be set by a request"
+        );
+        assert_eq!(rejected(json!({ "command": [] })), "command can't be empty");
+        assert_eq!(
+            rejected(json!({ "task_role_arn": "arn:aws:iam::123456789012:role/admin" })),
+            "bloomberg workers can't run as arn:aws:iam::123456789012:role/admin"
+        );
+    }
+
+    #[test]
+    fn pins_vendors_with_their_own_task_definition_to_one_architecture() {
+        let task_definition = "arn:aws:ecs:us-east-1:123456789012:task-definition/bloomberg:7";
+        assert!(vendor(json!({ "task_definition": task_definition }))
+            .validate()
+            .is_ok());
+        assert_eq!(
+            vendor(json!({
+                "task_definition": task_definition,
+                "architectures": ["x86_64", "arm64"],
+            }))
+            .validate(),
+            Err("a vendor with its own task_definition can only list one architecture".to_string())
+        );
+    }
+
+    fn with_containers(sidecars: Value, worker: Value) -> VendorConfig {
+        vendor(json!({
+            "min_size": { "cpu": 512, "memory": 1024 },
+            "sidecars": sidecars,
+            "worker": worker,
+        }))
+    }
+
+    fn statsd(config: Value) -> Value {
+        let mut statsd = json!({
+            "name": "statsd",
+            "image": "public.ecr.aws/soi/statsd-exporter:latest",
+            "cpu": 128,
+            "memory": 256,
+        });
+        statsd
+            .as_object_mut()
+            .unwrap()
+            .extend(config.as_object().unwrap().clone());
+        statsd
+    }
+
+    #[test]
+    fn accepts_sidecars_the_worker_waits_for() {
+        let vendor = with_containers(
+            json!([
+                statsd(json!({ "health_check": { "command": ["CMD-SHELL", "exit 0"] } })),
+                {
+                    "name": "fetch-config",
+                    "image": "public.ecr.aws/soi/fetch-config:latest",
+                    "essential": false,
+                },
+            ]),
+            json!({ "depends_on": [
+                { "container": "statsd", "condition": "HEALTHY" },
+                { "container": "fetch-config", "condition": "SUCCESS" },
+            ] }),
+        );
+        assert_eq!(vendor.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_dependencies_ecs_could_never_satisfy() {
+        let waits_for = |condition: &str| json!({ "depends_on": [{ "container": "statsd", "condition": condition }] });
+
+        assert_eq!(
+            with_containers(json!([statsd(json!({}))]), waits_for("HEALTHY")).validate(),
+            Err(
+                "my-container waits for statsd to be HEALTHY, but it has no health check"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            with_containers(json!([statsd(json!({}))]), waits_for("COMPLETE")).validate(),
+            Err("my-container waits for statsd to exit, but it is essential".to_string())
+        );
+        assert_eq!(
+            with_containers(json!([]), waits_for("START")).validate(),
+            Err("my-container depends on unknown container statsd".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_containers_that_wait_for_each_other() {
+        let vendor = with_containers(
+            json!([
+                statsd(json!({ "depends_on": [{ "container": "envoy", "condition": "START" }] })),
+                {
+                    "name": "envoy",
+                    "image": "public.ecr.aws/soi/envoy:latest",
+                    "depends_on": [{ "container": "my-container", "condition": "START" }],
+                },
+            ]),
+            json!({ "depends_on": [{ "container": "statsd", "condition": "START" }] }),
+        );
+        assert_eq!(
+            vendor.validate(),
+            Err("my-container depends on itself through its dependencies".to_string())
+        );
+    }
+
+    #[test]
+    fn checks_the_name_of_the_worker_container() {
+        assert_eq!(
+            with_containers(json!([]), json!({})).worker.name,
+            "my-container"
+        );
+        assert_eq!(
+            with_containers(json!([]), json!({ "name": "worker" })).validate(),
+            Ok(())
+        );
+        assert_eq!(
+            with_containers(json!([]), json!({ "name": "" })).validate(),
+            Err("\"\" can't be used as the name of the worker container".to_string())
+        );
+        assert_eq!(
+            with_containers(json!([]), json!({ "name": "my worker" })).validate(),
+            Err("\"my worker\" can't be used as the name of the worker container".to_string())
+        );
+        assert_eq!(
+            with_containers(json!([statsd(json!({}))]), json!({ "name": "statsd" })).validate(),
+            Err("container name statsd is used twice".to_string())
+        );
+    }
+
+    #[test]
+    fn checks_names_sizes_health_checks_and_ports_of_sidecars() {
+        let invalid =
+            |sidecars: Value| with_containers(sidecars, json!({})).validate().unwrap_err();
+
+        assert_eq!(
+            invalid(json!([statsd(json!({ "name": "my-container" }))])),
+            "container name my-container is used twice"
+        );
+        assert!(
+            invalid(json!([statsd(json!({ "memory": 1024 }))])).starts_with(
+                "sidecars and the log router take cpu 128 / memory 1024, leaving nothing"
+            )
+        );
+        assert!(invalid(json!([statsd(json!({
+            "health_check": { "command": ["CMD-SHELL", "exit 0"], "interval_secs": 1 },
+        }))]))
+        .starts_with("the health check of statsd needs an interval of 5-300s"));
+        assert_eq!(
+            invalid(json!([statsd(json!({
+                "port_mappings": [{ "container_port": 9125, "host_port": 19125 }],
+            }))])),
+            "statsd maps port 9125 to 19125, but the host port has to match in Awsvpc mode"
+        );
+    }
+
+    #[test]
+    fn renders_log_settings_per_vendor() {
+        let splunk = vendor(json!({ "logging": {
+            "driver": "splunk",
+            "options": [
+                { "name": "splunk-url", "value": "https://splunk.internal:8088" },
+                { "name": "tag", "value": "soi/{vendor}" },
+            ],
+        } }));
+        assert_eq!(splunk.validate(), Ok(()));
+        assert_eq!(
+            splunk.logging("bloomberg", "/ecs/soi-workers"),
+            LogRouting::Splunk {
+                options: vec![
+                    LogOption {
+                        name: "splunk-url".to_string(),
+                        value: "https://splunk.internal:8088".to_string(),
+                    },
+                    LogOption {
+                        name: "tag".to_string(),
+                        value: "soi/bloomberg".to_string(),
+                    },
+                ],
+            }
+        );
+
+        let awslogs = vendor(json!({ "logging": {
+            "driver": "awslogs",
+            "stream_prefix": "soi-{vendor}",
+        } }));
+        assert_eq!(
+            awslogs.logging("bloomberg", "/ecs/soi-workers"),
+            LogRouting::Awslogs {
+                group: Some("/ecs/soi-workers".to_string()),
+                region: None,
+                stream_prefix: "soi-bloomberg".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_log_routing_the_driver_would_refuse() {
+        let invalid = |logging: Value| {
+            vendor(json!({ "logging": logging }))
+                .validate()
+                .unwrap_err()
+        };
+
+        assert_eq!(
+            invalid(json!({ "driver": "awslogs", "stream_prefix": "{soiid}" })),
+            "log setting \"{soiid}\" can only use the {vendor} placeholder"
+        );
+        assert_eq!(
+            invalid(json!({ "driver": "awslogs", "stream_prefix": "{vendor}/{clientid}" })),
+            "log setting \"{vendor}/{clientid}\" can only use the {vendor} placeholder"
+        );
+        assert_eq!(
+            invalid(json!({ "driver": "awslogs", "stream_prefix": "soi:{vendor}" })),
+            "awslogs stream prefix \"soi:{vendor}\" can't be empty or contain : or *"
+        );
+        assert_eq!(
+            invalid(json!({ "driver": "firelens", "options": [] })),
+            "FireLens logging needs a Name option naming the Fluent Bit output"
+        );
+        assert_eq!(
+            invalid(json!({ "driver": "splunk", "options": [{ "name": "tag", "value": "x" }] })),
+            "splunk logging needs a splunk-url option"
+        );
+    }
+
+    #[test]
+    fn counts_the_log_router_against_the_worker_size() {
+        let firelens = json!({
+            "driver": "firelens",
+            "router_memory": 512,
+            "options": [{ "name": "Name", "value": "cloudwatch_logs" }],
+        });
+        assert_eq!(
+            vendor(json!({ "logging": firelens })).validate(),
+            Err("sidecars and the log router take cpu 0 / memory 512, leaving nothing for the worker at cpu 256 / memory 512; raise min_size".to_string())
+        );
+        assert_eq!(
+            vendor(json!({
+                "logging": firelens,
+                "min_size": { "cpu": 256, "memory": 1024 },
+            }))
+            .validate(),
+            Ok(())
+        );
+        assert_eq!(
+            with_containers(json!([statsd(json!({ "name": "log-router" }))]), json!({})).validate(),
+            Ok(())
+        );
+        let mut vendor =
+            with_containers(json!([statsd(json!({ "name": "log-router" }))]), json!({}));
+        vendor.logging = serde_json::from_value(firelens).unwrap();
+        assert_eq!(
+            vendor.validate(),
+            Err("container name log-router is used twice".to_string())
+        );
+    }
+
+    #[test]
+    fn gives_every_api_key_an_id_and_key_of_its_own() {
+        let validate = |api_keys: Value| {
+            serde_json::from_value::<AppConfig>(json!({
+                "api_key": "shared",
+                "log_level": "info",
+                "api_keys": api_keys,
+            }))
+            .unwrap()
+            .validate()
+            .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(
+            validate(json!([
+                { "id": "ops", "key": "ops-key", "admin": true },
+                { "id": "client-1", "key": "client-1-key" },
+            ])),
+            Ok(())
+        );
+        assert_eq!(
+            validate(json!([{ "id": "api_key", "key": "other" }])),
+            Err("\"api_key\" can't be used as the id of an API key".to_string())
+        );
+        assert_eq!(
+            validate(json!([
+                { "id": "ops", "key": "ops-key" },
+                { "id": "ops", "key": "other" },
+            ])),
+            Err("\"ops\" can't be used as the id of an API key".to_string())
+        );
+        assert_eq!(
+            validate(json!([{ "id": "ops", "key": "shared" }])),
+            Err("API key ops needs a key of its own".to_string())
+        );
+    }
+
+    #[test]
+    fn names_changed_settings_without_their_values() {
+        let config = |cpu: u32| -> AppConfig {
+            serde_json::from_value(json!({
+                "api_key": "key",
+                "log_level": "info",
+                "vendors": { "bloomberg": {
+                    "image": "public.ecr.aws/soi/worker:latest",
+                    "size": { "cpu": cpu, "memory": 2048 },
+                } },
+            }))
+            .unwrap()
+        };
+        let mut changed = config(1024);
+        changed.api_key = "rotated".to_string();
+
+        assert!(config(256).changed_settings(&config(256)).is_empty());
+        assert_eq!(
+            config(256).changed_settings(&changed),
+            ["api_key", "vendors.bloomberg.size.cpu"]
+        );
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy {
+            initial_backoff_secs: 10,
+            backoff_multiplier: 3.0,
+            max_backoff_secs: 60,
+            ..Default::default()
+        };
+        let backoffs: Vec<u64> = (0..5).map(|n| policy.backoff(n).as_secs()).collect();
+        assert_eq!(backoffs, [10, 10, 30, 60, 60]);
+    }
+
+    #[test]
+    fn retries_listed_exit_codes_and_stop_reasons() {
+        let retry = vendor(json!({ "retry": {
+            "max_attempts": 3,
+            "retryable_exit_codes": [2, 75],
+            "retryable_stop_reasons": ["CannotPullContainerError", "ResourceInitializationError"],
+        } }))
+        .retry;
+        let cases: &[(Option<i32>, &[&str], bool)] = &[
+            (Some(2), &[], true),
+            (Some(75), &["Essential container in task exited"], true),
+            (Some(1), &[], false),
+            (Some(0), &[], false),
+            (
+                None,
+                &["CannotPullContainerError: pull access denied"],
+                true,
+            ),
+            (None, &["cannotpullcontainererror: timeout"], true),
+            (
+                Some(1),
+                &[
+                    "Essential container in task exited",
+                    "ResourceInitializationError: secrets",
+                ],
+                true,
+            ),
+            (None, &["Task stopped by user"], false),
+            (None, &[], false),
+        ];
+        for (exit_code, reasons, retryable) in cases {
+            assert_eq!(
+                retry.is_retryable(*exit_code, reasons),
+                *retryable,
+                "{:?} {:?}",
+                exit_code,
+                reasons
+            );
+        }
+        assert!(!RetryPolicy::default().is_retryable(Some(2), &["CannotPullContainerError"]));
+    }
+
+    #[test]
+    fn checks_secret_names_and_sources() {
+        type Declared<'a> = &'a [(&'a str, &'a str)];
+        const TOKEN: &str = "arn:aws:ssm:us-east-1:123456789012:parameter/soi/token";
+        const PASSWORD: &str =
+            "arn:aws:secretsmanager:us-east-1:123456789012:secret:soi/pw:password::";
+        let secrets = |secrets: Declared| {
+            vendor(json!({
+                "secrets": secrets
+                    .iter()
+                    .map(|(name, value_from)| json!({ "name": name, "value_from": value_from }))
+                    .collect::<Vec<_>>(),
+            }))
+            .validate_secrets()
+        };
+
+        let cases: &[(Declared, Result<(), &str>)] = &[
+            (&[], Ok(())),
+            (&[("TOKEN", TOKEN), ("DB_PASSWORD", PASSWORD)], Ok(())),
+            (
+                &[("TOKEN", TOKEN), ("TOKEN", PASSWORD)],
+                Err("secret TOKEN is declared twice"),
+            ),
+            (
+                &[("APP_API_KEY", TOKEN)],
+                Err("\"APP_API_KEY\" can't be used as the name of a secret"),
+            ),
+            (
+                &[("1TOKEN", TOKEN)],
+                Err("\"1TOKEN\" can't be used as the name of a secret"),
+            ),
+            (
+                &[("TOKEN", "soi/token")],
+                Err("secret TOKEN: \"soi/token\" is not an SSM parameter or Secrets Manager secret ARN"),
+            ),
+        ];
+        for (declared, expected) in cases {
+            assert_eq!(
+                secrets(declared),
+                expected.map_err(str::to_string),
+                "{:?}",
+                declared
+            );
+        }
+
+        let mut own_definition = vendor(json!({ "task_definition": "soi-worker:3" }));
+        own_definition.secrets =
+            vendor(json!({ "secrets": [{ "name": "TOKEN", "value_from": TOKEN }] })).secrets;
+        assert_eq!(
+            own_definition.validate_secrets(),
+            Err("secrets can't be added to a vendor's own task_definition".to_string())
+        );
+    }
+}
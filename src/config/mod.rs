@@ -1 +1,2 @@
+pub mod impls;
 pub mod models;
@@ -1,22 +1,517 @@
-use std::env;
+use std::{collections::HashMap, env};
 
 use config::{Config, ConfigError, Environment, File};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    ecs::models::{
+        CapacityProviderItem, EcsSecret, LogRouting, PlacementConstraintItem,
+        PlacementStrategyItem, SidecarConfig, WorkerContainerConfig, WorkerLaunchType,
+        WorkerNetworkMode,
+    },
+    sizing::models::{Architecture, TaskSize},
+    ssm::{
+        impls::ssm_references,
+        models::{ParameterResolver, SSM_SCHEME},
+    },
+    terraform::models::TerraformOutputs,
+};
+
 #[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
 pub struct AppConfig {
+    // Shared by every client and audited as "api_key". It can't call /admin/*.
     pub api_key: String,
+    // Keys of callers that are audited under their own id.
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+    // An EnvFilter directive, e.g. "info,ecs_task_spawner=debug".
     pub log_level: String,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub ecs: EcsConfig,
+    #[serde(default)]
+    pub quotas: Vec<QuotaConfig>,
+    // Keyed by the `vendor` field of a TaskRequest.
+    #[serde(default)]
+    pub vendors: HashMap<String, VendorConfig>,
+    #[serde(default)]
+    pub dispatch: DispatchConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    #[serde(default)]
+    pub ssm: SsmConfig,
+    #[serde(default)]
+    pub terraform: TerraformConfig,
+    #[serde(default)]
+    pub preflight: PreflightConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub tracing: TracingConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct ApiKeyConfig {
+    // Recorded as the principal of what the key's holder does.
+    pub id: String,
+    pub key: String,
+    // Only admin keys can call /admin/*.
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    // One object per line, with the fields of the request's span such as request_id and
+    // trace_id.
+    Json,
+}
+
+// Infrastructure the workers are launched into.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct EcsConfig {
+    pub cluster_name: String,
+    pub subnets: Vec<SubnetConfig>,
+    pub subnet_strategy: SubnetStrategy,
+    // A subnet that failed a launch more recently than this is only tried after the others.
+    pub subnet_cooldown_secs: u64,
+    pub security_group_id: String,
+    // Default log group of vendors that log with awslogs.
+    pub log_group: String,
+    pub task_role_arn: String,
+    pub execution_role_arn: String,
+}
+
+impl Default for EcsConfig {
+    fn default() -> Self {
+        EcsConfig {
+            cluster_name: "default".to_string(),
+            subnets: vec![SubnetConfig {
+                id: "subnet-0c8b6b6b".to_string(),
+                availability_zone: "us-east-1a".to_string(),
+            }],
+            subnet_strategy: SubnetStrategy::default(),
+            subnet_cooldown_secs: 300,
+            security_group_id: "sg-0c8b6b6b".to_string(),
+            log_group: "/ecs/soi-worker".to_string(),
+            task_role_arn: "arn:aws:iam::123456789012:role/ecsTaskExecutionRole".to_string(),
+            execution_role_arn: "arn:aws:iam::123456789012:role/ecsTaskExecutionRole".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct SubnetConfig {
+    pub id: String,
+    pub availability_zone: String,
+}
+
+// The order subnets are tried in for each launch.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SubnetStrategy {
+    #[default]
+    RoundRobin,
+    // Subnets that never failed first, then the ones whose last failure is oldest.
+    LeastRecentlyFailed,
+    // The availability zone with the fewest launches first.
+    AzSpread,
+}
+
+// A limit on the number of RUNNING/PENDING tasks. A rule without a vendor counts
+// tasks of every vendor, a rule without a clientid applies to each client separately.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct QuotaConfig {
+    pub vendor: Option<String>,
+    pub clientid: Option<String>,
+    pub max_active: usize,
+    #[serde(default)]
+    pub policy: QuotaPolicy,
+    // How long a queued request waits for a free slot before it is rejected.
+    #[serde(default = "default_queue_timeout_secs")]
+    pub queue_timeout_secs: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPolicy {
+    #[default]
+    Reject,
+    Queue,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct VendorConfig {
+    pub image: String,
+    // ARN or family[:revision] of a task definition to run instead of registering one. Its
+    // worker container has to be named after worker.name for overrides to apply.
+    pub task_definition: Option<String>,
+    // Task roles requests may run the worker as instead of the cluster's.
+    #[serde(default)]
+    pub task_role_arns: Vec<String>,
+    // Template for the key. Supports {vendor}, {soiid} and {clientid}. At most one task per
+    // key is active at a time; an empty template turns mutual exclusion off for the vendor,
+    // and only then may requests pick their own key.
+    #[serde(default = "default_concurrency_key")]
+    pub concurrency_key: String,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    // How long a queued or replacing request waits for the running task to go away.
+    #[serde(default = "default_queue_timeout_secs")]
+    pub conflict_timeout_secs: u64,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    // Size of a first attempt, unless an earlier run of the soiid needed more.
+    #[serde(default)]
+    pub size: TaskSize,
+    // Requests can't ask for less than this.
+    pub min_size: Option<TaskSize>,
+    // Requests can't ask for more than this, and workers that run out of memory are retried
+    // one Fargate step larger up to it. When unset, requests can't go above `size` and
+    // nothing is resized.
+    pub max_size: Option<TaskSize>,
+    #[serde(default = "default_max_ephemeral_storage_gib")]
+    pub max_ephemeral_storage_gib: u32,
+    // Architectures the vendor's image is built for. The first one is the default.
+    #[serde(default = "default_architectures")]
+    pub architectures: Vec<Architecture>,
+    // Plain on-demand Fargate when empty. Spawns that can't get Spot capacity fall back to
+    // on-demand.
+    #[serde(default)]
+    pub capacity_providers: Vec<CapacityProviderItem>,
+    #[serde(default)]
+    pub spot: SpotPolicy,
+    #[serde(default)]
+    pub launch_type: WorkerLaunchType,
+    #[serde(default)]
+    pub network_mode: WorkerNetworkMode,
+    // EC2 only. Applied in order on every launch of the vendor's workers.
+    #[serde(default)]
+    pub placement_constraints: Vec<PlacementConstraintItem>,
+    #[serde(default)]
+    pub placement_strategies: Vec<PlacementStrategyItem>,
+    #[serde(default)]
+    pub logging: LogRouting,
+    // Environment variables of the worker read from SSM or Secrets Manager ARNs by the
+    // execution role. Not allowed with task_definition.
+    #[serde(default)]
+    pub secrets: Vec<EcsSecret>,
+    #[serde(default)]
+    pub worker: WorkerContainerConfig,
+    // Containers registered next to the worker. Not allowed with task_definition, whose
+    // containers are its own.
+    #[serde(default)]
+    pub sidecars: Vec<SidecarConfig>,
+}
+
+// Workers reclaimed by Fargate Spot are relaunched without using up retry attempts.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct SpotPolicy {
+    // The job fails once it has been interrupted this many times.
+    pub max_interruptions: u32,
+    // Relaunch on on-demand Fargate once the job has been interrupted this many times.
+    pub on_demand_after: Option<u32>,
+}
+
+impl Default for SpotPolicy {
+    fn default() -> Self {
+        SpotPolicy {
+            max_interruptions: 10,
+            on_demand_after: None,
+        }
+    }
+}
+
+// When a stopped worker is launched again with the same request.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct RetryPolicy {
+    // Including the first attempt, so 1 never retries.
+    pub max_attempts: u32,
+    pub initial_backoff_secs: u64,
+    pub backoff_multiplier: f64,
+    pub max_backoff_secs: u64,
+    pub retryable_exit_codes: Vec<i32>,
+    // Case-insensitive substrings of the task's or the worker container's stop reason.
+    pub retryable_stop_reasons: Vec<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff_secs: 30,
+            backoff_multiplier: 2.0,
+            max_backoff_secs: 900,
+            retryable_exit_codes: Vec::new(),
+            retryable_stop_reasons: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct PreflightConfig {
+    // Run the preflight checks before the server starts.
+    pub on_startup: bool,
+    // Refuse to start when a check fails, rather than only logging it.
+    pub fail_on_error: bool,
+}
+
+impl Default for PreflightConfig {
+    fn default() -> Self {
+        PreflightConfig {
+            on_startup: true,
+            fail_on_error: false,
+        }
+    }
+}
+
+// The readiness checks behind /health/ready.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct HealthConfig {
+    // How long a readiness result is served before the checks run again.
+    pub cache_ttl_secs: u64,
+    // A check that takes longer than this fails.
+    pub check_timeout_secs: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            cache_ttl_secs: 10,
+            check_timeout_secs: 3,
+        }
+    }
+}
+
+// Metrics are always served on /metrics for Prometheus.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    // Also pushes them to this OTLP/gRPC collector, e.g. http://otel-collector:4317.
+    pub otlp_endpoint: Option<String>,
+    pub otlp_interval_secs: u64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            otlp_endpoint: None,
+            otlp_interval_secs: 60,
+        }
+    }
+}
+
+// Spans of API requests, launches and AWS calls. Workers get the launch's trace context in
+// TRACEPARENT and TRACESTATE so their spans join the trace.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct TracingConfig {
+    // OTLP/gRPC collector the spans are sent to. Without one trace ids are still made up
+    // and handed to workers, but our own spans go nowhere.
+    pub otlp_endpoint: Option<String>,
+    // Fraction of new traces that are recorded. Requests that arrive with a traceparent
+    // follow the caller's decision.
+    pub sample_ratio: f64,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        TracingConfig {
+            otlp_endpoint: None,
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+// Spawns, stops and config changes, kept for compliance and served on /admin/audit.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct AuditConfig {
+    // JSON-lines file records are appended to. Rotated files are kept next to it.
+    pub path: String,
+    // The file is rotated before it grows past this.
+    pub max_file_bytes: u64,
+    // Rotated files kept, oldest deleted first. Unset keeps all of them.
+    pub max_files: Option<usize>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            path: "audit/audit.jsonl".to_string(),
+            max_file_bytes: 100 * 1024 * 1024,
+            max_files: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct JobsConfig {
+    // How often running jobs are checked for stopped tasks.
+    pub poll_interval_secs: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            poll_interval_secs: 15,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct TerraformConfig {
+    // A `terraform output -json` file whose outputs set the ECS settings. They override the
+    // config file and are overridden by the environment.
+    pub outputs_file: Option<String>,
+}
+
+// Parameter Store, which any string setting can be read from with ssm://<name>.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct SsmConfig {
+    // How often ssm:// settings are read again. Zero reads them only at startup.
+    pub refresh_interval_secs: u64,
+    // Talks to this endpoint instead of SSM, e.g. a local stand-in.
+    pub endpoint_url: Option<String>,
+}
+
+impl Default for SsmConfig {
+    fn default() -> Self {
+        SsmConfig {
+            refresh_interval_secs: 300,
+            endpoint_url: None,
+        }
+    }
+}
+
+// What to do when a task with the same concurrency key is already active.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Reject,
+    Queue,
+    Replace,
+}
+
+// Shares the dispatch queue between clients. A client with weight 2 gets twice the
+// dispatches of a client with weight 1 while both have spawns waiting.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct DispatchConfig {
+    // Spawns launching at once. The rest wait in the queue, in priority and fair order.
+    pub max_in_flight: usize,
+    pub default_weight: f64,
+    // Unlimited when unset.
+    pub default_rate_per_minute: Option<u32>,
+    pub clients: Vec<ClientDispatchConfig>,
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        DispatchConfig {
+            max_in_flight: 10,
+            default_weight: 1.0,
+            default_rate_per_minute: None,
+            clients: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq, Clone)]
+pub struct ClientDispatchConfig {
+    pub clientid: String,
+    pub weight: Option<f64>,
+    pub rate_per_minute: Option<u32>,
+}
+
+fn default_queue_timeout_secs() -> u64 {
+    300
+}
+
+fn default_max_ephemeral_storage_gib() -> u32 {
+    200
+}
+
+fn default_architectures() -> Vec<Architecture> {
+    vec![Architecture::X86_64]
+}
+
+fn default_concurrency_key() -> String {
+    "{vendor}:{soiid}".to_string()
 }
 
 impl AppConfig {
     pub fn new() -> Result<Self, ConfigError> {
+        let cfg = AppConfig::sources()?.try_deserialize::<AppConfig>()?;
+        cfg.validate()?;
+        Ok(cfg)
+    }
+
+    // The merged settings before ssm:// references are resolved. From lowest to highest
+    // precedence: the config file, terraform outputs, then APP_ environment variables, where
+    // __ separates nested keys as in APP_ECS__CLUSTER_NAME.
+    pub fn sources() -> Result<Config, ConfigError> {
         let run_mode = env::var("RUN_MODE").unwrap_or_else(|_| "local".into());
-        Config::builder()
-            .add_source(File::with_name(&format!("src/config/{}.toml", run_mode)).required(false))
-            .add_source(Environment::with_prefix("APP"))
-            .build()
-            .unwrap()
-            .try_deserialize()
+        let file = File::with_name(&format!("src/config/{}.toml", run_mode)).required(false);
+        let environment = Environment::with_prefix("APP")
+            .prefix_separator("_")
+            .separator("__");
+
+        // The outputs file is itself a setting, so it can't come from terraform.
+        let terraform: TerraformConfig = Config::builder()
+            .add_source(file.clone())
+            .add_source(environment.clone())
+            .build()?
+            .get("terraform")
+            .unwrap_or_default();
+
+        let mut builder = Config::builder().add_source(file);
+        if let Some(path) = terraform.outputs_file {
+            builder = builder.add_source(TerraformOutputs::new(&path));
+        }
+        builder.add_source(environment).build()
+    }
+
+    // Builds the config with every ssm:// setting replaced by its parameter.
+    pub async fn resolve(
+        sources: Config,
+        resolver: &ParameterResolver,
+    ) -> Result<Self, ConfigError> {
+        let references = ssm_references(&sources)?;
+        let mut names: Vec<String> = references.iter().map(|(_, name)| name.clone()).collect();
+        names.sort();
+        names.dedup();
+        let values = resolver.resolve(&names).await?;
+
+        let mut builder = Config::builder().add_source(sources);
+        for (path, name) in references {
+            let value = values.get(&name).ok_or_else(|| {
+                ConfigError::Message(format!(
+                    "SSM returned no value for {}{} of {}",
+                    SSM_SCHEME, name, path
+                ))
+            })?;
+            builder = builder.set_override(path, value.clone())?;
+        }
+        let cfg = builder.build()?.try_deserialize::<AppConfig>()?;
+        cfg.validate()?;
+        Ok(cfg)
     }
 }
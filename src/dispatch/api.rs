@@ -0,0 +1,14 @@
+use super::{
+    handlers::{get_queue, get_queue_entry},
+    models::Dispatcher,
+};
+
+use axum::{routing::get, Router};
+
+pub fn router(dispatcher: Dispatcher) -> Router {
+    Router::new()
+        .route("/admin/queue", get(get_queue))
+        // Callers follow their own spawns, so this one is not for admins only.
+        .route("/queue/:id", get(get_queue_entry))
+        .with_state(dispatcher)
+}
@@ -0,0 +1,22 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use super::models::{Dispatcher, QueueEntry, QueueStatus};
+use crate::errors::models::AppError;
+
+pub async fn get_queue(State(dispatcher): State<Dispatcher>) -> Json<QueueStatus> {
+    Json(dispatcher.status())
+}
+
+// A caller that sets x-request-id on its spawn can follow the spawn's position by it.
+pub async fn get_queue_entry(
+    State(dispatcher): State<Dispatcher>,
+    Path(id): Path<String>,
+) -> Result<Json<QueueEntry>, AppError> {
+    dispatcher
+        .entry(&id)
+        .map(Json)
+        .ok_or_else(|| AppError::NotFoundError(format!("No queued spawn with id {}", id)))
+}
@@ -0,0 +1,437 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinHandle,
+};
+use uuid::Uuid;
+
+use super::models::{
+    ClientState, DispatchQueue, DispatchTicket, Dispatcher, QueueEntry, QueueStatus, QueuedSpawn,
+};
+use crate::{
+    config::models::{AppConfig, DispatchConfig},
+    ecs::models::TaskRequest,
+    errors::models::AppError,
+};
+
+impl Dispatcher {
+    pub fn new(config: DispatchConfig) -> Self {
+        Dispatcher {
+            queue: Arc::new(Mutex::new(DispatchQueue::new(config))),
+            notify: Arc::default(),
+        }
+    }
+
+    // Runs the dispatch loop in the background.
+    pub fn start(&self) -> JoinHandle<()> {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let wait = dispatcher
+                    .queue
+                    .lock()
+                    .unwrap()
+                    .dispatch_ready(Instant::now());
+                match wait {
+                    Some(wait) => {
+                        tokio::select! {
+                            _ = tokio::time::sleep(wait) => {},
+                            _ = dispatcher.notify.notified() => {},
+                        }
+                    }
+                    None => dispatcher.notify.notified().await,
+                }
+            }
+        })
+    }
+
+    pub fn enqueue(&self, tr: &TaskRequest, request_id: Option<&str>) -> DispatchTicket {
+        let (sender, receiver) = oneshot::channel();
+        let id = self.queue.lock().unwrap().push(tr, request_id, sender);
+        tracing::info!(
+            "queued spawn {} for clientid {} soiid {}",
+            id,
+            tr.clientid,
+            tr.soiid
+        );
+        self.notify.notify_one();
+
+        DispatchTicket {
+            id,
+            receiver,
+            dispatcher: self.clone(),
+        }
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let (entries, in_flight) = {
+            let queue = self.queue.lock().unwrap();
+            (queue.entries(), queue.in_flight)
+        };
+
+        let mut by_client = HashMap::new();
+        let mut by_priority = HashMap::new();
+        for entry in entries.iter() {
+            *by_client.entry(entry.clientid.clone()).or_default() += 1;
+            *by_priority.entry(entry.priority).or_default() += 1;
+        }
+
+        QueueStatus {
+            depth: entries.len(),
+            in_flight,
+            by_client,
+            by_priority,
+            entries,
+        }
+    }
+
+    // By its own id, or the x-request-id of the request it is for.
+    pub fn entry(&self, id: &str) -> Option<QueueEntry> {
+        self.queue
+            .lock()
+            .unwrap()
+            .entries()
+            .into_iter()
+            .find(|entry| entry.id == id || entry.request_id.as_deref() == Some(id))
+    }
+
+    // Picks up dispatch settings changed by config refreshes. Spawns already queued keep
+    // the place they were given.
+    pub fn follow(&self, mut updates: watch::Receiver<Arc<AppConfig>>) -> JoinHandle<()> {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            while updates.changed().await.is_ok() {
+                let config = updates.borrow_and_update().dispatch.clone();
+                dispatcher.queue.lock().unwrap().config = config;
+                dispatcher.notify.notify_one();
+            }
+        })
+    }
+}
+
+impl DispatchTicket {
+    pub async fn dispatched(&mut self) -> Result<(), AppError> {
+        (&mut self.receiver).await.map_err(|_| {
+            AppError::InternalServerError(format!("spawn {} was dropped from the queue", self.id))
+        })
+    }
+}
+
+impl Drop for DispatchTicket {
+    fn drop(&mut self) {
+        self.dispatcher.queue.lock().unwrap().remove(&self.id);
+        self.dispatcher.notify.notify_one();
+    }
+}
+
+impl DispatchQueue {
+    pub fn new(config: DispatchConfig) -> Self {
+        DispatchQueue {
+            config,
+            entries: Vec::new(),
+            clients: HashMap::new(),
+            in_flight: 0,
+            virtual_time: 0.0,
+            next_seq: 0,
+        }
+    }
+
+    pub fn push(
+        &mut self,
+        tr: &TaskRequest,
+        request_id: Option<&str>,
+        sender: oneshot::Sender<()>,
+    ) -> String {
+        let weight = self.config.weight(&tr.clientid).max(f64::EPSILON);
+        let burst = self.burst(&tr.clientid);
+        let client = self
+            .clients
+            .entry(tr.clientid.clone())
+            .or_insert_with(|| ClientState {
+                last_finish: 0.0,
+                tokens: burst,
+                refilled_at: Instant::now(),
+            });
+
+        // A client's spawns finish 1/weight apart in virtual time, starting no earlier than
+        // the spawn currently being dispatched, so idle clients can't bank credit.
+        let finish = client.last_finish.max(self.virtual_time) + 1.0 / weight;
+        client.last_finish = finish;
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let spawn = QueuedSpawn {
+            entry: QueueEntry {
+                id: Uuid::new_v4().to_string(),
+                position: 0,
+                clientid: tr.clientid.clone(),
+                vendor: tr.vendor.clone(),
+                soiid: tr.soiid.clone(),
+                request_id: request_id.map(str::to_string),
+                priority: tr.priority,
+                enqueued_at: Utc::now(),
+            },
+            finish,
+            seq,
+            sender,
+        };
+        let id = spawn.entry.id.clone();
+
+        let index = self
+            .entries
+            .partition_point(|queued| queued.order() <= spawn.order());
+        self.entries.insert(index, spawn);
+
+        id
+    }
+
+    // Takes a spawn out of the queue when the request went away (e.g. the client
+    // disconnected) before its turn came, and frees its slot when it was dispatched.
+    pub fn remove(&mut self, id: &str) {
+        match self.entries.iter().position(|queued| queued.entry.id == id) {
+            Some(index) => {
+                self.entries.remove(index);
+            }
+            None => self.in_flight = self.in_flight.saturating_sub(1),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<QueueEntry> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, queued)| QueueEntry {
+                position: index + 1,
+                ..queued.entry.clone()
+            })
+            .collect()
+    }
+
+    // Releases spawns in dispatch order while there is in-flight budget, skipping clients
+    // out of rate budget. Returns how long to wait for the next client to earn budget, or
+    // None when only a new or finished spawn can change anything.
+    pub fn dispatch_ready(&mut self, now: Instant) -> Option<Duration> {
+        loop {
+            if self.in_flight >= self.config.max_in_flight {
+                return None;
+            }
+            let ready = self.entries.iter().position(|queued| {
+                let clientid = &queued.entry.clientid;
+                let rate = self.config.rate_per_minute(clientid);
+                let client = self.clients.get(clientid);
+                match (rate, client) {
+                    (Some(rate), Some(client)) => client.tokens + refill(client, rate, now) >= 1.0,
+                    _ => true,
+                }
+            });
+
+            let Some(index) = ready else {
+                break;
+            };
+
+            let queued = self.entries.remove(index);
+            let clientid = queued.entry.clientid.clone();
+            if let Some(rate) = self.config.rate_per_minute(&clientid) {
+                let burst = self.burst(&clientid);
+                if let Some(client) = self.clients.get_mut(&clientid) {
+                    client.tokens = (client.tokens + refill(client, rate, now)).min(burst) - 1.0;
+                    client.refilled_at = now;
+                }
+            }
+            self.virtual_time = self.virtual_time.max(queued.finish);
+            self.in_flight += 1;
+
+            tracing::info!(
+                "dispatching spawn {} for clientid {}",
+                queued.entry.id,
+                clientid
+            );
+            let _ = queued.sender.send(());
+        }
+
+        // Every queued client is out of budget, so sleep until the first one earns a token.
+        self.entries
+            .iter()
+            .filter_map(|queued| {
+                let clientid = &queued.entry.clientid;
+                let rate = self.config.rate_per_minute(clientid)?;
+                let client = self.clients.get(clientid)?;
+                let missing = 1.0 - (client.tokens + refill(client, rate, now));
+                Some(Duration::from_secs_f64(
+                    missing.max(0.0) * 60.0 / f64::from(rate.max(1)),
+                ))
+            })
+            .min()
+    }
+
+    // A client may use a minute's worth of its rate in one go.
+    fn burst(&self, clientid: &str) -> f64 {
+        self.config
+            .rate_per_minute(clientid)
+            .map_or(f64::INFINITY, |rate| f64::from(rate.max(1)))
+    }
+}
+
+impl QueuedSpawn {
+    fn order(&self) -> (u8, f64, u64) {
+        (self.entry.priority as u8, self.finish, self.seq)
+    }
+}
+
+fn refill(client: &ClientState, rate_per_minute: u32, now: Instant) -> f64 {
+    let elapsed = now.saturating_duration_since(client.refilled_at);
+    elapsed.as_secs_f64() * f64::from(rate_per_minute) / 60.0
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::models::ClientDispatchConfig;
+
+    fn request(clientid: &str, priority: &str) -> TaskRequest {
+        serde_json::from_value(json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": clientid,
+            "vendor": "bloomberg",
+            "priority": priority,
+        }))
+        .unwrap()
+    }
+
+    struct Queued {
+        id: String,
+        clientid: String,
+        receiver: oneshot::Receiver<()>,
+    }
+
+    fn push(queue: &mut DispatchQueue, clientid: &str, priority: &str) -> Queued {
+        let (sender, receiver) = oneshot::channel();
+        Queued {
+            id: queue.push(&request(clientid, priority), None, sender),
+            clientid: clientid.to_string(),
+            receiver,
+        }
+    }
+
+    // Dispatches one spawn at a time, finishing each before the next, and returns the
+    // clientids in the order they went.
+    fn drain(queue: &mut DispatchQueue, mut queued: Vec<Queued>) -> Vec<String> {
+        let mut order = Vec::new();
+        while !queued.is_empty() {
+            queue.dispatch_ready(Instant::now());
+            let index = queued
+                .iter_mut()
+                .position(|q| q.receiver.try_recv().is_ok())
+                .expect("a spawn is dispatched");
+            let done = queued.remove(index);
+            assert!(queued.iter_mut().all(|q| q.receiver.try_recv().is_err()));
+            queue.remove(&done.id);
+            order.push(done.clientid);
+        }
+        order
+    }
+
+    fn config(clients: Vec<ClientDispatchConfig>) -> DispatchConfig {
+        DispatchConfig {
+            max_in_flight: 1,
+            clients,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dispatches_higher_priorities_first_without_rate_limits() {
+        let mut queue = DispatchQueue::new(config(Vec::new()));
+        let queued = vec![
+            push(&mut queue, "a", "low"),
+            push(&mut queue, "b", "normal"),
+            push(&mut queue, "c", "high"),
+            push(&mut queue, "d", "normal"),
+        ];
+        assert_eq!(drain(&mut queue, queued), ["c", "b", "d", "a"]);
+        assert_eq!(queue.in_flight, 0);
+    }
+
+    #[test]
+    fn shares_dispatches_by_weight() {
+        let mut queue = DispatchQueue::new(config(vec![ClientDispatchConfig {
+            clientid: "heavy".to_string(),
+            weight: Some(2.0),
+            rate_per_minute: None,
+        }]));
+        let mut queued = Vec::new();
+        for _ in 0..3 {
+            queued.push(push(&mut queue, "light", "normal"));
+        }
+        for _ in 0..6 {
+            queued.push(push(&mut queue, "heavy", "normal"));
+        }
+        assert_eq!(
+            drain(&mut queue, queued),
+            ["heavy", "light", "heavy", "heavy", "light", "heavy", "heavy", "light", "heavy"]
+        );
+    }
+
+    #[tokio::test]
+    async fn finds_entries_by_request_id_and_follows_config_changes() {
+        let dispatcher = Dispatcher::new(config(Vec::new()));
+        let _first = dispatcher.enqueue(&request("a", "normal"), None);
+        let second = dispatcher.enqueue(&request("b", "normal"), Some("req-2"));
+        let entry = dispatcher.entry("req-2").unwrap();
+        assert_eq!((entry.id, entry.position), (second.id.clone(), 2));
+        assert!(dispatcher.entry("req-3").is_none());
+
+        let cfg: AppConfig = serde_json::from_value(json!({
+            "api_key": "key",
+            "log_level": "info",
+        }))
+        .unwrap();
+        let (sender, receiver) = watch::channel(Arc::new(cfg.clone()));
+        let follower = dispatcher.follow(receiver);
+        let mut changed = cfg;
+        changed.dispatch.max_in_flight = 5;
+        sender.send(Arc::new(changed)).unwrap();
+        drop(sender);
+        follower.await.unwrap();
+        assert_eq!(dispatcher.queue.lock().unwrap().config.max_in_flight, 5);
+    }
+
+    #[test]
+    fn refills_rate_budget_over_time() {
+        let mut queue = DispatchQueue::new(DispatchConfig {
+            default_rate_per_minute: Some(2),
+            ..Default::default()
+        });
+        let mut queued: Vec<Queued> = (0..3).map(|_| push(&mut queue, "a", "normal")).collect();
+        let mut other = push(&mut queue, "b", "normal");
+
+        // A minute's worth goes straight away, and other clients aren't held up.
+        let now = Instant::now();
+        assert_eq!(queue.dispatch_ready(now), Some(Duration::from_secs(30)));
+        let dispatched = queued
+            .iter_mut()
+            .filter_map(|q| q.receiver.try_recv().ok())
+            .count();
+        assert_eq!(dispatched, 2);
+        assert!(other.receiver.try_recv().is_ok());
+        assert_eq!(queue.entries().len(), 1);
+
+        assert_eq!(
+            queue.dispatch_ready(now + Duration::from_secs(15)),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(queue.dispatch_ready(now + Duration::from_secs(30)), None);
+        assert!(queue.entries().is_empty());
+        assert!(queued[2].receiver.try_recv().is_ok());
+    }
+}
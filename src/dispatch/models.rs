@@ -0,0 +1,51 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+pub use ecs_task_spawner_models::dispatch::models::{QueueEntry, QueueStatus};
+use tokio::sync::{oneshot, Notify};
+
+use crate::config::models::DispatchConfig;
+
+// Orders pending spawns by priority, then by weighted fair queuing across clientids, and
+// releases them no faster than each client's dispatch rate and the global in-flight budget.
+#[derive(Clone)]
+pub struct Dispatcher {
+    pub queue: Arc<Mutex<DispatchQueue>>,
+    pub notify: Arc<Notify>,
+}
+
+pub struct DispatchQueue {
+    pub config: DispatchConfig,
+    // Kept sorted in dispatch order, ignoring rate limits.
+    pub entries: Vec<QueuedSpawn>,
+    pub clients: HashMap<String, ClientState>,
+    // Dispatched spawns whose ticket is still held.
+    pub in_flight: usize,
+    // Finish tag of the last dispatched spawn.
+    pub virtual_time: f64,
+    pub next_seq: u64,
+}
+
+pub struct ClientState {
+    pub last_finish: f64,
+    pub tokens: f64,
+    pub refilled_at: Instant,
+}
+
+pub struct QueuedSpawn {
+    pub entry: QueueEntry,
+    pub finish: f64,
+    pub seq: u64,
+    pub sender: oneshot::Sender<()>,
+}
+
+// Resolves once the spawn may go ahead. Held until the launch is done; dropping it takes the
+// spawn out of the queue or gives its in-flight slot back.
+pub struct DispatchTicket {
+    pub id: String,
+    pub receiver: oneshot::Receiver<()>,
+    pub dispatcher: Dispatcher,
+}
@@ -0,0 +1,131 @@
+// An in-memory EcsTaskRepo for tests.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde_json::json;
+use tokio::time::Instant;
+
+use super::models::{EcsTag, EcsTaskDefinition, EcsTaskRepo, TaskFamily, TaskInfo};
+use crate::errors::models::AppError;
+
+// Tasks it was told about or spawned, and the ones stopped through it.
+#[derive(Clone, Default)]
+pub struct FakeRepo {
+    pub tasks: Arc<Mutex<Vec<TaskInfo>>>,
+    // How long spawned tasks stay out of ListTasks, which lags behind RunTask.
+    pub list_lag: Duration,
+    pub listed_at: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl FakeRepo {
+    pub fn run(&self, arn: &str, tags: &[(&str, &str)]) {
+        self.tasks.lock().unwrap().push(
+            serde_json::from_value(json!({
+                "task_arn": arn,
+                "status": "RUNNING",
+                "created_at": "2026-01-01T00:00:00Z",
+                "image": "public.ecr.aws/soi/worker:latest",
+                "tags": tags
+                    .iter()
+                    .map(|(key, value)| json!({ "key": key, "value": value }))
+                    .collect::<Vec<_>>(),
+                "containers": [],
+            }))
+            .unwrap(),
+        );
+    }
+
+    pub fn set_status(&self, arn: &str, status: &str) {
+        for task in self.tasks.lock().unwrap().iter_mut() {
+            if task.task_arn == arn {
+                task.status = status.to_string();
+            }
+        }
+    }
+
+    pub fn status(&self, arn: &str) -> String {
+        self.describe_now(&[arn.to_string()])[0].status.clone()
+    }
+
+    pub fn matching(&self, keep: impl Fn(&TaskInfo) -> bool) -> Vec<TaskInfo> {
+        self.tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|task| keep(task))
+            .cloned()
+            .collect()
+    }
+
+    pub fn describe_now(&self, task_arns: &[String]) -> Vec<TaskInfo> {
+        self.matching(|task| task_arns.contains(&task.task_arn))
+    }
+}
+
+#[async_trait]
+impl EcsTaskRepo for FakeRepo {
+    async fn spawn(&self, task: EcsTaskDefinition) -> Result<TaskInfo, AppError> {
+        let arn = format!("arn-{}", self.tasks.lock().unwrap().len() + 1);
+        let tags: Vec<(&str, &str)> = task
+            .tags
+            .iter()
+            .map(|tag| (tag.key.as_str(), tag.value.as_str()))
+            .collect();
+        self.run(&arn, &tags);
+        let mut tasks = self.tasks.lock().unwrap();
+        let spawned = tasks.last_mut().unwrap();
+        spawned.request_id = task.started_by;
+        self.listed_at
+            .lock()
+            .unwrap()
+            .insert(arn, Instant::now() + self.list_lag);
+        Ok(spawned.clone())
+    }
+
+    async fn get_task_family(&self, family: TaskFamily) -> Result<Vec<TaskInfo>, AppError> {
+        Err(AppError::CustomError(format!(
+            "the fake repo doesn't keep task families, asked for {}",
+            family.task_family
+        )))
+    }
+
+    async fn get_tasks(&self, tag: EcsTag) -> Result<Vec<TaskInfo>, AppError> {
+        Ok(self.matching(|task| {
+            task.tags
+                .iter()
+                .any(|t| t.key == tag.key && t.value == tag.value)
+        }))
+    }
+
+    async fn list_active(&self, tags: &[EcsTag]) -> Result<Vec<TaskInfo>, AppError> {
+        let listed_at = self.listed_at.lock().unwrap().clone();
+        Ok(self.matching(|task| {
+            task.status != "STOPPED"
+                && listed_at
+                    .get(&task.task_arn)
+                    .is_none_or(|at| *at <= Instant::now())
+                && tags.iter().all(|tag| {
+                    task.tags
+                        .iter()
+                        .any(|t| t.key == tag.key && t.value == tag.value)
+                })
+        }))
+    }
+
+    async fn started_by(&self, request_id: &str) -> Result<Vec<TaskInfo>, AppError> {
+        Ok(self.matching(|task| task.request_id.as_deref() == Some(request_id)))
+    }
+
+    async fn describe(&self, task_arns: &[String]) -> Result<Vec<TaskInfo>, AppError> {
+        Ok(self.describe_now(task_arns))
+    }
+
+    async fn stop(&self, task_arn: &str, _reason: &str) -> Result<(), AppError> {
+        self.set_status(task_arn, "DEPROVISIONING");
+        Ok(())
+    }
+}
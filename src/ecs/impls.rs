@@ -1,109 +1,511 @@
-use super::models::{EcsRepo, EcsTag, EcsTaskDefinition, EcsTaskRepo, TaskFamily, TaskInfo};
-use crate::errors::models::AppError;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use super::models::{
+    CapacityProviderItem, ContainerDependencyCondition, ContainerDependencyItem, ContainerInfo,
+    EcsRepo, EcsSecret, EcsTag, EcsTaskDefinition, EcsTaskRepo, HealthCheckConfig, LogOption,
+    LogRouting, PlacementConstraintKind, PlacementStrategyKind, PortMappingItem, PortProtocol,
+    TaskFamily, TaskInfo, WorkerLaunchType, WorkerNetworkMode, DEFAULT_WORKER_CONTAINER, FARGATE,
+    FARGATE_SPOT, LOG_ROUTER_CONTAINER, WORKER_CONTAINER_TAG,
+};
+use crate::{errors::models::AppError, secrets::impls::redact, sizing::models::Architecture};
 use async_trait::async_trait;
 use aws_sdk_ecs::{
+    operation::run_task::RunTaskOutput,
     types::{
-        AssignPublicIp, AwsVpcConfiguration, Compatibility, ContainerDefinition, KeyValuePair,
-        LaunchType, LogConfiguration, LogDriver, NetworkConfiguration, NetworkMode, Task,
+        AssignPublicIp, AwsVpcConfiguration, CapacityProviderStrategyItem, Compatibility,
+        ContainerCondition, ContainerDefinition, ContainerDependency, ContainerOverride,
+        CpuArchitecture, DesiredStatus, EphemeralStorage, Failure, FirelensConfiguration,
+        FirelensConfigurationType, HealthCheck, KeyValuePair, LaunchType, LogConfiguration,
+        LogDriver, NetworkConfiguration, NetworkMode, OsFamily, PlacementConstraint,
+        PlacementConstraintType, PlacementStrategy, PlacementStrategyType, PortMapping,
+        RuntimePlatform, Secret, Tag, Task, TaskField, TaskOverride, TransportProtocol,
     },
     Client as EcsClient,
 };
+use chrono::{DateTime, Utc};
 
 impl EcsRepo {
-    pub fn new(client: EcsClient) -> Self {
-        EcsRepo { client }
+    pub fn new(client: EcsClient, cluster_name: String) -> Self {
+        EcsRepo {
+            client,
+            cluster_name,
+            task_definitions: Arc::default(),
+        }
     }
-}
 
-#[async_trait]
-impl EcsTaskRepo for EcsRepo {
-    async fn spawn(&self, task: EcsTaskDefinition) -> Result<TaskInfo, AppError> {
-        let log_configuration = LogConfiguration::builder()
-            .log_driver(LogDriver::Awslogs)
-            .options("awslogs-group", task.log_group)
-            .options("awslogs-region", "us-east-1")
-            .options("awslogs-stream-prefix", "ecs")
-            .build()?;
-
-        let mut environment_variables = Vec::new();
-        for envvar in task.env_vars.iter() {
-            environment_variables.push(
-                KeyValuePair::builder()
-                    .name(envvar.name.clone())
-                    .value(envvar.value.clone())
+    // The vendor's own task definition if it has one, else a base definition registered the
+    // first time it is needed. Everything that varies per request goes in RunTask overrides.
+    async fn task_definition_arn(&self, task: &EcsTaskDefinition) -> Result<String, AppError> {
+        if let Some(task_definition) = &task.task_definition {
+            return Ok(task_definition.clone());
+        }
+
+        let base = task.base();
+        // Held across the registration so concurrent first spawns don't each register one.
+        let mut registered = self.task_definitions.lock().await;
+        if let Some(arn) = registered.get(&base) {
+            return Ok(arn.clone());
+        }
+
+        let arn = self.register_task_definition(task).await?;
+        tracing::info!("registered task definition {}", arn);
+        registered.insert(base, arn.clone());
+
+        Ok(arn)
+    }
+
+    async fn register_task_definition(&self, task: &EcsTaskDefinition) -> Result<String, AppError> {
+        let region = self
+            .client
+            .config()
+            .region()
+            .map(|region| region.as_ref().to_string())
+            .unwrap_or_default();
+        let log_configuration = match &task.logging {
+            LogRouting::Awslogs {
+                group,
+                region: group_region,
+                stream_prefix,
+            } => Some(
+                LogConfiguration::builder()
+                    .log_driver(LogDriver::Awslogs)
+                    .options(
+                        "awslogs-group",
+                        group.clone().unwrap_or_else(|| task.log_group.clone()),
+                    )
+                    .options("awslogs-region", group_region.as_ref().unwrap_or(&region))
+                    .options("awslogs-stream-prefix", stream_prefix)
+                    .build()?,
+            ),
+            LogRouting::Firelens { options, .. } => Some(
+                LogConfiguration::builder()
+                    .log_driver(LogDriver::Awsfirelens)
+                    .set_options(Some(log_options(options)))
+                    .build()?,
+            ),
+            LogRouting::Splunk { options } => Some(
+                LogConfiguration::builder()
+                    .log_driver(LogDriver::Splunk)
+                    .set_options(Some(log_options(options)))
+                    .build()?,
+            ),
+            LogRouting::None => None,
+        };
+
+        // Sizes here are only defaults, every RunTask overrides them.
+        let worker_size = task.worker_size();
+        let mut container_definitions = vec![ContainerDefinition::builder()
+            .name(task.worker.name.clone())
+            .image(task.image.clone())
+            .cpu(worker_size.cpu as i32)
+            .memory(worker_size.memory as i32)
+            .essential(true)
+            .set_log_configuration(log_configuration.clone())
+            .set_depends_on(Some(container_dependencies(&task.worker.depends_on)?))
+            .set_health_check(
+                task.worker
+                    .health_check
+                    .as_ref()
+                    .map(health_check)
+                    .transpose()?,
+            )
+            .set_port_mappings(Some(port_mappings(&task.worker.port_mappings)))
+            .set_secrets(Some(secrets(&task.secrets)?))
+            .build()];
+
+        for sidecar in task.sidecars.iter() {
+            let environment = sidecar
+                .environment
+                .iter()
+                .map(|envvar| {
+                    KeyValuePair::builder()
+                        .name(envvar.name.clone())
+                        .value(envvar.value.clone())
+                        .build()
+                })
+                .collect();
+
+            container_definitions.push(
+                ContainerDefinition::builder()
+                    .name(sidecar.name.clone())
+                    .image(sidecar.image.clone())
+                    .set_cpu((sidecar.cpu > 0).then_some(sidecar.cpu as i32))
+                    .set_memory((sidecar.memory > 0).then_some(sidecar.memory as i32))
+                    .essential(sidecar.essential)
+                    .set_command(sidecar.command.clone())
+                    .set_environment(Some(environment))
+                    .set_log_configuration(log_configuration.clone())
+                    .set_depends_on(Some(container_dependencies(&sidecar.depends_on)?))
+                    .set_health_check(
+                        sidecar
+                            .health_check
+                            .as_ref()
+                            .map(health_check)
+                            .transpose()?,
+                    )
+                    .set_port_mappings(Some(port_mappings(&sidecar.port_mappings)))
                     .build(),
             );
         }
 
-        let container_definition = ContainerDefinition::builder()
-            .name("my-container")
-            .image(task.image)
-            .cpu(256)
-            .memory(512)
-            .essential(true)
-            .log_configuration(log_configuration)
-            .set_environment(Some(environment_variables))
+        // The router has to be up before anything can log through it, and logs itself to
+        // CloudWatch so broken routing can be debugged.
+        if let LogRouting::Firelens {
+            router_image,
+            router_memory,
+            ..
+        } = &task.logging
+        {
+            container_definitions.push(
+                ContainerDefinition::builder()
+                    .name(LOG_ROUTER_CONTAINER)
+                    .image(router_image.clone())
+                    .memory(*router_memory as i32)
+                    .essential(true)
+                    .firelens_configuration(
+                        FirelensConfiguration::builder()
+                            .r#type(FirelensConfigurationType::Fluentbit)
+                            .build()?,
+                    )
+                    .log_configuration(
+                        LogConfiguration::builder()
+                            .log_driver(LogDriver::Awslogs)
+                            .options("awslogs-group", task.log_group.clone())
+                            .options("awslogs-region", region.clone())
+                            .options("awslogs-stream-prefix", "firelens")
+                            .build()?,
+                    )
+                    .build(),
+            );
+        }
+
+        let network_mode = match task.network_mode {
+            WorkerNetworkMode::Awsvpc => NetworkMode::Awsvpc,
+            WorkerNetworkMode::Bridge => NetworkMode::Bridge,
+            WorkerNetworkMode::Host => NetworkMode::Host,
+        };
+        let fargate = task.launch_type == WorkerLaunchType::Fargate;
+        let runtime_platform = RuntimePlatform::builder()
+            .cpu_architecture(match task.architecture {
+                Architecture::X86_64 => CpuArchitecture::X8664,
+                Architecture::Arm64 => CpuArchitecture::Arm64,
+            })
+            .operating_system_family(OsFamily::Linux)
             .build();
 
+        // EC2 instances pick their own architecture, so it is Fargate only.
         let task_definition_response = self
             .client
             .register_task_definition()
-            .family("my-task-family")
-            .task_role_arn(task.iam_role_arn)
-            .execution_role_arn(task.execution_role_arn)
-            .network_mode(NetworkMode::Awsvpc)
-            .requires_compatibilities(Compatibility::Fargate)
-            .cpu("256")
-            .memory("512")
-            .container_definitions(container_definition)
+            .family(task.family.clone())
+            .task_role_arn(task.iam_role_arn.clone())
+            .execution_role_arn(task.execution_role_arn.clone())
+            .network_mode(network_mode)
+            .requires_compatibilities(if fargate {
+                Compatibility::Fargate
+            } else {
+                Compatibility::Ec2
+            })
+            .cpu(task.size.cpu.to_string())
+            .memory(task.size.memory.to_string())
+            .set_container_definitions(Some(container_definitions))
+            .set_runtime_platform(fargate.then_some(runtime_platform))
             .send()
             .await?;
 
-        let task_definition_arn = task_definition_response
+        task_definition_response
             .task_definition
-            .unwrap()
-            .task_definition_arn
-            .unwrap();
-
-        let network_configuration = NetworkConfiguration::builder()
-            .awsvpc_configuration(
-                AwsVpcConfiguration::builder()
-                    .subnets(task.subnet_id)
-                    .assign_public_ip(AssignPublicIp::Disabled)
-                    .security_groups(task.security_group_id)
-                    .build()?,
+            .and_then(|task_definition| task_definition.task_definition_arn)
+            .ok_or_else(|| {
+                AppError::TaskSpawnError("RegisterTaskDefinition returned no ARN".to_string())
+            })
+    }
+
+    // An empty strategy launches on plain on-demand Fargate, or on EC2 for EC2 workers.
+    async fn run_task(
+        &self,
+        task: &EcsTaskDefinition,
+        task_definition_arn: &str,
+        strategy: &[CapacityProviderItem],
+    ) -> Result<RunTaskOutput, AppError> {
+        // Only awsvpc tasks get an ENI of their own.
+        let network_configuration = match task.network_mode {
+            WorkerNetworkMode::Awsvpc => Some(
+                NetworkConfiguration::builder()
+                    .awsvpc_configuration(
+                        AwsVpcConfiguration::builder()
+                            .subnets(task.subnet_id.clone())
+                            .assign_public_ip(AssignPublicIp::Disabled)
+                            .security_groups(task.security_group_id.clone())
+                            .build()?,
+                    )
+                    .build(),
+            ),
+            WorkerNetworkMode::Bridge | WorkerNetworkMode::Host => None,
+        };
+
+        let placement_constraints = task
+            .placement_constraints
+            .iter()
+            .map(|constraint| {
+                PlacementConstraint::builder()
+                    .r#type(match constraint.kind {
+                        PlacementConstraintKind::DistinctInstance => {
+                            PlacementConstraintType::DistinctInstance
+                        }
+                        PlacementConstraintKind::MemberOf => PlacementConstraintType::MemberOf,
+                    })
+                    .set_expression(constraint.expression.clone())
+                    .build()
+            })
+            .collect();
+
+        let placement_strategy = task
+            .placement_strategies
+            .iter()
+            .map(|strategy| {
+                PlacementStrategy::builder()
+                    .r#type(match strategy.kind {
+                        PlacementStrategyKind::Binpack => PlacementStrategyType::Binpack,
+                        PlacementStrategyKind::Spread => PlacementStrategyType::Spread,
+                        PlacementStrategyKind::Random => PlacementStrategyType::Random,
+                    })
+                    .set_field(strategy.field.clone())
+                    .build()
+            })
+            .collect();
+
+        let environment = task
+            .env_vars
+            .iter()
+            .map(|envvar| {
+                KeyValuePair::builder()
+                    .name(envvar.name.clone())
+                    .value(envvar.value.clone())
+                    .build()
+            })
+            .collect();
+
+        // A container limit below the task's memory would OOM the worker before it used
+        // the size it was given, so the container is resized along with the task.
+        let worker_size = task.worker_size();
+        let container_override = ContainerOverride::builder()
+            .name(task.worker.name.clone())
+            .set_command(task.command.clone())
+            .set_environment(Some(environment))
+            .cpu(worker_size.cpu as i32)
+            .memory(worker_size.memory as i32)
+            .build();
+
+        let overrides = TaskOverride::builder()
+            .container_overrides(container_override)
+            .cpu(task.size.cpu.to_string())
+            .memory(task.size.memory.to_string())
+            .set_task_role_arn(task.task_role_override.clone())
+            .set_ephemeral_storage(
+                task.ephemeral_storage_gib
+                    .filter(|_| task.launch_type == WorkerLaunchType::Fargate)
+                    .map(|gib| EphemeralStorage::builder().size_in_gib(gib as i32).build()),
             )
             .build();
 
+        let tags = task
+            .tags
+            .iter()
+            .map(|tag| Tag::builder().key(&tag.key).value(&tag.value).build())
+            .collect();
+
+        let mut capacity_provider_strategy = Vec::new();
+        for item in strategy.iter() {
+            capacity_provider_strategy.push(
+                CapacityProviderStrategyItem::builder()
+                    .capacity_provider(&item.provider)
+                    .weight(item.weight as i32)
+                    .base(item.base as i32)
+                    .build()?,
+            );
+        }
+
+        // A launch type and a capacity provider strategy are mutually exclusive.
+        let (launch_type, capacity_provider_strategy) = if capacity_provider_strategy.is_empty() {
+            let launch_type = match task.launch_type {
+                WorkerLaunchType::Fargate => LaunchType::Fargate,
+                WorkerLaunchType::Ec2 => LaunchType::Ec2,
+            };
+            (Some(launch_type), None)
+        } else {
+            (None, Some(capacity_provider_strategy))
+        };
+
         let response = self
             .client
             .run_task()
-            .cluster(task.cluster_name)
-            .launch_type(LaunchType::Fargate)
+            .cluster(task.cluster_name.clone())
+            .set_launch_type(launch_type)
+            .set_capacity_provider_strategy(capacity_provider_strategy)
             .task_definition(task_definition_arn)
-            .network_configuration(network_configuration)
+            .overrides(overrides)
+            .set_network_configuration(network_configuration)
+            .set_placement_constraints(Some(placement_constraints))
+            .set_placement_strategy(Some(placement_strategy))
+            .set_tags(Some(tags))
+            .set_started_by(task.started_by.clone())
             .count(1)
             .send()
             .await?;
 
-        // TODO: handle the case where there are no tasks
-        let new_task = response.tasks().first().unwrap();
-        let task_info = TaskInfo::from(new_task);
+        Ok(response)
+    }
+}
+
+fn container_dependencies(
+    depends_on: &[ContainerDependencyItem],
+) -> Result<Vec<ContainerDependency>, AppError> {
+    let mut dependencies = Vec::new();
+    for dependency in depends_on.iter() {
+        dependencies.push(
+            ContainerDependency::builder()
+                .container_name(dependency.container.clone())
+                .condition(match dependency.condition {
+                    ContainerDependencyCondition::Start => ContainerCondition::Start,
+                    ContainerDependencyCondition::Healthy => ContainerCondition::Healthy,
+                    ContainerDependencyCondition::Complete => ContainerCondition::Complete,
+                    ContainerDependencyCondition::Success => ContainerCondition::Success,
+                })
+                .build()?,
+        );
+    }
+    Ok(dependencies)
+}
+
+fn health_check(config: &HealthCheckConfig) -> Result<HealthCheck, AppError> {
+    let health_check = HealthCheck::builder()
+        .set_command(Some(config.command.clone()))
+        .interval(config.interval_secs as i32)
+        .timeout(config.timeout_secs as i32)
+        .retries(config.retries as i32)
+        .start_period(config.start_period_secs as i32)
+        .build()?;
+    Ok(health_check)
+}
+
+// A missing host port lets ECS pick one in bridge mode, and means the container port
+// otherwise.
+fn port_mappings(mappings: &[PortMappingItem]) -> Vec<PortMapping> {
+    mappings
+        .iter()
+        .map(|mapping| {
+            PortMapping::builder()
+                .container_port(i32::from(mapping.container_port))
+                .set_host_port(mapping.host_port.map(i32::from))
+                .protocol(match mapping.protocol {
+                    PortProtocol::Tcp => TransportProtocol::Tcp,
+                    PortProtocol::Udp => TransportProtocol::Udp,
+                })
+                .build()
+        })
+        .collect()
+}
 
-        Ok(task_info)
+fn secrets(secrets: &[EcsSecret]) -> Result<Vec<Secret>, AppError> {
+    let mut converted = Vec::new();
+    for secret in secrets.iter() {
+        converted.push(
+            Secret::builder()
+                .name(secret.name.clone())
+                .value_from(secret.value_from.clone())
+                .build()?,
+        );
+    }
+    Ok(converted)
+}
+
+fn log_options(options: &[LogOption]) -> HashMap<String, String> {
+    options
+        .iter()
+        .map(|option| (option.name.clone(), option.value.clone()))
+        .collect()
+}
+
+fn capacity_unavailable(failures: &[Failure]) -> bool {
+    failures.iter().any(|failure| {
+        failure
+            .reason()
+            .is_some_and(|reason| reason.to_lowercase().contains("capacity"))
+    })
+}
+
+fn failure_reasons(failures: &[Failure]) -> String {
+    if failures.is_empty() {
+        return "RunTask returned no tasks".to_string();
+    }
+    failures
+        .iter()
+        .map(|failure| {
+            format!(
+                "{} {}",
+                failure.reason().unwrap_or_default(),
+                failure.detail().unwrap_or_default()
+            )
+            .trim()
+            .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+#[async_trait]
+impl EcsTaskRepo for EcsRepo {
+    async fn spawn(&self, task: EcsTaskDefinition) -> Result<TaskInfo, AppError> {
+        let task_definition_arn = self.task_definition_arn(&task).await?;
+
+        let mut response = self
+            .run_task(&task, &task_definition_arn, &task.capacity_providers)
+            .await?;
+
+        // Spot capacity comes and goes. Rather than fail the load, run it on-demand.
+        let uses_spot = task
+            .capacity_providers
+            .iter()
+            .any(|item| item.provider == FARGATE_SPOT);
+        if response.tasks().is_empty() && uses_spot && capacity_unavailable(response.failures()) {
+            tracing::warn!(
+                "Fargate Spot capacity unavailable ({}), falling back to on-demand",
+                failure_reasons(response.failures())
+            );
+            let on_demand = [CapacityProviderItem {
+                provider: FARGATE.to_string(),
+                weight: 1,
+                base: 0,
+            }];
+            response = self
+                .run_task(&task, &task_definition_arn, &on_demand)
+                .await?;
+        }
+
+        let Some(new_task) = response.tasks().first() else {
+            return Err(AppError::TaskSpawnError(failure_reasons(
+                response.failures(),
+            )));
+        };
+        Ok(task_info(new_task))
     }
 
     async fn get_task_family(&self, task_family: TaskFamily) -> Result<Vec<TaskInfo>, AppError> {
         let list_tasks_response = self
             .client
             .list_tasks()
-            .cluster("cluster-name".to_string())
+            .cluster(self.cluster_name.clone())
             .send()
             .await?;
 
         let task_arns = list_tasks_response.task_arns().to_vec();
         if task_arns.is_empty() {
-            println!("No tasks found in the cluster.");
+            tracing::info!("no tasks found in cluster {}", self.cluster_name);
             return Err(AppError::CustomError(
                 "No tasks found in task family.".to_string(),
             ));
@@ -112,7 +514,7 @@ impl EcsTaskRepo for EcsRepo {
         let describe_tasks_response = self
             .client
             .describe_tasks()
-            .cluster("cluster-name".to_string())
+            .cluster(self.cluster_name.clone())
             .set_tasks(Some(task_arns.to_vec()))
             .send()
             .await?;
@@ -130,10 +532,7 @@ impl EcsTaskRepo for EcsRepo {
             })
             .collect();
 
-        let task_arns: Vec<TaskInfo> = filtered_tasks
-            .iter()
-            .map(|task| TaskInfo::from(*task))
-            .collect();
+        let task_arns: Vec<TaskInfo> = filtered_tasks.iter().map(|task| task_info(task)).collect();
 
         Ok(task_arns)
     }
@@ -143,7 +542,7 @@ impl EcsTaskRepo for EcsRepo {
         let list_tasks_response = self
             .client
             .list_tasks()
-            .cluster("cluster-name".to_string())
+            .cluster(self.cluster_name.clone())
             .send()
             .await?;
 
@@ -158,7 +557,7 @@ impl EcsTaskRepo for EcsRepo {
         let describe_tasks_response = self
             .client
             .describe_tasks()
-            .cluster("cluster-name".to_string())
+            .cluster(self.cluster_name.clone())
             .set_tasks(Some(task_arns.to_vec()))
             .send()
             .await?;
@@ -173,9 +572,321 @@ impl EcsTaskRepo for EcsRepo {
                     .iter()
                     .any(|t| t.key().unwrap() == tag.key && t.value().unwrap() == tag.value)
             })
-            .map(|task| TaskInfo::from(task))
+            .map(task_info)
             .collect();
 
         Ok(filtered_tasks)
     }
+
+    async fn list_active(&self, tags: &[EcsTag]) -> Result<Vec<TaskInfo>, AppError> {
+        // Tasks with a desired status of RUNNING are either PENDING or RUNNING.
+        let task_arns = self
+            .client
+            .list_tasks()
+            .cluster(self.cluster_name.clone())
+            .desired_status(DesiredStatus::Running)
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+
+        let mut active = Vec::new();
+        // DescribeTasks accepts at most 100 tasks per call.
+        for chunk in task_arns.chunks(100) {
+            let describe_tasks_response = self
+                .client
+                .describe_tasks()
+                .cluster(self.cluster_name.clone())
+                .set_tasks(Some(chunk.to_vec()))
+                .include(TaskField::Tags)
+                .send()
+                .await?;
+
+            active.extend(
+                describe_tasks_response
+                    .tasks()
+                    .iter()
+                    .filter(|task| {
+                        tags.iter().all(|tag| {
+                            task.tags().iter().any(|t| {
+                                t.key() == Some(tag.key.as_str())
+                                    && t.value() == Some(tag.value.as_str())
+                            })
+                        })
+                    })
+                    .map(task_info),
+            );
+        }
+
+        Ok(active)
+    }
+
+    async fn started_by(&self, request_id: &str) -> Result<Vec<TaskInfo>, AppError> {
+        // ListTasks only returns tasks of one desired status at a time.
+        let mut task_arns = Vec::new();
+        for status in [DesiredStatus::Running, DesiredStatus::Stopped] {
+            let arns: Vec<String> = self
+                .client
+                .list_tasks()
+                .cluster(self.cluster_name.clone())
+                .started_by(request_id)
+                .desired_status(status)
+                .into_paginator()
+                .items()
+                .send()
+                .try_collect()
+                .await?;
+            task_arns.extend(arns);
+        }
+
+        self.describe(&task_arns).await
+    }
+
+    async fn describe(&self, task_arns: &[String]) -> Result<Vec<TaskInfo>, AppError> {
+        let mut tasks = Vec::new();
+        for chunk in task_arns.chunks(100) {
+            let describe_tasks_response = self
+                .client
+                .describe_tasks()
+                .cluster(self.cluster_name.clone())
+                .set_tasks(Some(chunk.to_vec()))
+                .include(TaskField::Tags)
+                .send()
+                .await?;
+
+            tasks.extend(describe_tasks_response.tasks().iter().map(task_info));
+        }
+
+        Ok(tasks)
+    }
+
+    async fn stop(&self, task_arn: &str, reason: &str) -> Result<(), AppError> {
+        self.client
+            .stop_task()
+            .cluster(self.cluster_name.clone())
+            .task(task_arn)
+            .reason(reason)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+// What the API tells callers about a task.
+pub fn task_info(task: &Task) -> TaskInfo {
+    let tags: Vec<EcsTag> = task
+        .tags()
+        .to_vec()
+        .iter()
+        .map(|tag| EcsTag {
+            key: tag.key().unwrap_or_default().to_string(),
+            value: tag.value().unwrap_or_default().to_string(),
+        })
+        .collect();
+
+    let created_at = task.created_at().map(to_utc);
+
+    let running_duration = created_at.map(|created_at| {
+        Utc::now()
+            .signed_duration_since(created_at)
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+    });
+
+    let image: String = task
+        .containers()
+        .iter()
+        .map(|containers| containers.image().unwrap_or_default().to_string())
+        .collect();
+
+    let job_id = tags
+        .iter()
+        .find(|tag| tag.key == "job_id")
+        .map(|tag| tag.value.clone());
+
+    let worker_name = tags
+        .iter()
+        .find(|tag| tag.key == WORKER_CONTAINER_TAG)
+        .map_or(DEFAULT_WORKER_CONTAINER, |tag| tag.value.as_str());
+    let worker = task
+        .containers()
+        .iter()
+        .find(|container| container.name() == Some(worker_name));
+
+    TaskInfo {
+        task_arn: task.task_arn().unwrap_or_default().to_string(),
+        status: task.last_status().unwrap_or_default().to_string(),
+        created_at: created_at.unwrap_or_else(Utc::now),
+        running_duration,
+        image,
+        cpu_usage: None,    // Placeholder, will be fetched from CloudWatch
+        memory_usage: None, // Placeholder, will be fetched from CloudWatch
+        tags,
+        job_id,
+        request_id: task.started_by().map(str::to_string),
+        capacity_provider: task
+            .capacity_provider_name()
+            .or(task.launch_type().map(LaunchType::as_str))
+            .map(str::to_string),
+        stopped_at: task.stopped_at().map(to_utc),
+        stop_code: task.stop_code().map(|code| code.as_str().to_string()),
+        // Failures to fetch a secret name it in the reason.
+        stopped_reason: task.stopped_reason().map(redact),
+        exit_code: worker.and_then(|container| container.exit_code()),
+        container_reason: worker.and_then(|container| container.reason().map(redact)),
+        containers: task
+            .containers()
+            .iter()
+            .map(|container| ContainerInfo {
+                name: container.name().unwrap_or_default().to_string(),
+                status: container.last_status().unwrap_or_default().to_string(),
+                health_status: container
+                    .health_status()
+                    .map(|status| status.as_str().to_string()),
+                exit_code: container.exit_code(),
+                reason: container.reason().map(redact),
+            })
+            .collect(),
+    }
+}
+
+fn to_utc(t: &aws_sdk_ecs::primitives::DateTime) -> DateTime<Utc> {
+    let secs = t.secs().max(0) as u64;
+    let nanos = t.subsec_nanos();
+    DateTime::<Utc>::from(UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::models::AppConfig;
+    use aws_sdk_ecs::types::Container;
+
+    fn failure(reason: &str, detail: Option<&str>) -> Failure {
+        Failure::builder()
+            .arn("arn:aws:ecs:us-east-1:123456789012:task-definition/my-task-family:1")
+            .reason(reason)
+            .set_detail(detail.map(str::to_string))
+            .build()
+    }
+
+    #[test]
+    fn falls_back_to_on_demand_only_when_capacity_is_missing() {
+        assert!(capacity_unavailable(&[failure(
+            "Capacity is unavailable at this time. Please try again later or in a different availability zone",
+            None,
+        )]));
+        assert!(!capacity_unavailable(&[failure("RESOURCE:ENI", None)]));
+        assert!(!capacity_unavailable(&[]));
+    }
+
+    #[test]
+    fn explains_why_no_task_was_launched() {
+        assert_eq!(failure_reasons(&[]), "RunTask returned no tasks");
+        assert_eq!(
+            failure_reasons(&[
+                failure(
+                    "RESOURCE:MEMORY",
+                    Some("no container instance has enough memory")
+                ),
+                failure("AGENT", None),
+            ]),
+            "RESOURCE:MEMORY no container instance has enough memory; AGENT"
+        );
+    }
+
+    fn task(request: serde_json::Value) -> EcsTaskDefinition {
+        let cfg: AppConfig = serde_json::from_value(serde_json::json!({
+            "api_key": "secret",
+            "log_level": "info",
+            "vendors": { "bloomberg": { "image": "public.ecr.aws/soi/bloomberg-worker:1.0" } },
+        }))
+        .unwrap();
+        let mut tr = serde_json::json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+        });
+        tr.as_object_mut()
+            .unwrap()
+            .extend(request.as_object().unwrap().clone());
+        EcsTaskDefinition::new(serde_json::from_value(tr).unwrap(), &cfg).unwrap()
+    }
+
+    #[test]
+    fn sends_per_request_settings_as_overrides_of_one_base_definition() {
+        let plain = task(serde_json::json!({}));
+        let custom = task(serde_json::json!({
+            "clientid": "client-2",
+            "soiid": "soi-2",
+            "env": [{ "name": "BATCH_SIZE", "value": "10" }],
+            "command": ["worker", "--full"],
+            "task_role_arn": "arn:aws:iam::123456789012:role/bloomberg-reader",
+            "ephemeral_storage_gib": 50,
+        }));
+
+        assert_eq!(plain.base(), custom.base());
+        assert_eq!(plain.base().family, "soi-worker-bloomberg");
+
+        let env: Vec<(&str, &str)> = custom
+            .env_vars
+            .iter()
+            .map(|envvar| (envvar.name.as_str(), envvar.value.as_str()))
+            .collect();
+        assert_eq!(
+            env,
+            [("APP_DATA_URL", "s3://bucket/key"), ("BATCH_SIZE", "10")]
+        );
+        assert_eq!(
+            custom.command,
+            Some(vec!["worker".to_string(), "--full".to_string()])
+        );
+        assert_eq!(
+            custom.task_role_override.as_deref(),
+            Some("arn:aws:iam::123456789012:role/bloomberg-reader")
+        );
+        assert_eq!(plain.task_role_override, None);
+    }
+
+    #[test]
+    fn reports_the_launch_type_of_tasks_without_a_capacity_provider() {
+        let spot = Task::builder()
+            .capacity_provider_name(FARGATE_SPOT)
+            .launch_type(LaunchType::Fargate)
+            .build();
+        let ec2 = Task::builder().launch_type(LaunchType::Ec2).build();
+
+        assert_eq!(
+            task_info(&spot).capacity_provider.as_deref(),
+            Some(FARGATE_SPOT)
+        );
+        assert_eq!(task_info(&ec2).capacity_provider.as_deref(), Some("EC2"));
+        assert_eq!(task_info(&Task::builder().build()).capacity_provider, None);
+    }
+
+    #[test]
+    fn finds_the_worker_container_by_the_name_it_was_tagged_with() {
+        let container = |name: &str, exit_code: i32| {
+            Container::builder().name(name).exit_code(exit_code).build()
+        };
+        let task = |tags: Vec<Tag>| {
+            Task::builder()
+                .containers(container("log_router", 0))
+                .containers(container("worker", 1))
+                .containers(container(DEFAULT_WORKER_CONTAINER, 2))
+                .set_tags(Some(tags))
+                .build()
+        };
+        let tag = Tag::builder()
+            .key(WORKER_CONTAINER_TAG)
+            .value("worker")
+            .build();
+
+        assert_eq!(task_info(&task(vec![tag])).exit_code, Some(1));
+        // Tasks started before the tag existed.
+        assert_eq!(task_info(&task(Vec::new())).exit_code, Some(2));
+    }
 }
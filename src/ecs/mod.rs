@@ -1,2 +1,4 @@
+#[cfg(test)]
+pub mod fake;
 pub mod impls;
 pub mod models;
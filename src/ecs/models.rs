@@ -1,16 +1,30 @@
-use std::time::{Duration, UNIX_EPOCH};
+use std::{collections::HashMap, sync::Arc};
 
-use crate::errors::models::AppError;
+pub use ecs_task_spawner_models::ecs::models::{
+    CapacityProviderItem, ContainerInfo, EcsEnvVar, EcsTag, Priority, RequestId, RunningDuration,
+    TaskFamily, TaskInfo, TaskRequest,
+};
+
+use crate::{
+    config::models::AppConfig,
+    errors::models::AppError,
+    sizing::models::{Architecture, TaskSize},
+};
 use async_trait::async_trait;
-use aws_sdk_ecs::{types::Task, Client as EcsClient};
-use chrono::{DateTime, Utc};
+use aws_sdk_ecs::Client as EcsClient;
 use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
 
 // #[async_trait]
 // pub trait TaskSpawner: Send + Sync + Clone + 'static {
 //     async fn spawn_task(&self, task_req: TaskRequest) -> Result<String, AppError>;
 // }
 
+// Name of the container that runs the vendor worker, unless the vendor names it.
+pub const DEFAULT_WORKER_CONTAINER: &str = "my-container";
+// Tag that tells which of a task's containers is the worker.
+pub const WORKER_CONTAINER_TAG: &str = "worker_container";
+
 #[derive(Clone)]
 pub struct EcsTaskSpawner {
     pub ecs_client: EcsClient,
@@ -23,101 +37,422 @@ pub struct EcsTaskSpawner {
     pub execution_role_arn: String,
 }
 
-// New stuff
-// To spawna a task we need the following:
-// - s3 url
-// - soiid
-// - clientid
-// - worker type
+pub const FARGATE: &str = "FARGATE";
+pub const FARGATE_SPOT: &str = "FARGATE_SPOT";
+
+// Where a vendor's workers run. EC2 workers go to the cluster's container instances.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerLaunchType {
+    #[default]
+    Fargate,
+    Ec2,
+}
+
+// Fargate only supports awsvpc. Bridge and host tasks share the instance's network, so
+// they get no subnet or security group of their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerNetworkMode {
+    #[default]
+    Awsvpc,
+    Bridge,
+    Host,
+}
+
+// e.g. {type = "memberOf", expression = "attribute:ecs.instance-type =~ r6i.*"}.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlacementConstraintItem {
+    #[serde(rename = "type")]
+    pub kind: PlacementConstraintKind,
+    #[serde(default)]
+    pub expression: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PlacementConstraintKind {
+    DistinctInstance,
+    MemberOf,
+}
+
+// e.g. {type = "binpack", field = "memory"} or {type = "spread", field = "instanceId"}.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlacementStrategyItem {
+    #[serde(rename = "type")]
+    pub kind: PlacementStrategyKind,
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementStrategyKind {
+    Binpack,
+    Spread,
+    Random,
+}
+
+// Settings of the worker container that only the vendor decides.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerContainerConfig {
+    // Container overrides are addressed by it, so with a vendor's own task_definition it has
+    // to match the container there.
+    pub name: String,
+    pub depends_on: Vec<ContainerDependencyItem>,
+    pub health_check: Option<HealthCheckConfig>,
+    pub port_mappings: Vec<PortMappingItem>,
+}
+
+// A container that runs next to the worker, e.g. a log router, proxy or metrics agent.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SidecarConfig {
+    pub name: String,
+    pub image: String,
+    // The task stops when an essential container stops.
+    #[serde(default = "default_essential")]
+    pub essential: bool,
+    // Taken out of the task's size, the worker gets what is left.
+    #[serde(default)]
+    pub cpu: u32,
+    #[serde(default)]
+    pub memory: u32,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub environment: Vec<EcsEnvVar>,
+    #[serde(default)]
+    pub depends_on: Vec<ContainerDependencyItem>,
+    #[serde(default)]
+    pub health_check: Option<HealthCheckConfig>,
+    #[serde(default)]
+    pub port_mappings: Vec<PortMappingItem>,
+}
+
+impl Default for WorkerContainerConfig {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_WORKER_CONTAINER.to_string(),
+            depends_on: Vec::new(),
+            health_check: None,
+            port_mappings: Vec::new(),
+        }
+    }
+}
+
+fn default_essential() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ContainerDependencyItem {
+    pub container: String,
+    pub condition: ContainerDependencyCondition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ContainerDependencyCondition {
+    Start,
+    // The dependency has passed its health check.
+    Healthy,
+    // The dependency has exited, with any exit code.
+    Complete,
+    // The dependency has exited with code 0.
+    Success,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HealthCheckConfig {
+    // e.g. ["CMD-SHELL", "curl -f http://localhost:2020/ || exit 1"].
+    pub command: Vec<String>,
+    #[serde(default = "default_health_check_interval_secs")]
+    pub interval_secs: u32,
+    #[serde(default = "default_health_check_timeout_secs")]
+    pub timeout_secs: u32,
+    #[serde(default = "default_health_check_retries")]
+    pub retries: u32,
+    #[serde(default)]
+    pub start_period_secs: u32,
+}
+
+fn default_health_check_interval_secs() -> u32 {
+    30
+}
+
+fn default_health_check_timeout_secs() -> u32 {
+    5
+}
+
+fn default_health_check_retries() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PortMappingItem {
+    pub container_port: u16,
+    // Must equal the container port in awsvpc and host mode, picked by ECS in bridge mode
+    // when unset.
+    #[serde(default)]
+    pub host_port: Option<u16>,
+    #[serde(default)]
+    pub protocol: PortProtocol,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TaskRequest {
-    pub data_location: String,
-    pub soiid: String,
-    pub clientid: String,
-    pub vendor: String,
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PortProtocol {
+    #[default]
+    Tcp,
+    Udp,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TaskFamily {
-    pub task_family: String,
+// Where a vendor's containers send their logs. Option values and the stream prefix can use
+// {vendor}. Nothing per client, the base task definition is shared by all of them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "driver", rename_all = "snake_case")]
+pub enum LogRouting {
+    Awslogs {
+        // The cluster's log group when unset.
+        #[serde(default)]
+        group: Option<String>,
+        // The region the ECS client is configured for when unset.
+        #[serde(default)]
+        region: Option<String>,
+        #[serde(default = "default_stream_prefix")]
+        stream_prefix: String,
+    },
+    // Routes logs through a Fluent Bit container added to the task.
+    Firelens {
+        #[serde(default = "default_log_router_image")]
+        router_image: String,
+        // Taken out of the task's size like a sidecar's.
+        #[serde(default = "default_log_router_memory")]
+        router_memory: u32,
+        // Fluent Bit output settings, e.g. Name = cloudwatch_logs.
+        #[serde(default)]
+        options: Vec<LogOption>,
+    },
+    Splunk {
+        #[serde(default)]
+        options: Vec<LogOption>,
+    },
+    // The task definition has no log configuration and output is discarded.
+    None,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EcsTag {
-    pub key: String,
+impl Default for LogRouting {
+    fn default() -> Self {
+        LogRouting::Awslogs {
+            group: None,
+            region: None,
+            stream_prefix: default_stream_prefix(),
+        }
+    }
+}
+
+// A list rather than a map, since config keys are lowercased and log drivers are not
+// always case-insensitive.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LogOption {
+    pub name: String,
     pub value: String,
 }
 
+// Name of the Fluent Bit container FireLens vendors get.
+pub const LOG_ROUTER_CONTAINER: &str = "log-router";
+
+fn default_stream_prefix() -> String {
+    "ecs".to_string()
+}
+
+fn default_log_router_image() -> String {
+    "public.ecr.aws/aws-observability/aws-for-fluent-bit:stable".to_string()
+}
+
+fn default_log_router_memory() -> u32 {
+    64
+}
+
 #[derive(Debug, Clone)]
 pub struct EcsRepo {
     pub client: EcsClient,
+    pub cluster_name: String,
+    // Base task definitions this process registered, so each is only registered once.
+    pub task_definitions: Arc<Mutex<HashMap<BaseTaskDefinition, String>>>,
+}
+
+// The parts of a task definition that RunTask can't override.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BaseTaskDefinition {
+    pub family: String,
+    pub image: String,
+    pub logging: LogRouting,
+    pub log_group: String,
+    pub task_role_arn: String,
+    pub execution_role_arn: String,
+    pub launch_type: WorkerLaunchType,
+    pub network_mode: WorkerNetworkMode,
+    pub architecture: Architecture,
+    pub worker: WorkerContainerConfig,
+    pub sidecars: Vec<SidecarConfig>,
+    pub secrets: Vec<EcsSecret>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct EcsEnvVar {
+// An environment variable ECS fills in from SSM Parameter Store or Secrets Manager.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct EcsSecret {
     pub name: String,
-    pub value: String,
+    pub value_from: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct EcsTaskDefinition {
     pub cluster_name: String,
+    pub family: String,
+    // ARN or family[:revision] of a task definition managed outside the spawner. When unset
+    // a base definition is registered from the fields below.
+    pub task_definition: Option<String>,
     pub subnet_id: String,
     pub security_group_id: String,
     pub image: String,
+    // With the vendor's placeholders filled in.
+    pub logging: LogRouting,
+    // Where the FireLens log router logs to itself.
     pub log_group: String,
     pub iam_role_arn: String,
     pub execution_role_arn: String,
     pub tags: Vec<EcsTag>,
+    // Passed to RunTask as startedBy, so tasks can be listed by it.
+    pub started_by: Option<String>,
     pub env_vars: Vec<EcsEnvVar>,
+    pub command: Option<Vec<String>>,
+    pub task_role_override: Option<String>,
+    pub size: TaskSize,
+    pub ephemeral_storage_gib: Option<u32>,
+    pub architecture: Architecture,
+    pub capacity_providers: Vec<CapacityProviderItem>,
+    pub launch_type: WorkerLaunchType,
+    pub network_mode: WorkerNetworkMode,
+    pub placement_constraints: Vec<PlacementConstraintItem>,
+    pub placement_strategies: Vec<PlacementStrategyItem>,
+    pub worker: WorkerContainerConfig,
+    pub sidecars: Vec<SidecarConfig>,
+    pub secrets: Vec<EcsSecret>,
 }
 
 impl EcsTaskDefinition {
-    pub fn new(tr: TaskRequest) -> Result<Self, AppError> {
-        let mut tags = Vec::new();
-        tags.push(EcsTag {
-            key: "soiid".to_string(),
-            value: tr.soiid.clone(),
-        });
-        tags.push(EcsTag {
-            key: "clientid".to_string(),
-            value: tr.clientid.clone(),
-        });
-        tags.push(EcsTag {
-            key: "worker_type".to_string(),
-            value: tr.vendor.clone(),
-        });
-
-        // We can add more env vars to the worker task definition here.
-        let mut env_vars = Vec::new();
-        env_vars.push(EcsEnvVar {
+    pub fn new(tr: TaskRequest, cfg: &AppConfig) -> Result<Self, AppError> {
+        let vendor = cfg.vendor(&tr.vendor)?;
+
+        let mut tags = vec![
+            EcsTag {
+                key: "soiid".to_string(),
+                value: tr.soiid.clone(),
+            },
+            EcsTag {
+                key: "clientid".to_string(),
+                value: tr.clientid.clone(),
+            },
+            EcsTag {
+                key: "worker_type".to_string(),
+                value: tr.vendor.clone(),
+            },
+            EcsTag {
+                key: WORKER_CONTAINER_TAG.to_string(),
+                value: vendor.worker.name.clone(),
+            },
+        ];
+        if let Some(key) = vendor.concurrency_key(&tr)? {
+            tags.push(EcsTag {
+                key: "concurrency_key".to_string(),
+                value: key,
+            });
+        }
+
+        let mut env_vars = vec![EcsEnvVar {
             name: "APP_DATA_URL".to_string(),
             value: tr.data_location.clone(),
-        });
+        }];
+        env_vars.extend(tr.env.iter().cloned());
 
-        // NOTE: Add more vendor images here as needed.
-        // Given tr.vendor determine which worker image to use.
-        let image = match tr.vendor.as_str() {
-            "bloomberg" => "public.ecr.aws/soi/bloomberg-worker:latest".to_string(),
-            _ => return Err(AppError::UnsupportedVendor(tr.vendor)),
-        };
-
-        // TODO: Should we read these from SSM?
+        // Any of these can be an ssm:// reference, resolved when the config is loaded.
+        let ecs = &cfg.ecs;
         let task_defn = EcsTaskDefinition {
-            cluster_name: "default".to_string(),
-            subnet_id: "subnet-0c8b6b6b".to_string(),
-            security_group_id: "sg-0c8b6b6b".to_string(),
-            image,
-            log_group: "/ecs/soi-worker".to_string(),
-            iam_role_arn: "arn:aws:iam::123456789012:role/ecsTaskExecutionRole".to_string(),
-            execution_role_arn: "arn:aws:iam::123456789012:role/ecsTaskExecutionRole".to_string(),
+            cluster_name: ecs.cluster_name.clone(),
+            family: format!("soi-worker-{}", tr.vendor),
+            task_definition: vendor.task_definition.clone(),
+            // Picked from the subnet pool for each launch.
+            subnet_id: String::new(),
+            security_group_id: ecs.security_group_id.clone(),
+            image: vendor.image.clone(),
+            logging: vendor.logging(&tr.vendor, &ecs.log_group),
+            log_group: ecs.log_group.clone(),
+            iam_role_arn: ecs.task_role_arn.clone(),
+            execution_role_arn: ecs.execution_role_arn.clone(),
             tags,
+            started_by: None,
             env_vars,
+            command: tr.command.clone(),
+            task_role_override: tr.task_role_arn.clone(),
+            size: vendor.size,
+            ephemeral_storage_gib: tr.ephemeral_storage_gib,
+            architecture: tr
+                .architecture
+                .or_else(|| vendor.architectures.first().copied())
+                .unwrap_or_default(),
+            capacity_providers: vendor.capacity_providers(&tr)?,
+            launch_type: vendor.launch_type,
+            network_mode: vendor.network_mode,
+            placement_constraints: vendor.placement_constraints.clone(),
+            placement_strategies: vendor.placement_strategies.clone(),
+            worker: vendor.worker.clone(),
+            sidecars: vendor.sidecars.clone(),
+            secrets: vendor.secrets.clone(),
         };
 
         Ok(task_defn)
     }
+
+    pub fn base(&self) -> BaseTaskDefinition {
+        BaseTaskDefinition {
+            family: self.family.clone(),
+            image: self.image.clone(),
+            logging: self.logging.clone(),
+            log_group: self.log_group.clone(),
+            task_role_arn: self.iam_role_arn.clone(),
+            execution_role_arn: self.execution_role_arn.clone(),
+            launch_type: self.launch_type,
+            network_mode: self.network_mode,
+            architecture: self.architecture,
+            worker: self.worker.clone(),
+            sidecars: self.sidecars.clone(),
+            secrets: self.secrets.clone(),
+        }
+    }
+
+    // What is left of the task's size for the worker once the sidecars and log router have
+    // theirs.
+    pub fn worker_size(&self) -> TaskSize {
+        let router_memory = match &self.logging {
+            LogRouting::Firelens { router_memory, .. } => *router_memory,
+            _ => 0,
+        };
+        TaskSize {
+            cpu: self
+                .size
+                .cpu
+                .saturating_sub(self.sidecars.iter().map(|sidecar| sidecar.cpu).sum()),
+            memory: self.size.memory.saturating_sub(
+                self.sidecars
+                    .iter()
+                    .map(|sidecar| sidecar.memory)
+                    .sum::<u32>()
+                    + router_memory,
+            ),
+        }
+    }
 }
 
 #[async_trait]
@@ -128,60 +463,11 @@ pub trait EcsTaskRepo: Send + Sync + Clone + 'static {
     async fn get_task_family(&self, task_family: TaskFamily) -> Result<Vec<TaskInfo>, AppError>;
     // Return tasks with a given tag info. Would be good to return a struct contaning task info and metrics.
     async fn get_tasks(&self, tag: EcsTag) -> Result<Vec<TaskInfo>, AppError>;
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TaskInfo {
-    pub task_arn: String,
-    pub status: String,
-    pub created_at: DateTime<Utc>,
-    pub running_duration: Option<Duration>,
-    pub image: String,
-    pub cpu_usage: Option<f64>,
-    pub memory_usage: Option<f64>,
-    pub tags: Vec<EcsTag>,
-}
-
-impl From<&Task> for TaskInfo {
-    fn from(task: &Task) -> Self {
-        let tags = task
-            .tags()
-            .to_vec()
-            .iter()
-            .map(|tag| EcsTag {
-                key: tag.key().unwrap_or_default().to_string(),
-                value: tag.value().unwrap_or_default().to_string(),
-            })
-            .collect();
-
-        let created_at = task.created_at().map(|t| {
-            let secs = t.secs().max(0) as u64;
-            let nanos = t.subsec_nanos();
-            DateTime::<Utc>::from(UNIX_EPOCH + Duration::new(secs, nanos))
-        });
-
-        let running_duration = created_at.map(|created_at| {
-            Utc::now()
-                .signed_duration_since(created_at)
-                .to_std()
-                .unwrap_or(Duration::ZERO)
-        });
-
-        let image: String = task
-            .containers()
-            .iter()
-            .map(|containers| containers.image().unwrap_or_default().to_string())
-            .collect();
-
-        TaskInfo {
-            task_arn: task.task_arn().unwrap_or_default().to_string(),
-            status: task.last_status().unwrap_or_default().to_string(),
-            created_at: created_at.unwrap_or_else(Utc::now),
-            running_duration,
-            image,
-            cpu_usage: None,    // Placeholder, will be fetched from CloudWatch
-            memory_usage: None, // Placeholder, will be fetched from CloudWatch
-            tags,
-        }
-    }
+    // Returns the RUNNING/PENDING tasks carrying every one of the given tags.
+    async fn list_active(&self, tags: &[EcsTag]) -> Result<Vec<TaskInfo>, AppError>;
+    // Returns the running and recently stopped tasks started by the given request.
+    async fn started_by(&self, request_id: &str) -> Result<Vec<TaskInfo>, AppError>;
+    // Returns the current state of the given tasks, including stopped ones.
+    async fn describe(&self, task_arns: &[String]) -> Result<Vec<TaskInfo>, AppError>;
+    async fn stop(&self, task_arn: &str, reason: &str) -> Result<(), AppError>;
 }
@@ -1,10 +1,20 @@
-use super::models::AppError;
+use aws_sdk_ecs::{
+    error::SdkError,
+    operation::{
+        describe_tasks::DescribeTasksError, list_tasks::ListTasksError,
+        register_task_definition::RegisterTaskDefinitionError, run_task::RunTaskError,
+        stop_task::StopTaskError,
+    },
+};
+use aws_sdk_iam::operation::simulate_principal_policy::SimulatePrincipalPolicyError;
+
+use super::models::{AppError, ErrorBody, ErrorResponse};
+use crate::{logging::impls::current_request_id, secrets::impls::redact};
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
 
 impl AppError {
     pub fn error_type(&self) -> &str {
@@ -14,18 +24,45 @@ impl AppError {
             AppError::ValidationError(_) => "VALIDATION_ERROR",
             AppError::NotFoundError(_) => "NOT_FOUND_ERROR",
             AppError::UnauthorizedError(_) => "UNAUTHORIZED_ERROR",
+            AppError::ForbiddenError(_) => "FORBIDDEN_ERROR",
             AppError::InternalServerError(_) => "INTERNAL_SERVER_ERROR",
             AppError::LogConfigurationError(_) => "LOG_CONFIGURATION_ERROR",
             AppError::RegisterTaskDefinitionError(_) => "REGISTER_TASK_DEFINITION_ERROR",
             AppError::RunTaskError(_) => "RUN_TASK_ERROR",
             AppError::ListTasksError(_) => "LIST_TASKS_ERROR",
             AppError::DescribeTaskError(_) => "DESCRIBE_TASK_ERROR",
+            AppError::StopTaskError(_) => "STOP_TASK_ERROR",
+            AppError::SimulatePolicyError(_) => "SIMULATE_POLICY_ERROR",
             AppError::CustomError(_) => "CUSTOM_ERROR",
             AppError::UnsupportedVendor(_) => "UNSUPPORTED_VENDOR",
+            AppError::QuotaExceededError(_) => "QUOTA_EXCEEDED_ERROR",
+            AppError::ConcurrencyConflictError(_) => "CONCURRENCY_CONFLICT_ERROR",
         }
     }
 }
 
+macro_rules! boxed_from {
+    ($($variant:ident($error:ty)),* $(,)?) => {
+        $(
+            impl From<$error> for AppError {
+                fn from(err: $error) -> Self {
+                    AppError::$variant(Box::new(err))
+                }
+            }
+        )*
+    };
+}
+
+boxed_from!(
+    AwsSdkError(aws_sdk_ecs::Error),
+    RegisterTaskDefinitionError(SdkError<RegisterTaskDefinitionError>),
+    RunTaskError(SdkError<RunTaskError>),
+    ListTasksError(SdkError<ListTasksError>),
+    DescribeTaskError(SdkError<DescribeTasksError>),
+    StopTaskError(SdkError<StopTaskError>),
+    SimulatePolicyError(SdkError<SimulatePrincipalPolicyError>),
+);
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match self {
@@ -34,6 +71,7 @@ impl IntoResponse for AppError {
             AppError::ValidationError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             AppError::NotFoundError(_) => (StatusCode::NOT_FOUND, self.to_string()),
             AppError::UnauthorizedError(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AppError::ForbiddenError(_) => (StatusCode::FORBIDDEN, self.to_string()),
             AppError::InternalServerError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
             }
@@ -46,16 +84,24 @@ impl IntoResponse for AppError {
             AppError::RunTaskError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AppError::ListTasksError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AppError::DescribeTaskError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            AppError::StopTaskError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
+            AppError::SimulatePolicyError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
+            }
             AppError::CustomError(_) => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
             AppError::UnsupportedVendor(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::QuotaExceededError(_) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::ConcurrencyConflictError(_) => (StatusCode::CONFLICT, self.to_string()),
         };
 
-        let body = Json(json!({
-            "error": {
-                "type": self.error_type(),
-                "message": error_message,
-            }
-        }));
+        let body = Json(ErrorResponse {
+            error: ErrorBody {
+                error_type: self.error_type().to_string(),
+                message: redact(&error_message),
+                // Ties the error to the x-request-id of the response and the spawner's logs.
+                request_id: current_request_id(),
+            },
+        });
 
         (status, body).into_response()
     }
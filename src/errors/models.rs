@@ -3,38 +3,52 @@ use aws_sdk_ecs::{
     operation::{
         describe_tasks::DescribeTasksError, list_tasks::ListTasksError,
         register_task_definition::RegisterTaskDefinitionError, run_task::RunTaskError,
+        stop_task::StopTaskError,
     },
 };
+use aws_sdk_iam::operation::simulate_principal_policy::SimulatePrincipalPolicyError;
+pub use ecs_task_spawner_models::errors::models::{ErrorBody, ErrorResponse};
 use thiserror::Error;
 
+// The AWS SDK errors are boxed, they are large enough to bloat every Result they are in.
 // TODO: fix dead code warning
 #[allow(dead_code)]
 #[derive(Error, Debug)]
 pub enum AppError {
-    #[error("Task spawn error")]
+    #[error("Task spawn error: {0}")]
     TaskSpawnError(String),
     #[error("AWS SDK error")]
-    AwsSdkError(#[from] aws_sdk_ecs::Error),
-    #[error("Validation error")]
+    AwsSdkError(#[source] Box<aws_sdk_ecs::Error>),
+    #[error("Validation error: {0}")]
     ValidationError(String),
     #[error("Not found error")]
     NotFoundError(String),
     #[error("Unauthorized error")]
     UnauthorizedError(String),
+    #[error("Forbidden error: {0}")]
+    ForbiddenError(String),
     #[error("Internal server error")]
     InternalServerError(String),
     #[error("Log configuration build error")]
     LogConfigurationError(#[from] BuildError),
     #[error("Register task definition error")]
-    RegisterTaskDefinitionError(#[from] SdkError<RegisterTaskDefinitionError>),
+    RegisterTaskDefinitionError(#[source] Box<SdkError<RegisterTaskDefinitionError>>),
     #[error("Run task error")]
-    RunTaskError(#[from] SdkError<RunTaskError>),
+    RunTaskError(#[source] Box<SdkError<RunTaskError>>),
     #[error("List task error")]
-    ListTasksError(#[from] SdkError<ListTasksError>),
+    ListTasksError(#[source] Box<SdkError<ListTasksError>>),
     #[error("Describe task error")]
-    DescribeTaskError(#[from] SdkError<DescribeTasksError>),
+    DescribeTaskError(#[source] Box<SdkError<DescribeTasksError>>),
+    #[error("Stop task error")]
+    StopTaskError(#[source] Box<SdkError<StopTaskError>>),
+    #[error("Simulate principal policy error")]
+    SimulatePolicyError(#[source] Box<SdkError<SimulatePrincipalPolicyError>>),
     #[error("Cusom error")]
     CustomError(String),
     #[error("Unsupport vendor.")]
     UnsupportedVendor(String),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceededError(String),
+    #[error("Concurrency conflict: {0}")]
+    ConcurrencyConflictError(String),
 }
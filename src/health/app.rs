@@ -1,6 +1,12 @@
-use super::handlers::health;
+use super::handlers::{health, ready};
+use super::models::Readiness;
 use axum::{routing::get, Router};
 
-pub fn router() -> Router {
-    Router::new().route("/health", get(health))
+// Liveness only says the process is serving. Readiness checks what launches depend on.
+pub fn router(readiness: Readiness) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/health/live", get(health))
+        .route("/health/ready", get(ready))
+        .with_state(readiness)
 }
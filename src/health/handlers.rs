@@ -1,10 +1,47 @@
-use axum::Json;
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::{
+    headers::{authorization::Bearer, Authorization},
+    TypedHeader,
+};
 
-use super::models::Health;
+use super::models::{Health, Readiness, ReadinessReport};
 
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "health",
+    responses((status = 200, description = "The process is serving", body = Health))
+)]
 pub async fn health() -> Json<Health> {
     let health_status = Health {
         status: "ok".to_string(),
     };
     Json(health_status)
 }
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Every check passed", body = ReadinessReport),
+        (status = 503, description = "A check failed", body = ReadinessReport),
+    )
+)]
+// The checks are only listed for callers with an API key.
+pub async fn ready(
+    State(readiness): State<Readiness>,
+    bearer: Option<TypedHeader<Authorization<Bearer>>>,
+) -> (StatusCode, Json<ReadinessReport>) {
+    let token = bearer
+        .as_ref()
+        .map(|TypedHeader(Authorization(bearer))| bearer.token());
+    let report = readiness.report_for(token).await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
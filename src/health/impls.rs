@@ -0,0 +1,262 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use aws_config::SdkConfig;
+use aws_sdk_ecs::{config::ProvideCredentials, error::DisplayErrorContext};
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use super::models::{BackgroundLoop, Readiness, ReadinessCheck, ReadinessReport};
+use crate::{
+    auth::models::Caller, aws_client, jobs::models::ArcJobStore, secrets::impls::redact,
+    ssm::models::ConfigHandle,
+};
+
+impl Readiness {
+    pub fn new(sdk_config: &SdkConfig, config: ConfigHandle, jobs: ArcJobStore) -> Self {
+        let health = config.current().health.clone();
+        Readiness {
+            credentials: sdk_config.credentials_provider(),
+            ecs: aws_client!(aws_sdk_ecs, sdk_config),
+            config,
+            jobs,
+            loops: Arc::new(Mutex::new(Vec::new())),
+            cache_ttl: Duration::from_secs(health.cache_ttl_secs),
+            check_timeout: Duration::from_secs(health.check_timeout_secs.max(1)),
+            cache: Arc::new(tokio::sync::Mutex::new(None)),
+        }
+    }
+
+    // Readiness fails once the loop has stopped.
+    pub fn watch(&self, name: &str, handle: JoinHandle<()>) {
+        self.loops.lock().unwrap().push(BackgroundLoop {
+            name: name.to_string(),
+            handle,
+        });
+    }
+
+    // The last report, unless it is older than cache_ttl.
+    pub async fn report(&self) -> ReadinessReport {
+        let mut cache = self.cache.lock().await;
+        if let Some((checked, report)) = cache.as_ref() {
+            if checked.elapsed() < self.cache_ttl {
+                return report.clone();
+            }
+        }
+
+        let report = self.run().await;
+        if !report.ready {
+            tracing::warn!(
+                "not ready: {}",
+                report
+                    .checks
+                    .iter()
+                    .filter(|check| !check.passed)
+                    .map(|check| format!("{}: {}", check.name, check.detail))
+                    .collect::<Vec<String>>()
+                    .join("; ")
+            );
+        }
+        *cache = Some((Instant::now(), report.clone()));
+        report
+    }
+
+    // Callers without an API key, e.g. load balancers, only learn whether the spawner is
+    // ready. The checks name the cluster and carry raw AWS errors.
+    pub async fn report_for(&self, token: Option<&str>) -> ReadinessReport {
+        let mut report = self.report().await;
+        let authenticated = token
+            .is_some_and(|token| Caller::authenticate(&self.config.current(), token).is_some());
+        if authenticated {
+            for check in report.checks.iter_mut() {
+                check.detail = redact(&check.detail);
+            }
+        } else {
+            report.checks.clear();
+        }
+        report
+    }
+
+    pub async fn run(&self) -> ReadinessReport {
+        let (credentials, cluster, jobs) = tokio::join!(
+            self.timed("credentials", self.check_credentials()),
+            self.timed("cluster", self.check_cluster()),
+            self.timed("job store", self.check_jobs()),
+        );
+        let mut checks = vec![credentials, cluster, jobs];
+        checks.extend(self.check_loops());
+
+        ReadinessReport {
+            ready: checks.iter().all(|check| check.passed),
+            checked_at: Utc::now(),
+            checks,
+        }
+    }
+
+    async fn timed(
+        &self,
+        name: &str,
+        check: impl Future<Output = Result<String, String>>,
+    ) -> ReadinessCheck {
+        let started = Instant::now();
+        let result = match tokio::time::timeout(self.check_timeout, check).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("timed out after {:?}", self.check_timeout)),
+        };
+        ReadinessCheck::new(name, result, started.elapsed())
+    }
+
+    async fn check_credentials(&self) -> Result<String, String> {
+        let provider = self
+            .credentials
+            .as_ref()
+            .ok_or("no credentials provider is configured")?;
+        let credentials = provider
+            .provide_credentials()
+            .await
+            .map_err(|err| DisplayErrorContext(&err).to_string())?;
+        Ok(match credentials.expiry() {
+            Some(expiry) => format!(
+                "expire at {}",
+                chrono::DateTime::<Utc>::from(expiry).to_rfc3339()
+            ),
+            None => "do not expire".to_string(),
+        })
+    }
+
+    async fn check_cluster(&self) -> Result<String, String> {
+        let cluster_name = self.config.current().ecs.cluster_name.clone();
+        let response = self
+            .ecs
+            .describe_clusters()
+            .clusters(&cluster_name)
+            .send()
+            .await
+            .map_err(|err| DisplayErrorContext(&err).to_string())?;
+        match response.clusters().first() {
+            None => Err(format!("{} does not exist", cluster_name)),
+            Some(cluster) if cluster.status() != Some("ACTIVE") => Err(format!(
+                "{} is {}, not ACTIVE",
+                cluster_name,
+                cluster.status().unwrap_or("in an unknown state")
+            )),
+            Some(_) => Ok(format!("{} is ACTIVE", cluster_name)),
+        }
+    }
+
+    async fn check_jobs(&self) -> Result<String, String> {
+        self.jobs.ping().await.map_err(|err| format!("{:?}", err))?;
+        Ok("reachable".to_string())
+    }
+
+    pub fn check_loops(&self) -> Vec<ReadinessCheck> {
+        self.loops
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|background| {
+                let result = if background.handle.is_finished() {
+                    Err("has stopped".to_string())
+                } else {
+                    Ok("running".to_string())
+                };
+                ReadinessCheck::new(&format!("{} loop", background.name), result, Duration::ZERO)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::models::AppConfig, jobs::models::InMemoryJobStore, ssm::models::ParameterResolver,
+    };
+
+    fn readiness() -> Readiness {
+        let sdk_config = SdkConfig::builder()
+            .behavior_version(aws_config::BehaviorVersion::latest())
+            .build();
+        let cfg: AppConfig = config::Config::builder()
+            .set_override("api_key", "key")
+            .unwrap()
+            .set_override("log_level", "info")
+            .unwrap()
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        let (sender, _) = tokio::sync::watch::channel(Arc::new(cfg));
+        let config = ConfigHandle {
+            sender: Arc::new(sender),
+            resolver: ParameterResolver::new(&sdk_config, None),
+            refresh_interval: Duration::ZERO,
+        };
+        Readiness::new(&sdk_config, config, Arc::new(InMemoryJobStore::default()))
+    }
+
+    #[tokio::test]
+    async fn fails_once_a_loop_stops() {
+        let readiness = readiness();
+        readiness.watch("dispatch", tokio::spawn(std::future::pending()));
+        readiness.watch("watcher", tokio::spawn(async {}));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let checks = readiness.check_loops();
+        assert_eq!(checks[0].name, "dispatch loop");
+        assert!(checks[0].passed);
+        assert_eq!(checks[1].name, "watcher loop");
+        assert!(!checks[1].passed);
+        assert_eq!(checks[1].detail, "has stopped");
+    }
+
+    #[tokio::test]
+    async fn reports_missing_credentials_and_times_out_slow_checks() {
+        let mut readiness = readiness();
+        readiness.check_timeout = Duration::from_millis(10);
+
+        let credentials = readiness
+            .timed("credentials", readiness.check_credentials())
+            .await;
+        assert!(!credentials.passed);
+        assert_eq!(credentials.detail, "no credentials provider is configured");
+
+        let slow = readiness
+            .timed("slow", std::future::pending::<Result<String, String>>())
+            .await;
+        assert!(!slow.passed);
+        assert_eq!(slow.detail, "timed out after 10ms");
+    }
+
+    #[tokio::test]
+    async fn only_lists_redacted_checks_for_callers_with_a_key() {
+        let mut readiness = readiness();
+        readiness.cache_ttl = Duration::from_secs(60);
+        crate::secrets::models::REDACTIONS
+            .write()
+            .unwrap()
+            .push("soi/readiness/token".to_string());
+        let report = ReadinessReport {
+            ready: false,
+            checked_at: Utc::now(),
+            checks: vec![ReadinessCheck::new(
+                "cluster",
+                Err("AccessDenied on soi/readiness/token".to_string()),
+                Duration::ZERO,
+            )],
+        };
+        *readiness.cache.lock().await = Some((Instant::now(), report));
+
+        for token in [None, Some("wrong")] {
+            let report = readiness.report_for(token).await;
+            assert!(!report.ready);
+            assert!(report.checks.is_empty());
+        }
+        let report = readiness.report_for(Some("key")).await;
+        assert_eq!(report.checks[0].name, "cluster");
+        assert_eq!(report.checks[0].detail, "AccessDenied on [REDACTED]");
+    }
+}
@@ -1,6 +1,32 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Health {
-    pub status: String,
+use aws_sdk_ecs::{config::SharedCredentialsProvider, Client as EcsClient};
+pub use ecs_task_spawner_models::health::models::{Health, ReadinessCheck, ReadinessReport};
+use tokio::task::JoinHandle;
+
+use crate::{jobs::models::ArcJobStore, ssm::models::ConfigHandle};
+
+// Whether the spawner can launch workers right now. Checks are run at most once per
+// cache_ttl, however often load balancers ask.
+#[derive(Clone)]
+pub struct Readiness {
+    pub credentials: Option<SharedCredentialsProvider>,
+    pub ecs: EcsClient,
+    pub config: ConfigHandle,
+    pub jobs: ArcJobStore,
+    pub loops: Arc<Mutex<Vec<BackgroundLoop>>>,
+    pub cache_ttl: Duration,
+    pub check_timeout: Duration,
+    // Held while checks run, so concurrent requests wait for one run instead of each
+    // starting their own.
+    pub cache: Arc<tokio::sync::Mutex<Option<(Instant, ReadinessReport)>>>,
+}
+
+// A loop the spawner can't work without, e.g. the dispatcher.
+pub struct BackgroundLoop {
+    pub name: String,
+    pub handle: JoinHandle<()>,
 }
@@ -0,0 +1,9 @@
+use super::{handlers::get_job, models::ArcJobStore};
+
+use axum::{routing::get, Router};
+
+pub fn router(jobs: ArcJobStore) -> Router {
+    Router::new()
+        .route("/jobs/:id", get(get_job))
+        .with_state(jobs)
+}
@@ -0,0 +1,17 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use super::models::{ArcJobStore, Job};
+use crate::errors::models::AppError;
+
+pub async fn get_job(
+    State(jobs): State<ArcJobStore>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>, AppError> {
+    jobs.get(&id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| AppError::NotFoundError(format!("No job with id {}", id)))
+}
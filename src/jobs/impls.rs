@@ -0,0 +1,45 @@
+use async_trait::async_trait;
+
+use super::models::{InMemoryJobStore, Job, JobStore};
+use crate::{errors::models::AppError, sizing::models::TaskSize};
+
+#[async_trait]
+impl JobStore for InMemoryJobStore {
+    async fn insert(&self, job: Job) -> Result<(), AppError> {
+        self.jobs.write().await.insert(job.id.clone(), job);
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Job>, AppError> {
+        Ok(self.jobs.read().await.get(id).cloned())
+    }
+
+    async fn update(&self, job: Job) -> Result<(), AppError> {
+        self.insert(job).await
+    }
+
+    async fn list_unfinished(&self) -> Result<Vec<Job>, AppError> {
+        Ok(self
+            .jobs
+            .read()
+            .await
+            .values()
+            .filter(|job| !job.is_finished())
+            .cloned()
+            .collect())
+    }
+
+    async fn remembered_size(&self, key: &str) -> Result<Option<TaskSize>, AppError> {
+        Ok(self.sizes.read().await.get(key).copied())
+    }
+
+    async fn remember_size(&self, key: &str, size: TaskSize) -> Result<(), AppError> {
+        self.sizes.write().await.insert(key.to_string(), size);
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), AppError> {
+        let _jobs = self.jobs.read().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+pub use ecs_task_spawner_models::jobs::models::{Attempt, AttemptOutcome, Job, JobStatus};
+use tokio::sync::RwLock;
+
+use crate::{errors::models::AppError, sizing::models::TaskSize};
+
+#[async_trait]
+pub trait JobStore: Send + Sync {
+    async fn insert(&self, job: Job) -> Result<(), AppError>;
+    async fn get(&self, id: &str) -> Result<Option<Job>, AppError>;
+    async fn update(&self, job: Job) -> Result<(), AppError>;
+    // Jobs that are not yet Succeeded or Failed.
+    async fn list_unfinished(&self) -> Result<Vec<Job>, AppError>;
+    // The size the last successful run of a soiid needed, keyed by `{vendor}:{soiid}`.
+    async fn remembered_size(&self, key: &str) -> Result<Option<TaskSize>, AppError>;
+    async fn remember_size(&self, key: &str, size: TaskSize) -> Result<(), AppError>;
+    // Fails when the store can't be read, for the readiness check.
+    async fn ping(&self) -> Result<(), AppError>;
+}
+
+pub type ArcJobStore = Arc<dyn JobStore>;
+
+// Jobs are lost on restart. Good enough until we need the history to outlive the process.
+#[derive(Default)]
+pub struct InMemoryJobStore {
+    pub jobs: RwLock<HashMap<String, Job>>,
+    pub sizes: RwLock<HashMap<String, TaskSize>>,
+}
@@ -1,8 +1,22 @@
 pub mod app;
+pub mod audit;
 pub mod auth;
 pub mod config;
+pub mod dispatch;
 pub mod ecs;
 pub mod errors;
 pub mod health;
+pub mod jobs;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
+pub mod openapi;
+pub mod preflight;
+pub mod secrets;
 pub mod shutdown;
+pub mod sizing;
+pub mod ssm;
+pub mod subnets;
 pub mod task;
+pub mod terraform;
+pub mod watcher;
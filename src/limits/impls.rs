@@ -0,0 +1,469 @@
+use std::sync::Arc;
+
+use serde_json::json;
+use tokio::time::Instant;
+
+use super::models::{
+    ConcurrencyManager, KeyGuard, KeyPermit, KeyedLocks, QuotaManager, QuotaPermit,
+    QUEUE_POLL_INTERVAL, SETTLE_POLL_INTERVAL,
+};
+use crate::{
+    audit::{self, models::AuditAction},
+    config::models::{ConflictPolicy, QuotaConfig, QuotaPolicy, VendorConfig},
+    ecs::models::{EcsTag, EcsTaskRepo, TaskInfo, TaskRequest},
+    errors::models::AppError,
+};
+
+impl QuotaManager {
+    pub fn new(rules: Vec<QuotaConfig>) -> Self {
+        QuotaManager {
+            rules: Arc::new(rules),
+            locks: KeyedLocks::default(),
+        }
+    }
+
+    // Waits for every quota that applies to the request to have a free slot. Reject rules
+    // fail straight away, queue rules keep polling until their timeout runs out.
+    pub async fn acquire<T: EcsTaskRepo>(
+        &self,
+        repo: &T,
+        tr: &TaskRequest,
+    ) -> Result<QuotaPermit, AppError> {
+        let rules: Vec<(usize, &QuotaConfig)> = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| applies_to(rule, tr))
+            .collect();
+        if rules.is_empty() {
+            return Ok(QuotaPermit::default());
+        }
+
+        let started = Instant::now();
+        loop {
+            let permit = self.lock(&rules, tr).await;
+
+            let mut exceeded = None;
+            for (_, rule) in rules.iter() {
+                let active = repo.list_active(&quota_tags(rule, tr)).await?;
+                if active.len() >= rule.max_active {
+                    exceeded = Some(*rule);
+                    break;
+                }
+            }
+
+            let Some(rule) = exceeded else {
+                return Ok(permit);
+            };
+            drop(permit);
+
+            let message = format!(
+                "clientid {} already has {} active {} task(s)",
+                tr.clientid,
+                rule.max_active,
+                rule.vendor.as_deref().unwrap_or("worker"),
+            );
+            if rule.policy == QuotaPolicy::Reject
+                || started.elapsed().as_secs() >= rule.queue_timeout_secs
+            {
+                return Err(AppError::QuotaExceededError(message));
+            }
+
+            tracing::info!("{}, queueing spawn for soiid {}", message, tr.soiid);
+            tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+        }
+    }
+
+    // Locks are always taken in rule order so concurrent requests can't deadlock.
+    async fn lock(&self, rules: &[(usize, &QuotaConfig)], tr: &TaskRequest) -> QuotaPermit {
+        let mut guards = Vec::new();
+        for (index, _) in rules {
+            guards.push(self.locks.lock(format!("{}:{}", index, tr.clientid)).await);
+        }
+        QuotaPermit { guards }
+    }
+}
+
+impl ConcurrencyManager {
+    pub fn new() -> Self {
+        ConcurrencyManager::default()
+    }
+
+    // Makes sure no other task with the request's concurrency key is active, applying the
+    // vendor's conflict policy when one is.
+    pub async fn acquire<T: EcsTaskRepo>(
+        &self,
+        repo: &T,
+        tr: &TaskRequest,
+        vendor: &VendorConfig,
+    ) -> Result<KeyPermit, AppError> {
+        let Some(key) = vendor.concurrency_key(tr)? else {
+            return Ok(KeyPermit::default());
+        };
+        let tags = key_tags(&key);
+
+        let started = Instant::now();
+        loop {
+            let guard = self.locks.lock(key.clone()).await;
+
+            let active = repo.list_active(&tags).await?;
+            if active.is_empty() {
+                return Ok(KeyPermit { guard: Some(guard) });
+            }
+
+            let message = format!("a task with concurrency key {} is already active", key);
+            match vendor.on_conflict {
+                ConflictPolicy::Reject => return Err(AppError::ConcurrencyConflictError(message)),
+                ConflictPolicy::Queue => {
+                    tracing::info!("{}, queueing spawn for soiid {}", message, tr.soiid);
+                }
+                ConflictPolicy::Replace => {
+                    let reason = format!("Replaced by a newer request for {}", key);
+                    let task_arns: Vec<String> =
+                        active.into_iter().map(|task| task.task_arn).collect();
+                    for task_arn in task_arns.iter() {
+                        tracing::info!("stopping {} to replace it", task_arn);
+                        let result = repo.stop(task_arn, &reason).await;
+                        audit::impls::record(
+                            AuditAction::Stop,
+                            json!({ "reason": reason, "concurrency_key": key }),
+                            vec![task_arn.clone()],
+                            result.as_ref().err(),
+                        )
+                        .await;
+                        result?;
+                    }
+                    // A stopped task keeps running until its containers exit, and ListTasks
+                    // stops reporting it as soon as StopTask returns, so wait on the ARNs
+                    // while still holding the key.
+                    while !repo
+                        .describe(&task_arns)
+                        .await?
+                        .iter()
+                        .all(|task| task.status == "STOPPED")
+                    {
+                        if started.elapsed().as_secs() >= vendor.conflict_timeout_secs {
+                            return Err(AppError::ConcurrencyConflictError(format!(
+                                "the task with concurrency key {} did not stop within {}s",
+                                key, vendor.conflict_timeout_secs
+                            )));
+                        }
+                        tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+                    }
+                    if repo.list_active(&tags).await?.is_empty() {
+                        return Ok(KeyPermit { guard: Some(guard) });
+                    }
+                    continue;
+                }
+            }
+
+            drop(guard);
+            if started.elapsed().as_secs() >= vendor.conflict_timeout_secs {
+                return Err(AppError::ConcurrencyConflictError(message));
+            }
+            tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl KeyPermit {
+    // Keeps the key until the task launched under it shows up in ListTasks, which is where
+    // the next request for the key looks and which lags behind RunTask. Gives up, launching
+    // the next one after all, once the task has stopped or the timeout runs out.
+    pub async fn settle<T: EcsTaskRepo>(self, repo: &T, task: &TaskInfo, timeout_secs: u64) {
+        let Some(guard) = &self.guard else {
+            return;
+        };
+        let tags = key_tags(&guard.key);
+        let task_arns = [task.task_arn.clone()];
+
+        let started = Instant::now();
+        loop {
+            let listed = repo
+                .list_active(&tags)
+                .await
+                .map(|active| active.iter().any(|active| active.task_arn == task.task_arn));
+            let stopped = repo
+                .describe(&task_arns)
+                .await
+                .map(|tasks| tasks.iter().any(|task| task.status == "STOPPED"));
+            match (listed, stopped) {
+                (Ok(true), _) | (_, Ok(true)) => return,
+                (Err(err), _) | (_, Err(err)) => {
+                    tracing::warn!(
+                        "could not check that {} is listed, releasing concurrency key {}: {:?}",
+                        task.task_arn,
+                        guard.key,
+                        err
+                    );
+                    return;
+                }
+                _ => {}
+            }
+            if started.elapsed().as_secs() >= timeout_secs {
+                tracing::warn!(
+                    "{} was not listed within {}s, releasing concurrency key {}",
+                    task.task_arn,
+                    timeout_secs,
+                    guard.key
+                );
+                return;
+            }
+            tokio::time::sleep(SETTLE_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl KeyedLocks {
+    pub async fn lock(&self, key: String) -> KeyGuard {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let guard = lock.lock_owned().await;
+        KeyGuard {
+            key,
+            locks: self.clone(),
+            guard: Some(guard),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.locks.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Drop for KeyGuard {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        // Waiters clone the lock out of the map while holding it, so a lock only the map
+        // still refers to has nobody left to hand over to.
+        let mut locks = self.locks.locks.lock().unwrap();
+        if locks
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.key);
+        }
+    }
+}
+
+fn applies_to(rule: &QuotaConfig, tr: &TaskRequest) -> bool {
+    rule.vendor.as_ref().is_none_or(|v| *v == tr.vendor)
+        && rule.clientid.as_ref().is_none_or(|c| *c == tr.clientid)
+}
+
+fn key_tags(key: &str) -> [EcsTag; 1] {
+    [EcsTag {
+        key: "concurrency_key".to_string(),
+        value: key.to_string(),
+    }]
+}
+
+fn quota_tags(rule: &QuotaConfig, tr: &TaskRequest) -> Vec<EcsTag> {
+    let mut tags = vec![EcsTag {
+        key: "clientid".to_string(),
+        value: tr.clientid.clone(),
+    }];
+    if let Some(vendor) = &rule.vendor {
+        tags.push(EcsTag {
+            key: "worker_type".to_string(),
+            value: vendor.clone(),
+        });
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::ecs::fake::FakeRepo;
+
+    fn request() -> TaskRequest {
+        serde_json::from_value(json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+        }))
+        .unwrap()
+    }
+
+    fn quotas(policy: QuotaPolicy) -> QuotaManager {
+        QuotaManager::new(vec![QuotaConfig {
+            vendor: Some("bloomberg".to_string()),
+            clientid: None,
+            max_active: 1,
+            policy,
+            queue_timeout_secs: 60,
+        }])
+    }
+
+    const BLOOMBERG_TASK: &[(&str, &str)] =
+        &[("clientid", "client-1"), ("worker_type", "bloomberg")];
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_requests_over_quota_straight_away() {
+        let repo = FakeRepo::default();
+        let quotas = quotas(QuotaPolicy::Reject);
+        assert!(quotas.acquire(&repo, &request()).await.is_ok());
+
+        repo.run("arn-1", BLOOMBERG_TASK);
+        let started = Instant::now();
+        assert!(matches!(
+            quotas.acquire(&repo, &request()).await,
+            Err(AppError::QuotaExceededError(_))
+        ));
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        // Other vendors' tasks don't count.
+        let mut other = request();
+        other.vendor = "refinitiv".to_string();
+        assert!(quotas.acquire(&repo, &other).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queues_until_a_slot_frees_up() {
+        let repo = FakeRepo::default();
+        repo.run("arn-1", BLOOMBERG_TASK);
+        let finisher = repo.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(12)).await;
+            finisher.set_status("arn-1", "STOPPED");
+        });
+
+        let started = Instant::now();
+        assert!(quotas(QuotaPolicy::Queue)
+            .acquire(&repo, &request())
+            .await
+            .is_ok());
+        assert_eq!(started.elapsed(), Duration::from_secs(15));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_queueing_after_the_timeout() {
+        let repo = FakeRepo::default();
+        repo.run("arn-1", BLOOMBERG_TASK);
+
+        let started = Instant::now();
+        assert!(matches!(
+            quotas(QuotaPolicy::Queue).acquire(&repo, &request()).await,
+            Err(AppError::QuotaExceededError(_))
+        ));
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+    }
+
+    fn vendor(on_conflict: &str) -> VendorConfig {
+        serde_json::from_value(json!({
+            "image": "worker:latest",
+            "on_conflict": on_conflict,
+            "conflict_timeout_secs": 60,
+        }))
+        .unwrap()
+    }
+
+    const SAME_KEY: &[(&str, &str)] = &[("concurrency_key", "bloomberg:soi-1")];
+
+    // Marks the task STOPPED after a while, as ECS does once its containers exit.
+    fn stop_later(repo: &FakeRepo, arn: &str, secs: u64) {
+        let repo = repo.clone();
+        let arn = arn.to_string();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(secs)).await;
+            repo.set_status(&arn, "STOPPED");
+        });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_a_second_task_for_the_same_key() {
+        let repo = FakeRepo::default();
+        repo.run("arn-1", SAME_KEY);
+
+        let concurrency = ConcurrencyManager::new();
+        let started = Instant::now();
+        assert!(matches!(
+            concurrency
+                .acquire(&repo, &request(), &vendor("reject"))
+                .await,
+            Err(AppError::ConcurrencyConflictError(_))
+        ));
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert_eq!(repo.status("arn-1"), "RUNNING");
+        assert!(concurrency.locks.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queues_behind_the_active_task_for_the_key() {
+        let repo = FakeRepo::default();
+        repo.run("arn-1", SAME_KEY);
+        stop_later(&repo, "arn-1", 12);
+
+        let started = Instant::now();
+        assert!(ConcurrencyManager::new()
+            .acquire(&repo, &request(), &vendor("queue"))
+            .await
+            .is_ok());
+        assert_eq!(started.elapsed(), Duration::from_secs(15));
+
+        repo.run("arn-2", SAME_KEY);
+        let started = Instant::now();
+        assert!(matches!(
+            ConcurrencyManager::new()
+                .acquire(&repo, &request(), &vendor("queue"))
+                .await,
+            Err(AppError::ConcurrencyConflictError(_))
+        ));
+        assert_eq!(started.elapsed(), Duration::from_secs(60));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn replaces_the_active_task_once_it_has_stopped() {
+        let repo = FakeRepo::default();
+        repo.run("arn-1", SAME_KEY);
+        stop_later(&repo, "arn-1", 7);
+
+        let started = Instant::now();
+        assert!(ConcurrencyManager::new()
+            .acquire(&repo, &request(), &vendor("replace"))
+            .await
+            .is_ok());
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        assert_eq!(repo.status("arn-1"), "STOPPED");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forgets_keys_nobody_holds_or_waits_for() {
+        let repo = FakeRepo::default();
+        let concurrency = ConcurrencyManager::new();
+        let vendor = vendor("queue");
+
+        let first = concurrency
+            .acquire(&repo, &request(), &vendor)
+            .await
+            .unwrap();
+        let waiter = {
+            let (concurrency, repo, vendor) = (concurrency.clone(), repo.clone(), vendor.clone());
+            tokio::spawn(async move { concurrency.acquire(&repo, &request(), &vendor).await })
+        };
+        tokio::task::yield_now().await;
+
+        // The waiter still needs the lock, so it stays.
+        drop(first);
+        assert_eq!(concurrency.locks.len(), 1);
+        drop(waiter.await.unwrap().unwrap());
+        assert!(concurrency.locks.is_empty());
+
+        let quotas = quotas(QuotaPolicy::Reject);
+        drop(quotas.acquire(&repo, &request()).await.unwrap());
+        assert!(quotas.locks.is_empty());
+    }
+}
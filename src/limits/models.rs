@@ -0,0 +1,52 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::sync::OwnedMutexGuard;
+
+use crate::config::models::QuotaConfig;
+
+// How often a queued request re-checks the live task count.
+pub const QUEUE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// How often a launch holding a concurrency key checks whether ListTasks shows its task yet.
+pub const SETTLE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Serialises checks per key so two requests can't both take the last slot. A key's entry
+// goes away once nobody holds or waits for it.
+#[derive(Debug, Clone, Default)]
+pub struct KeyedLocks {
+    pub locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+pub struct KeyGuard {
+    pub key: String,
+    pub locks: KeyedLocks,
+    pub guard: Option<OwnedMutexGuard<()>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QuotaManager {
+    pub rules: Arc<Vec<QuotaConfig>>,
+    pub locks: KeyedLocks,
+}
+
+// Held while the task is being spawned. Dropping it lets the next request in.
+#[derive(Default)]
+pub struct QuotaPermit {
+    pub guards: Vec<KeyGuard>,
+}
+
+// Keeps at most one task per concurrency key active.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyManager {
+    pub locks: KeyedLocks,
+}
+
+// Held while the task for a concurrency key is being spawned, and until it is listed.
+#[derive(Default)]
+pub struct KeyPermit {
+    pub guard: Option<KeyGuard>,
+}
@@ -0,0 +1,12 @@
+use super::{
+    handlers::{get_log_level, set_log_level},
+    models::LogFilter,
+};
+
+use axum::{routing::get, Router};
+
+pub fn router(filter: LogFilter) -> Router {
+    Router::new()
+        .route("/admin/log-level", get(get_log_level).put(set_log_level))
+        .with_state(filter)
+}
@@ -0,0 +1,16 @@
+use axum::{extract::State, Json};
+
+use super::models::{LogFilter, LogLevel};
+use crate::errors::models::AppError;
+
+pub async fn get_log_level(State(filter): State<LogFilter>) -> Result<Json<LogLevel>, AppError> {
+    Ok(Json(filter.current()?))
+}
+
+pub async fn set_log_level(
+    State(filter): State<LogFilter>,
+    Json(level): Json<LogLevel>,
+) -> Result<Json<LogLevel>, AppError> {
+    filter.set(&level.filter).await?;
+    Ok(Json(filter.current()?))
+}
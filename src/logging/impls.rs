@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use aws_smithy_runtime_api::{
+    box_error::BoxError,
+    client::{
+        interceptors::{
+            context::{BeforeSerializationInterceptorContextRef, FinalizerInterceptorContextRef},
+            Intercept,
+        },
+        orchestrator::Metadata,
+        runtime_components::RuntimeComponents,
+    },
+};
+use aws_smithy_types::config_bag::ConfigBag;
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use opentelemetry::{
+    global,
+    trace::{TraceContextExt, TraceError, TracerProvider as _},
+    Context,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{self, Sampler, Tracer, TracerProvider},
+};
+use serde_json::json;
+use tracing::{field::Empty, Instrument, Level};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::Targets, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Layer,
+};
+use uuid::Uuid;
+
+use super::models::{
+    AwsCallSpan, AwsCallSpans, LogFilter, LogLevel, MAX_REQUEST_ID_LEN, REQUEST_ID,
+    REQUEST_ID_HEADER,
+};
+use crate::{
+    audit::{self, models::AuditAction},
+    config::models::{AppConfig, LogFormat, TracingConfig},
+    errors::models::AppError,
+};
+
+impl LogFilter {
+    // Installs the global subscriber with the configured level and format, and the tracer
+    // that turns spans into OpenTelemetry spans. The level only filters what is logged.
+    pub fn init(cfg: &AppConfig) -> Result<Self, AppError> {
+        let (filter, handle) = reload::Layer::new(parse_filter(&cfg.log_level)?);
+        let output = match cfg.log_format {
+            LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+            LogFormat::Json => tracing_subscriber::fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .boxed(),
+        };
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer =
+            tracer(&cfg.tracing).map_err(|err| AppError::InternalServerError(err.to_string()))?;
+        // The HTTP request spans of OtelAxumLayer are TRACE spans of their own target.
+        let traced = Targets::new()
+            .with_target("otel::tracing", Level::TRACE)
+            .with_target("ecs_task_spawner", Level::INFO);
+
+        tracing_subscriber::registry()
+            .with(output.with_filter(filter))
+            .with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .with_filter(traced),
+            )
+            .try_init()
+            .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+        Ok(LogFilter { handle })
+    }
+
+    pub fn current(&self) -> Result<LogLevel, AppError> {
+        self.handle
+            .with_current(|filter| LogLevel {
+                filter: filter.to_string(),
+            })
+            .map_err(|err| AppError::InternalServerError(err.to_string()))
+    }
+
+    pub async fn set(&self, directive: &str) -> Result<(), AppError> {
+        let result = parse_filter(directive).and_then(|filter| {
+            self.handle
+                .reload(filter)
+                .map_err(|err| AppError::InternalServerError(err.to_string()))
+        });
+        audit::impls::record(
+            AuditAction::ConfigChange,
+            json!({ "source": "api", "changed": ["log_level"], "log_level": directive }),
+            Vec::new(),
+            result.as_ref().err(),
+        )
+        .await;
+        result?;
+        tracing::info!("log filter changed to {}", directive);
+        Ok(())
+    }
+}
+
+fn parse_filter(directive: &str) -> Result<EnvFilter, AppError> {
+    EnvFilter::try_new(directive).map_err(|err| {
+        AppError::ValidationError(format!(
+            "{:?} is not a valid log filter: {}",
+            directive, err
+        ))
+    })
+}
+
+// Runs the request in a span carrying its request and trace ids, so every line logged while
+// handling it can be tied back to it. Callers' x-request-id is kept if RunTask would take it
+// as startedBy, otherwise one is made up. Either way it is sent back.
+pub async fn request_span(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| valid_request_id(value))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let span = tracing::info_span!(
+        "request",
+        request_id = %request_id,
+        trace_id = Empty,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let trace_id = span.context().span().span_context().trace_id();
+    if span.context().span().span_context().is_valid() {
+        span.record("trace_id", trace_id.to_string());
+    }
+
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(req).instrument(span))
+        .await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+pub fn valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+// Id of the request being handled. None outside of requests, e.g. for relaunches.
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+fn tracer(cfg: &TracingConfig) -> Result<Tracer, TraceError> {
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(cfg.sample_ratio)));
+    let mut builder = TracerProvider::builder().with_config(trace::config().with_sampler(sampler));
+    if let Some(endpoint) = &cfg.otlp_endpoint {
+        let exporter = opentelemetry_otlp::SpanExporterBuilder::from(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .build_span_exporter()?;
+        builder = builder.with_batch_exporter(exporter, runtime::Tokio);
+    }
+    let provider = builder.build();
+    let tracer = provider.tracer("ecs-task-spawner");
+    global::set_tracer_provider(provider);
+    Ok(tracer)
+}
+
+// The W3C trace context of the current span, as traceparent and tracestate. Empty when the
+// span isn't traced.
+pub fn current_trace_context() -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    let context = tracing::Span::current().context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    carrier
+}
+
+pub fn trace_context_parent(carrier: &HashMap<String, String>) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(carrier))
+}
+
+// A client of the given SDK crate whose calls are counted and traced, e.g.
+// `aws_client!(aws_sdk_ecs, &sdk_config)`.
+#[macro_export]
+macro_rules! aws_client {
+    ($sdk:ident, $sdk_config:expr) => {
+        $sdk::Client::from_conf(
+            $sdk::config::Builder::from($sdk_config)
+                .interceptor($crate::metrics::models::AwsCallMetrics)
+                .interceptor($crate::logging::models::AwsCallSpans)
+                .build(),
+        )
+    };
+}
+
+impl Intercept for AwsCallSpans {
+    fn name(&self) -> &'static str {
+        "AwsCallSpans"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(metadata) = cfg.load::<Metadata>() else {
+            return Ok(());
+        };
+        // Closed when the call's config bag is dropped, once the call has finished.
+        let span = tracing::info_span!(
+            "aws",
+            otel.name = format!("{}.{}", metadata.service(), metadata.name()),
+            otel.kind = "client",
+            otel.status_code = Empty,
+            rpc.system = "aws-api",
+            rpc.service = metadata.service(),
+            rpc.method = metadata.name(),
+            http.response.status_code = Empty,
+        );
+        cfg.interceptor_state().store_put(AwsCallSpan(span));
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let Some(AwsCallSpan(span)) = cfg.load::<AwsCallSpan>() else {
+            return Ok(());
+        };
+        if let Some(response) = context.response() {
+            span.record("http.response.status_code", response.status().as_u16());
+        }
+        if !matches!(context.output_or_error(), Some(Ok(_))) {
+            span.record("otel.status_code", "ERROR");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_on_the_trace_of_the_parent() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        // Tracers only hold on to their provider weakly.
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let parent = HashMap::from([(
+                "traceparent".to_string(),
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+            )]);
+            let span = tracing::info_span!("launch");
+            span.set_parent(trace_context_parent(&parent));
+            let _entered = span.enter();
+
+            let context = current_trace_context();
+            let traceparent = &context["traceparent"];
+            assert!(
+                traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"),
+                "{}",
+                traceparent
+            );
+            // A span of its own, not the caller's.
+            assert!(!traceparent.contains("00f067aa0ba902b7"), "{}", traceparent);
+        });
+
+        assert!(current_trace_context().is_empty());
+    }
+
+    #[tokio::test]
+    async fn takes_request_ids_runtask_accepts() {
+        assert!(valid_request_id("0f8fad5b-d9cb-469f-a165-70867728950e"));
+        assert!(valid_request_id("batch_42"));
+        assert!(!valid_request_id(""));
+        assert!(!valid_request_id("a b"));
+        assert!(!valid_request_id("a,b"));
+        assert!(!valid_request_id(&"a".repeat(129)));
+
+        assert_eq!(current_request_id(), None);
+        let id = REQUEST_ID
+            .scope("req-1".to_string(), async { current_request_id() })
+            .await;
+        assert_eq!(id.as_deref(), Some("req-1"));
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        assert!(parse_filter("info,ecs_task_spawner=debug").is_ok());
+        assert!(matches!(
+            parse_filter("ecs_task_spawner=loud"),
+            Err(AppError::ValidationError(_))
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+pub use ecs_task_spawner_models::logging::models::{LogLevel, REQUEST_ID_HEADER};
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+// RunTask's startedBy takes at most 128 letters, numbers, hyphens and underscores, so only
+// such ids are taken from callers.
+pub const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    // Id of the request being handled, for errors and the tasks it launches.
+    pub static REQUEST_ID: String;
+}
+
+// Environment variables a worker's W3C trace context is handed over in.
+pub const TRACEPARENT_ENV: &str = "TRACEPARENT";
+pub const TRACESTATE_ENV: &str = "TRACESTATE";
+
+// Swaps the filter of the installed subscriber, so log levels change without a restart.
+#[derive(Clone)]
+pub struct LogFilter {
+    pub handle: reload::Handle<EnvFilter, Registry>,
+}
+
+// Wraps every call made by an AWS client it is added to in a span.
+#[derive(Debug, Default)]
+pub struct AwsCallSpans;
+
+#[derive(Debug, Clone)]
+pub struct AwsCallSpan(pub tracing::Span);
+
+impl Storable for AwsCallSpan {
+    type Storer = StoreReplace<Self>;
+}
@@ -1,44 +1,94 @@
-mod auth;
 mod errors;
-mod health;
-
-use crate::auth::api::auth;
 
 use std::sync::Arc;
 
-use aws_sdk_ecs::Client as EcsClient;
 use axum::middleware;
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
 use ecs_task_spawner::app;
-use ecs_task_spawner::config::models::AppConfig;
+use ecs_task_spawner::app::models::AppState;
+use ecs_task_spawner::audit;
+use ecs_task_spawner::audit::models::{ArcAuditSink, JsonLinesSink};
+use ecs_task_spawner::auth::api::auth;
+use ecs_task_spawner::aws_client;
+use ecs_task_spawner::dispatch;
+use ecs_task_spawner::dispatch::models::Dispatcher;
 use ecs_task_spawner::ecs::models::EcsRepo;
+use ecs_task_spawner::health;
+use ecs_task_spawner::health::models::Readiness;
+use ecs_task_spawner::jobs;
+use ecs_task_spawner::jobs::models::{ArcJobStore, InMemoryJobStore};
+use ecs_task_spawner::limits::models::{ConcurrencyManager, QuotaManager};
+use ecs_task_spawner::logging;
+use ecs_task_spawner::logging::impls::request_span;
+use ecs_task_spawner::logging::models::LogFilter;
+use ecs_task_spawner::metrics;
+use ecs_task_spawner::metrics::models::MetricsExporter;
+use ecs_task_spawner::openapi;
+use ecs_task_spawner::preflight::models::Preflight;
+use ecs_task_spawner::secrets;
 use ecs_task_spawner::shutdown::shutdown_signal;
+use ecs_task_spawner::ssm::models::ConfigHandle;
+use ecs_task_spawner::subnets;
+use ecs_task_spawner::subnets::models::SubnetPool;
+use ecs_task_spawner::watcher::models::JobWatcher;
 use tower::ServiceBuilder;
-use tracing_subscriber::layer::SubscriberExt;
-use tracing_subscriber::util::SubscriberInitExt;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .try_init()
-        .unwrap();
+    // Initialize ECS client
+    let config = aws_config::load_from_env().await;
+    let ecs_client = aws_client!(aws_sdk_ecs, &config);
+
+    // Settings owned by our Terraform are ssm:// references, resolved here.
+    let config_handle = ConfigHandle::load(&config)
+        .await
+        .unwrap_or_else(|err| panic!("could not load the config: {}", err));
+    let config_refresh = config_handle.start();
+    let cfg = config_handle.current();
+
+    // Logging is configured too, so anything logged before this point is lost.
+    let log_filter =
+        LogFilter::init(&cfg).unwrap_or_else(|err| panic!("could not set up logging: {}", err));
+
+    secrets::impls::register_redactions(&cfg);
+
+    // `--preflight` only checks the infrastructure the config points at, for deploy pipelines,
+    // so it exits before anything else is set up.
+    let preflight_only = std::env::args().any(|arg| arg == "--preflight");
+    if preflight_only || cfg.preflight.on_startup {
+        let report = Preflight::new(&config).run(&cfg).await;
+        if preflight_only {
+            println!("{}", report);
+            std::process::exit(if report.passed() { 0 } else { 1 });
+        }
+        if report.passed() {
+            tracing::info!("preflight checks passed\n{}", report);
+        } else if cfg.preflight.fail_on_error {
+            panic!("preflight checks failed\n{}", report);
+        } else {
+            tracing::warn!("preflight checks failed\n{}", report);
+        }
+    }
 
-    let cfg = AppConfig::new().unwrap();
+    // Calls made while loading the config aren't recorded, the exporter needs its settings.
+    let metrics_exporter = MetricsExporter::install(&cfg.metrics)
+        .unwrap_or_else(|err| panic!("could not set up metrics: {}", err));
+
+    // Spawns, stops and config changes from here on are audited.
+    let audit_sink: ArcAuditSink = JsonLinesSink::open(&cfg.audit)
+        .unwrap_or_else(|err| panic!("could not open the audit log: {}", err));
+    audit::impls::install(audit_sink.clone())
+        .unwrap_or_else(|err| panic!("could not set up auditing: {}", err));
 
     // Setup the auth layer.
-    let token = Arc::new(cfg.api_key.clone());
+    let auth_config = config_handle.subscribe();
     let auth_layer = ServiceBuilder::new()
         .layer(middleware::from_fn(move |req, next| {
-            let token = token.clone();
-            async move { auth(req, next, token).await }
+            let config = auth_config.clone();
+            async move { auth(req, next, config).await }
         }))
         .into_inner();
 
-    // Initialize ECS client
-    let config = aws_config::load_from_env().await;
-    let ecs_client = EcsClient::new(&config);
-
     // TODO: Need to terraform resources that workers will all use:
     // - security group
     // - cloudwatch log group
@@ -54,21 +104,71 @@ async fn main() {
     //     "ecs-task-execution-role-arn".to_string(), // Replace with your execution role ARN
     // );
 
-    let ecs_repo = EcsRepo::new(ecs_client);
-    let worker_api = app::api::router(ecs_repo);
+    let job_store: ArcJobStore = Arc::new(InMemoryJobStore::default());
+    let jobs_api = jobs::api::router(job_store.clone());
+
+    let readiness = Readiness::new(&config, config_handle.clone(), job_store.clone());
+    if let Some(config_refresh) = config_refresh {
+        readiness.watch("config refresh", config_refresh);
+    }
+
+    let dispatcher = Dispatcher::new(cfg.dispatch.clone());
+    readiness.watch("dispatch", dispatcher.start());
+    readiness.watch(
+        "dispatch reload",
+        dispatcher.follow(config_handle.subscribe()),
+    );
+    let _queue_depth = metrics_exporter.observe_queue(&dispatcher);
+    let admin_api = dispatch::api::router(dispatcher.clone());
+
+    let subnet_pool = SubnetPool::new(&cfg.ecs);
+    readiness.watch(
+        "subnet reload",
+        subnet_pool.follow(config_handle.subscribe()),
+    );
+    let subnets_api = subnets::api::router(subnet_pool.clone());
+
+    let ecs_repo = EcsRepo::new(ecs_client, cfg.ecs.cluster_name.clone());
+    let state = AppState {
+        repo: ecs_repo,
+        quotas: QuotaManager::new(cfg.quotas.clone()),
+        concurrency: ConcurrencyManager::new(),
+        dispatcher,
+        jobs: job_store,
+        subnets: subnet_pool,
+        config: config_handle.clone(),
+    };
+    readiness.watch("job watcher", JobWatcher::new(state.clone()).start());
+    let worker_api = app::api::router(state);
 
-    let health_api = health::app::router();
+    let health_api = health::app::router(readiness);
+    let logging_api = logging::api::router(log_filter);
+    let audit_api = audit::api::router(audit_sink);
+    let metrics_api = metrics::api::router(metrics_exporter.clone());
+    let http_metrics = metrics::impls::http_metrics();
 
     let app = worker_api
+        .merge(admin_api)
+        .merge(jobs_api)
+        .merge(subnets_api)
+        .merge(logging_api)
+        .merge(audit_api)
         .layer(auth_layer)
         .merge(health_api)
-        .layer(OtelInResponseLayer::default())
+        .merge(metrics_api)
+        .merge(http_metrics.routes())
+        .merge(openapi::api::router())
+        .layer(http_metrics)
+        .layer(middleware::from_fn(request_span))
+        .layer(OtelInResponseLayer)
         .layer(OtelAxumLayer::default());
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    println!("Listening on {}", listener.local_addr().unwrap());
+    tracing::info!("listening on {}", listener.local_addr().unwrap());
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
+    metrics_exporter.shutdown();
+    opentelemetry::global::shutdown_tracer_provider();
 }
@@ -0,0 +1,9 @@
+use super::{handlers::get_metrics, models::MetricsExporter};
+
+use axum::{routing::get, Router};
+
+pub fn router(exporter: MetricsExporter) -> Router {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(exporter)
+}
@@ -0,0 +1,19 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+use prometheus::{Encoder, TextEncoder};
+
+use super::models::MetricsExporter;
+use crate::errors::models::AppError;
+
+pub async fn get_metrics(
+    State(exporter): State<MetricsExporter>,
+) -> Result<impl IntoResponse, AppError> {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&exporter.registry.gather(), &mut buffer)
+        .map_err(|err| AppError::InternalServerError(err.to_string()))?;
+    Ok((
+        [(header::CONTENT_TYPE, encoder.format_type().to_string())],
+        buffer,
+    ))
+}
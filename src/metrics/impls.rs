@@ -0,0 +1,394 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use aws_smithy_runtime_api::{
+    box_error::BoxError,
+    client::{
+        interceptors::{
+            context::{BeforeSerializationInterceptorContextRef, FinalizerInterceptorContextRef},
+            Intercept,
+        },
+        orchestrator::Metadata,
+        runtime_components::RuntimeComponents,
+    },
+};
+use aws_smithy_types::config_bag::ConfigBag;
+use axum_otel_metrics::{HttpMetricsLayer, HttpMetricsLayerBuilder};
+use opentelemetry::{
+    metrics::{noop::NoopMeterProvider, Meter, MeterProvider, MetricsError, ObservableGauge, Unit},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    metrics::{
+        new_view,
+        reader::{DefaultAggregationSelector, DefaultTemporalitySelector},
+        Aggregation, Instrument, PeriodicReader, SdkMeterProvider, Stream,
+    },
+    runtime,
+};
+use prometheus::Registry;
+
+use super::models::{
+    AwsCallMetrics, CallStarted, Metrics, MetricsExporter, AWS_CALL_BUCKETS, HTTP_METRICS_PATH,
+    METER_NAME, METRICS, TASK_DURATION_BUCKETS, UNRECORDED,
+};
+use crate::{
+    config::models::MetricsConfig,
+    dispatch::models::Dispatcher,
+    ecs::models::{Priority, TaskInfo, TaskRequest},
+    errors::models::AppError,
+    jobs::models::Attempt,
+};
+
+pub fn metrics() -> &'static Metrics {
+    METRICS.get().unwrap_or_else(|| {
+        UNRECORDED.get_or_init(|| Metrics::new(&NoopMeterProvider::new().meter(METER_NAME)))
+    })
+}
+
+impl MetricsExporter {
+    // Builds the meter provider and records into it from then on. Has to run inside the
+    // tokio runtime when OTLP is configured, since the periodic export runs on it.
+    pub fn install(cfg: &MetricsConfig) -> Result<Self, MetricsError> {
+        let exporter = MetricsExporter::new(cfg)?;
+        if METRICS.set(Metrics::new(&exporter.meter())).is_err() {
+            return Err(MetricsError::Other(
+                "metrics are already installed".to_string(),
+            ));
+        }
+        Ok(exporter)
+    }
+
+    pub fn new(cfg: &MetricsConfig) -> Result<Self, MetricsError> {
+        let registry = Registry::new();
+        let prometheus = opentelemetry_prometheus::exporter()
+            .with_registry(registry.clone())
+            .without_scope_info()
+            .build()?;
+
+        let mut builder = SdkMeterProvider::builder()
+            .with_reader(prometheus)
+            .with_view(buckets("aws.api.duration", AWS_CALL_BUCKETS)?)
+            .with_view(buckets("task.duration", TASK_DURATION_BUCKETS)?);
+        if let Some(endpoint) = &cfg.otlp_endpoint {
+            let otlp = opentelemetry_otlp::MetricsExporterBuilder::from(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .build_metrics_exporter(
+                Box::new(DefaultTemporalitySelector::new()),
+                Box::new(DefaultAggregationSelector::new()),
+            )?;
+            let reader = PeriodicReader::builder(otlp, runtime::Tokio)
+                .with_interval(Duration::from_secs(cfg.otlp_interval_secs.max(1)))
+                .build();
+            builder = builder.with_reader(reader);
+        }
+
+        Ok(MetricsExporter {
+            registry,
+            provider: builder.build(),
+        })
+    }
+
+    // Pushes what hasn't been exported to the collector yet.
+    pub fn shutdown(&self) {
+        if let Err(err) = self.provider.shutdown() {
+            tracing::warn!("could not flush metrics: {}", err);
+        }
+    }
+
+    pub fn meter(&self) -> Meter {
+        self.provider.meter(METER_NAME)
+    }
+
+    // Reports the depth of the dispatch queue by priority.
+    pub fn observe_queue(&self, dispatcher: &Dispatcher) -> ObservableGauge<u64> {
+        let dispatcher = dispatcher.clone();
+        self.meter()
+            .u64_observable_gauge("dispatch.queue.depth")
+            .with_description("Spawns waiting in the dispatch queue.")
+            .with_callback(move |gauge| {
+                let by_priority = dispatcher.status().by_priority;
+                for (priority, name) in [
+                    (Priority::High, "high"),
+                    (Priority::Normal, "normal"),
+                    (Priority::Low, "low"),
+                ] {
+                    let depth = by_priority.get(&priority).copied().unwrap_or(0);
+                    gauge.observe(depth as u64, &[KeyValue::new("priority", name)]);
+                }
+            })
+            .init()
+    }
+}
+
+// Request counts, latencies and sizes by route and status, recorded by axum-otel-metrics. It
+// keeps them in a meter provider and registry of its own, served from HTTP_METRICS_PATH by
+// its routes(), so unlike the rest they aren't pushed over OTLP.
+pub fn http_metrics() -> HttpMetricsLayer {
+    HttpMetricsLayerBuilder::new()
+        .with_service_name(METER_NAME.to_string())
+        .with_path(HTTP_METRICS_PATH.to_string())
+        .build()
+}
+
+fn buckets(
+    name: &'static str,
+    boundaries: &[f64],
+) -> Result<Box<dyn opentelemetry_sdk::metrics::View>, MetricsError> {
+    new_view(
+        Instrument::new().name(name),
+        Stream::new().aggregation(Aggregation::ExplicitBucketHistogram {
+            boundaries: boundaries.to_vec(),
+            record_min_max: true,
+        }),
+    )
+}
+
+impl Metrics {
+    pub fn new(meter: &Meter) -> Self {
+        let retry_scheduled = Arc::new(AtomicU64::new(0));
+        let observed = retry_scheduled.clone();
+        meter
+            .u64_observable_gauge("jobs.retry_scheduled")
+            .with_description("Jobs waiting for their next attempt.")
+            .with_callback(move |gauge| gauge.observe(observed.load(Ordering::Relaxed), &[]))
+            .init();
+
+        Metrics {
+            spawns: meter
+                .u64_counter("spawns")
+                .with_description("Worker launch attempts.")
+                .init(),
+            aws_call_duration: meter
+                .f64_histogram("aws.api.duration")
+                .with_unit(Unit::new("s"))
+                .with_description("How long AWS API calls took, including retries.")
+                .init(),
+            aws_call_errors: meter
+                .u64_counter("aws.api.errors")
+                .with_description("AWS API calls that failed.")
+                .init(),
+            task_duration: meter
+                .f64_histogram("task.duration")
+                .with_unit(Unit::new("s"))
+                .with_description("How long stopped workers ran.")
+                .init(),
+            retry_scheduled,
+        }
+    }
+
+    pub fn record_spawn(&self, tr: &TaskRequest, result: &Result<TaskInfo, AppError>) {
+        self.spawns.add(
+            1,
+            &[
+                KeyValue::new("vendor", tr.vendor.clone()),
+                KeyValue::new("clientid", tr.clientid.clone()),
+                KeyValue::new("outcome", spawn_outcome(result)),
+            ],
+        );
+    }
+
+    // Attempts that never started or haven't stopped have no duration.
+    pub fn record_task(&self, vendor: &str, attempt: &Attempt) {
+        let Some(stopped_at) = attempt.stopped_at else {
+            return;
+        };
+        let Ok(duration) = (stopped_at - attempt.started_at).to_std() else {
+            return;
+        };
+        let exit_code = attempt
+            .exit_code
+            .map_or("none".to_string(), |code| code.to_string());
+        self.task_duration.record(
+            duration.as_secs_f64(),
+            &[
+                KeyValue::new("vendor", vendor.to_string()),
+                KeyValue::new("exit_code", exit_code),
+            ],
+        );
+    }
+}
+
+pub fn spawn_outcome(result: &Result<TaskInfo, AppError>) -> &'static str {
+    match result {
+        Ok(_) => "launched",
+        Err(AppError::QuotaExceededError(_)) => "quota_exceeded",
+        Err(AppError::ConcurrencyConflictError(_)) => "conflict",
+        Err(AppError::ValidationError(_)) | Err(AppError::UnsupportedVendor(_)) => "invalid",
+        Err(_) => "failed",
+    }
+}
+
+impl Intercept for AwsCallMetrics {
+    fn name(&self) -> &'static str {
+        "AwsCallMetrics"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        cfg.interceptor_state()
+            .store_put(CallStarted(Instant::now()));
+        Ok(())
+    }
+
+    fn read_after_execution(
+        &self,
+        context: &FinalizerInterceptorContextRef<'_>,
+        _runtime_components: &RuntimeComponents,
+        cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        let (Some(started), Some(metadata)) = (cfg.load::<CallStarted>(), cfg.load::<Metadata>())
+        else {
+            return Ok(());
+        };
+
+        let error = match context.output_or_error() {
+            Some(Ok(_)) => None,
+            Some(Err(err)) => Some(match context.response() {
+                Some(response) => response.status().as_u16().to_string(),
+                None if err.is_timeout_error() => "timeout".to_string(),
+                None if err.is_connector_error() => "connection".to_string(),
+                None => "other".to_string(),
+            }),
+            None => Some("other".to_string()),
+        };
+
+        let mut attributes = vec![
+            KeyValue::new("service", metadata.service().to_string()),
+            KeyValue::new("operation", metadata.name().to_string()),
+        ];
+        let metrics = metrics();
+        metrics.aws_call_duration.record(
+            started.0.elapsed().as_secs_f64(),
+            &[
+                attributes.clone(),
+                vec![KeyValue::new(
+                    "outcome",
+                    if error.is_some() { "error" } else { "ok" },
+                )],
+            ]
+            .concat(),
+        );
+        if let Some(error) = error {
+            attributes.push(KeyValue::new("error", error));
+            metrics.aws_call_errors.add(1, &attributes);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use chrono::Utc;
+    use prometheus::{Encoder, TextEncoder};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::sizing::models::TaskSize;
+
+    fn exported(exporter: &MetricsExporter) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&exporter.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn exports_spawns_and_task_durations_to_prometheus() {
+        let exporter = MetricsExporter::new(&MetricsConfig::default()).unwrap();
+        let metrics = Metrics::new(&exporter.meter());
+
+        let tr: TaskRequest = serde_json::from_value(serde_json::json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+        }))
+        .unwrap();
+        metrics.record_spawn(
+            &tr,
+            &Err(AppError::QuotaExceededError("5 of 5".to_string())),
+        );
+        let started_at = Utc::now();
+        metrics.record_task(
+            "bloomberg",
+            &Attempt {
+                number: 1,
+                task_arn: "arn".to_string(),
+                size: TaskSize::default(),
+                capacity_provider: None,
+                started_at,
+                stopped_at: Some(started_at + chrono::Duration::seconds(90)),
+                exit_code: Some(75),
+                stop_code: None,
+                stopped_reason: None,
+                container_reason: None,
+                outcome: None,
+            },
+        );
+
+        let text = exported(&exporter);
+        assert!(
+            text.contains(
+                "spawns_total{clientid=\"client-1\",outcome=\"quota_exceeded\",vendor=\"bloomberg\"} 1"
+            ),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains(
+                "task_duration_seconds_bucket{exit_code=\"75\",vendor=\"bloomberg\",le=\"120\"} 1"
+            ),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains(
+                "task_duration_seconds_bucket{exit_code=\"75\",vendor=\"bloomberg\",le=\"60\"} 0"
+            ),
+            "{}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_http_metrics_by_route() {
+        let http_metrics = http_metrics();
+        let app = Router::new()
+            .route("/jobs/:id", get(|| async { "job" }))
+            .merge(http_metrics.routes())
+            .layer(http_metrics);
+        let get = |path: &str| {
+            let request = Request::get(path).body(Body::empty()).unwrap();
+            app.clone().oneshot(request)
+        };
+
+        get("/jobs/1").await.unwrap();
+        let response = get(HTTP_METRICS_PATH).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let exported = String::from_utf8_lossy(&body);
+
+        assert!(
+            exported.contains(r#"http_route="/jobs/:id""#),
+            "{}",
+            exported
+        );
+        assert!(!exported.contains(HTTP_METRICS_PATH), "{}", exported);
+    }
+}
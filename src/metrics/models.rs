@@ -0,0 +1,58 @@
+use std::{
+    sync::{atomic::AtomicU64, Arc, OnceLock},
+    time::Instant,
+};
+
+use aws_smithy_types::config_bag::{Storable, StoreReplace};
+use opentelemetry::metrics::{Counter, Histogram};
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use prometheus::Registry;
+
+pub const METER_NAME: &str = "ecs-task-spawner";
+
+// Where the HTTP request metrics are served, next to /metrics.
+pub const HTTP_METRICS_PATH: &str = "/metrics/http";
+
+// Seconds. AWS calls take tens of milliseconds, RunTask sometimes a few seconds.
+pub const AWS_CALL_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// Seconds. Workers run for minutes to hours.
+pub const TASK_DURATION_BUCKETS: &[f64] = &[
+    30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0, 7200.0, 14400.0, 28800.0,
+];
+
+// Instruments of the installed exporter. Until it is installed, e.g. while the config is
+// loaded or in tests, recordings go to instruments that drop them.
+pub static METRICS: OnceLock<Metrics> = OnceLock::new();
+pub static UNRECORDED: OnceLock<Metrics> = OnceLock::new();
+
+pub struct Metrics {
+    // Launch attempts by vendor, clientid and outcome.
+    pub spawns: Counter<u64>,
+    // By service, operation and outcome.
+    pub aws_call_duration: Histogram<f64>,
+    // By service, operation and error: the HTTP status, or why there was no response.
+    pub aws_call_errors: Counter<u64>,
+    // Stopped workers by vendor and exit code.
+    pub task_duration: Histogram<f64>,
+    // Jobs waiting for their next attempt, as of the watcher's last check.
+    pub retry_scheduled: Arc<AtomicU64>,
+}
+
+// The meter provider behind /metrics, and behind the OTLP collector when one is configured.
+#[derive(Clone)]
+pub struct MetricsExporter {
+    pub registry: Registry,
+    pub provider: SdkMeterProvider,
+}
+
+// Records the latency and errors of every call made by an AWS client it is added to.
+#[derive(Debug, Default)]
+pub struct AwsCallMetrics;
+
+#[derive(Debug, Clone)]
+pub struct CallStarted(pub Instant);
+
+impl Storable for CallStarted {
+    type Storer = StoreReplace<Self>;
+}
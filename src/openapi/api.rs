@@ -0,0 +1,12 @@
+use super::{handlers::get_openapi, models::ApiDoc};
+
+use axum::{routing::get, Router};
+use utoipa::OpenApi;
+use utoipa_redoc::{Redoc, Servable};
+
+// Served without auth, like the health checks.
+pub fn router() -> Router {
+    Router::new()
+        .route("/openapi.json", get(get_openapi))
+        .merge(Redoc::with_url("/docs", ApiDoc::openapi()))
+}
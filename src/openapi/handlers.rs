@@ -0,0 +1,8 @@
+use axum::Json;
+use utoipa::OpenApi;
+
+use super::models::ApiDoc;
+
+pub async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
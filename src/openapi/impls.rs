@@ -0,0 +1,44 @@
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    Modify,
+};
+
+use super::models::SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        // Taken from Cargo.toml, which has none.
+        openapi.info.license = None;
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use utoipa::OpenApi;
+
+    use super::super::models::{ApiDoc, SPEC_FILE};
+
+    // Run with UPDATE_OPENAPI=1 to write the document after changing the API.
+    #[test]
+    fn committed_spec_matches_the_code() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(SPEC_FILE);
+        let spec = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(&path, &spec).unwrap();
+        }
+
+        let committed = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            committed == spec,
+            "{} is out of date, run `UPDATE_OPENAPI=1 cargo test` and commit it",
+            SPEC_FILE
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use utoipa::OpenApi;
+
+use crate::{
+    app,
+    ecs::models::{
+        CapacityProviderItem, ContainerInfo, EcsEnvVar, EcsTag, Priority, RequestId,
+        RunningDuration, TaskFamily, TaskInfo, TaskRequest,
+    },
+    errors::models::{ErrorBody, ErrorResponse},
+    health,
+    health::models::{Health, ReadinessCheck, ReadinessReport},
+    sizing::models::Architecture,
+};
+
+// Committed next to Cargo.toml, so consumers can generate clients without running us.
+pub const SPEC_FILE: &str = "openapi.json";
+
+// The API as documented on /openapi.json and /docs.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "ECS task spawner",
+        description = "Launches vendor workers on ECS and reports on them."
+    ),
+    paths(
+        app::handlers::spawn,
+        app::handlers::get_task_family,
+        app::handlers::get_tasks,
+        app::handlers::get_tasks_started_by,
+        health::handlers::health,
+        health::handlers::ready,
+    ),
+    components(schemas(
+        TaskRequest,
+        Priority,
+        Architecture,
+        CapacityProviderItem,
+        EcsEnvVar,
+        TaskInfo,
+        RunningDuration,
+        ContainerInfo,
+        EcsTag,
+        TaskFamily,
+        RequestId,
+        ErrorResponse,
+        ErrorBody,
+        Health,
+        ReadinessReport,
+        ReadinessCheck,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "tasks", description = "Launching and finding workers"),
+        (name = "health", description = "Liveness and readiness, without auth"),
+    )
+)]
+pub struct ApiDoc;
+
+// Adds the bearer API key the authenticated routes require, and drops the empty license.
+pub struct SecuritySchemes;
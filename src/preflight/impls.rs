@@ -0,0 +1,377 @@
+use std::fmt;
+
+use aws_config::SdkConfig;
+use aws_sdk_ecs::error::DisplayErrorContext;
+
+use super::models::{Preflight, PreflightCheck, PreflightReport};
+use crate::{
+    aws_client, config::models::AppConfig, ecs::models::LogRouting, secrets::impls::denied_secrets,
+};
+
+impl Preflight {
+    pub fn new(sdk_config: &SdkConfig) -> Self {
+        Preflight {
+            ecs: aws_client!(aws_sdk_ecs, sdk_config),
+            ec2: aws_client!(aws_sdk_ec2, sdk_config),
+            logs: aws_client!(aws_sdk_cloudwatchlogs, sdk_config),
+            iam: aws_client!(aws_sdk_iam, sdk_config),
+        }
+    }
+
+    pub async fn run(&self, cfg: &AppConfig) -> PreflightReport {
+        let mut checks = vec![self.check_cluster(&cfg.ecs.cluster_name).await];
+
+        let mut log_groups = vec![cfg.ecs.log_group.clone()];
+        for vendor in cfg.vendors.values() {
+            if let LogRouting::Awslogs {
+                group: Some(group), ..
+            } = &vendor.logging
+            {
+                log_groups.push(group.clone());
+            }
+        }
+        log_groups.sort();
+        log_groups.dedup();
+        for log_group in log_groups.iter() {
+            checks.push(self.check_log_group(log_group).await);
+        }
+
+        let mut vendors: Vec<(&String, &String)> = cfg
+            .vendors
+            .iter()
+            .map(|(name, vendor)| (name, &vendor.image))
+            .chain(cfg.vendors.iter().flat_map(|(name, vendor)| {
+                vendor
+                    .sidecars
+                    .iter()
+                    .map(move |sidecar| (name, &sidecar.image))
+            }))
+            .collect();
+        vendors.sort();
+        for (vendor, image) in vendors {
+            checks.push(PreflightCheck::new(
+                format!("image {} of {}", image, vendor),
+                image_reference_error(image),
+            ));
+        }
+
+        checks.extend(self.check_network(cfg).await);
+        checks.push(self.check_execution_role(cfg).await);
+
+        PreflightReport { checks }
+    }
+
+    async fn check_cluster(&self, cluster_name: &str) -> PreflightCheck {
+        let name = format!("cluster {}", cluster_name);
+        let response = match self
+            .ecs
+            .describe_clusters()
+            .clusters(cluster_name)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => return PreflightCheck::failed(name, DisplayErrorContext(&err)),
+        };
+
+        let error = match response.clusters().first() {
+            None => Some("does not exist".to_string()),
+            Some(cluster) if cluster.status() != Some("ACTIVE") => Some(format!(
+                "is {}, not ACTIVE",
+                cluster.status().unwrap_or("in an unknown state")
+            )),
+            Some(_) => None,
+        };
+        PreflightCheck::new(name, error)
+    }
+
+    async fn check_log_group(&self, log_group: &str) -> PreflightCheck {
+        let name = format!("log group {}", log_group);
+        let response = self
+            .logs
+            .describe_log_groups()
+            .log_group_name_prefix(log_group)
+            .send()
+            .await;
+        match response {
+            Ok(response) => {
+                let exists = response
+                    .log_groups()
+                    .iter()
+                    .any(|group| group.log_group_name() == Some(log_group));
+                PreflightCheck::new(name, (!exists).then(|| "does not exist".to_string()))
+            }
+            Err(err) => PreflightCheck::failed(name, DisplayErrorContext(&err)),
+        }
+    }
+
+    // Subnets and the security group have to exist and be in the same VPC.
+    async fn check_network(&self, cfg: &AppConfig) -> Vec<PreflightCheck> {
+        let mut checks = Vec::new();
+
+        let subnet_ids: Vec<String> = cfg.ecs.subnets.iter().map(|s| s.id.clone()).collect();
+        let name = format!("subnets {}", subnet_ids.join(", "));
+        // Without ids, DescribeSubnets would list every subnet in the account.
+        let subnets = if subnet_ids.is_empty() {
+            checks.push(PreflightCheck::failed(name.clone(), "none are configured"));
+            Vec::new()
+        } else {
+            match self
+                .ec2
+                .describe_subnets()
+                .set_subnet_ids(Some(subnet_ids))
+                .send()
+                .await
+            {
+                Ok(response) => response.subnets().to_vec(),
+                Err(err) => {
+                    checks.push(PreflightCheck::failed(
+                        name.clone(),
+                        DisplayErrorContext(&err),
+                    ));
+                    Vec::new()
+                }
+            }
+        };
+        if !subnets.is_empty() {
+            let wrong_az: Vec<String> = cfg
+                .ecs
+                .subnets
+                .iter()
+                .filter(|configured| !configured.availability_zone.is_empty())
+                .filter_map(|configured| {
+                    let subnet = subnets
+                        .iter()
+                        .find(|subnet| subnet.subnet_id() == Some(configured.id.as_str()))?;
+                    let actual = subnet.availability_zone().unwrap_or_default();
+                    (actual != configured.availability_zone)
+                        .then(|| format!("{} is in {}", configured.id, actual))
+                })
+                .collect();
+            checks.push(PreflightCheck::new(
+                name,
+                (!wrong_az.is_empty()).then(|| wrong_az.join(", ")),
+            ));
+        }
+
+        let name = format!("security group {}", cfg.ecs.security_group_id);
+        match self
+            .ec2
+            .describe_security_groups()
+            .group_ids(&cfg.ecs.security_group_id)
+            .send()
+            .await
+        {
+            Ok(response) => {
+                let vpc = response
+                    .security_groups()
+                    .first()
+                    .and_then(|group| group.vpc_id());
+                let other_vpc =
+                    subnets
+                        .iter()
+                        .find(|subnet| subnet.vpc_id() != vpc)
+                        .map(|subnet| {
+                            format!(
+                                "is in {} but {} is in {}",
+                                vpc.unwrap_or("no VPC"),
+                                subnet.subnet_id().unwrap_or_default(),
+                                subnet.vpc_id().unwrap_or("no VPC")
+                            )
+                        });
+                checks.push(PreflightCheck::new(name, other_vpc));
+            }
+            Err(err) => checks.push(PreflightCheck::failed(name, DisplayErrorContext(&err))),
+        }
+
+        checks
+    }
+
+    // A role that can't read a secret fails every launch. Without permission to simulate
+    // policies we can only hope for the best, so that doesn't fail the check.
+    async fn check_execution_role(&self, cfg: &AppConfig) -> PreflightCheck {
+        let name = format!("execution role {}", cfg.ecs.execution_role_arn);
+        match denied_secrets(&self.iam, cfg).await {
+            Ok(denied) => PreflightCheck::new(
+                name,
+                (!denied.is_empty()).then(|| format!("is not allowed {}", denied.join(", "))),
+            ),
+            Err(err) => PreflightCheck {
+                name,
+                passed: true,
+                detail: format!("could not check secret access: {}", err),
+            },
+        }
+    }
+}
+
+impl PreflightCheck {
+    fn new(name: String, error: Option<String>) -> Self {
+        PreflightCheck {
+            name,
+            passed: error.is_none(),
+            detail: error.unwrap_or_else(|| "ok".to_string()),
+        }
+    }
+
+    fn failed(name: String, error: impl fmt::Display) -> Self {
+        PreflightCheck::new(name, Some(error.to_string()))
+    }
+}
+
+impl PreflightReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in self.checks.iter() {
+            let status = if check.passed { "PASS" } else { "FAIL" };
+            writeln!(f, "{} {}: {}", status, check.name, check.detail)?;
+        }
+        let failed = self.checks.iter().filter(|check| !check.passed).count();
+        write!(
+            f,
+            "{} of {} checks passed",
+            self.checks.len() - failed,
+            self.checks.len()
+        )
+    }
+}
+
+// What is wrong with an image reference like
+// 123456789012.dkr.ecr.us-east-1.amazonaws.com/soi/worker:1.2@sha256:<digest>, if anything.
+pub fn image_reference_error(image: &str) -> Option<String> {
+    let (name, digest) = match image.split_once('@') {
+        Some((name, digest)) => (name, Some(digest)),
+        None => (image, None),
+    };
+    if let Some(digest) = digest {
+        let valid = digest
+            .strip_prefix("sha256:")
+            .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if !valid {
+            return Some(format!("digest {} is not sha256:<64 hex digits>", digest));
+        }
+    }
+
+    // A colon after the last slash starts the tag, one before it is a registry port.
+    let (repository, tag) = match name.rsplit_once(':') {
+        Some((repository, tag)) if !tag.contains('/') => (repository, Some(tag)),
+        _ => (name, None),
+    };
+    if let Some(tag) = tag {
+        let valid = tag.len() <= 128
+            && tag
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+            && tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_.-".contains(c));
+        if !valid {
+            return Some(format!("tag {:?} is not valid", tag));
+        }
+    }
+
+    let mut components: Vec<&str> = repository.split('/').collect();
+    // The first component is a registry when it looks like a host.
+    if components.len() > 1 && (components[0].contains(['.', ':']) || components[0] == "localhost")
+    {
+        components.remove(0);
+    }
+    let valid_component = |component: &str| {
+        !component.is_empty()
+            && component
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c))
+            && component.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && component.ends_with(|c: char| c.is_ascii_alphanumeric())
+    };
+    if let Some(component) = components
+        .iter()
+        .find(|component| !valid_component(component))
+    {
+        return Some(format!(
+            "repository path component {:?} must be lowercase letters, digits and . _ -",
+            component
+        ));
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_names_with_registries_tags_and_digests() {
+        let digest = format!("sha256:{}", "0a".repeat(32));
+        for image in [
+            "worker".to_string(),
+            "soi/worker:latest".to_string(),
+            "123456789012.dkr.ecr.us-east-1.amazonaws.com/soi/worker:1.2".to_string(),
+            "localhost/worker".to_string(),
+            "localhost:5000/worker:v1_rc-2".to_string(),
+            "registry:5000/soi/worker".to_string(),
+            format!("public.ecr.aws/soi/worker@{}", digest),
+            format!("soi/worker:1.2@{}", digest),
+        ] {
+            assert_eq!(image_reference_error(&image), None, "{}", image);
+        }
+    }
+
+    #[test]
+    fn only_takes_sha256_digests() {
+        assert_eq!(
+            image_reference_error("soi/worker@sha256:abc").unwrap(),
+            "digest sha256:abc is not sha256:<64 hex digits>"
+        );
+        assert_eq!(
+            image_reference_error(&format!("soi/worker@md5:{}", "a".repeat(64))).unwrap(),
+            format!(
+                "digest md5:{} is not sha256:<64 hex digits>",
+                "a".repeat(64)
+            )
+        );
+    }
+
+    #[test]
+    fn checks_the_tag_characters_and_length() {
+        assert_eq!(
+            image_reference_error("soi/worker:").unwrap(),
+            "tag \"\" is not valid"
+        );
+        assert_eq!(
+            image_reference_error("soi/worker:-dev").unwrap(),
+            "tag \"-dev\" is not valid"
+        );
+        assert_eq!(
+            image_reference_error("soi/worker:a+b").unwrap(),
+            "tag \"a+b\" is not valid"
+        );
+        assert_eq!(
+            image_reference_error(&format!("soi/worker:{}", "a".repeat(128))),
+            None
+        );
+        assert!(image_reference_error(&format!("soi/worker:{}", "a".repeat(129))).is_some());
+    }
+
+    #[test]
+    fn wants_lowercase_repository_paths() {
+        assert_eq!(
+            image_reference_error("soi/Worker").unwrap(),
+            "repository path component \"Worker\" must be lowercase letters, digits and . _ -"
+        );
+        assert_eq!(
+            image_reference_error("soi//worker").unwrap(),
+            "repository path component \"\" must be lowercase letters, digits and . _ -"
+        );
+        // Only a registry can have upper case letters, and only a host is a registry.
+        assert_eq!(image_reference_error("Registry.example.com/worker"), None);
+        assert!(image_reference_error("Soi/worker").is_some());
+    }
+}
@@ -0,0 +1,27 @@
+use aws_sdk_cloudwatchlogs::Client as LogsClient;
+use aws_sdk_ec2::Client as Ec2Client;
+use aws_sdk_ecs::Client as EcsClient;
+use aws_sdk_iam::Client as IamClient;
+use serde::Serialize;
+
+// Checks that the infrastructure the config points at exists, so misconfiguration shows up
+// at startup rather than on the first spawn.
+#[derive(Debug, Clone)]
+pub struct Preflight {
+    pub ecs: EcsClient,
+    pub ec2: Ec2Client,
+    pub logs: LogsClient,
+    pub iam: IamClient,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreflightCheck {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
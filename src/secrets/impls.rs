@@ -0,0 +1,290 @@
+use std::cmp::Reverse;
+
+use aws_sdk_iam::{types::PolicyEvaluationDecisionType, Client as IamClient};
+
+use super::models::{SecretSource, REDACTED, REDACTIONS};
+use crate::{config::models::AppConfig, errors::models::AppError};
+
+const PARTITIONS: [&str; 3] = ["aws", "aws-cn", "aws-us-gov"];
+
+impl SecretSource {
+    pub fn parse(value_from: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "{:?} is not an SSM parameter or Secrets Manager secret ARN",
+                value_from
+            )
+        };
+
+        let parts: Vec<&str> = value_from.splitn(6, ':').collect();
+        let [prefix, partition, service, region, account, resource] = parts[..] else {
+            return Err(invalid());
+        };
+        if prefix != "arn"
+            || !PARTITIONS.contains(&partition)
+            || region.is_empty()
+            || account.len() != 12
+            || !account.chars().all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        match service {
+            "ssm" => {
+                let name = resource.strip_prefix("parameter/").ok_or_else(invalid)?;
+                if !valid_name(name) {
+                    return Err(invalid());
+                }
+                Ok(SecretSource::Ssm {
+                    arn: value_from.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            "secretsmanager" => {
+                let rest = resource.strip_prefix("secret:").ok_or_else(invalid)?;
+                // The optional json-key, version-stage and version-id select what ECS injects,
+                // IAM only knows the secret itself.
+                let mut fields = rest.split(':');
+                let name = fields.next().unwrap_or_default();
+                if !valid_name(name) || fields.count() > 3 {
+                    return Err(invalid());
+                }
+                Ok(SecretSource::SecretsManager {
+                    arn: format!(
+                        "arn:{}:secretsmanager:{}:{}:secret:{}",
+                        partition, region, account, name
+                    ),
+                    name: name.to_string(),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn arn(&self) -> &str {
+        match self {
+            SecretSource::Ssm { arn, .. } | SecretSource::SecretsManager { arn, .. } => arn,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            SecretSource::Ssm { name, .. } | SecretSource::SecretsManager { name, .. } => name,
+        }
+    }
+
+    // What the execution role needs to be allowed to do for ECS to fetch the secret.
+    pub fn action(&self) -> &str {
+        match self {
+            SecretSource::Ssm { .. } => "ssm:GetParameters",
+            SecretSource::SecretsManager { .. } => "secretsmanager:GetSecretValue",
+        }
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/_+=.@-".contains(c))
+}
+
+fn sources(cfg: &AppConfig) -> Vec<SecretSource> {
+    cfg.vendors
+        .values()
+        .flat_map(|vendor| vendor.secrets.iter())
+        .filter_map(|secret| SecretSource::parse(&secret.value_from).ok())
+        .collect()
+}
+
+// Registers every vendor secret ARN, and the names that are paths, for redaction. Called
+// once the config is loaded.
+pub fn register_redactions(cfg: &AppConfig) {
+    let mut redactions: Vec<String> = cfg
+        .vendors
+        .values()
+        .flat_map(|vendor| vendor.secrets.iter())
+        .map(|secret| secret.value_from.clone())
+        .collect();
+    for source in sources(cfg) {
+        redactions.push(source.arn().to_string());
+        // A bare name like "token" would redact ordinary words out of every message.
+        if source.name().contains('/') {
+            redactions.push(source.name().to_string());
+        }
+    }
+    // Longest first, so an ARN is replaced as a whole before the name inside it is.
+    redactions.sort_by(|a, b| Reverse(a.len()).cmp(&Reverse(b.len())).then(a.cmp(b)));
+    redactions.dedup();
+
+    *REDACTIONS.write().unwrap() = redactions;
+}
+
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for redaction in REDACTIONS.read().unwrap().iter() {
+        text = text.replace(redaction.as_str(), REDACTED);
+    }
+    text
+}
+
+// The secrets the execution role isn't allowed to read, as "<action> on <arn>". ECS only
+// finds out when a task fails to start, so IAM is asked up front.
+pub async fn denied_secrets(iam: &IamClient, cfg: &AppConfig) -> Result<Vec<String>, AppError> {
+    let mut denied = Vec::new();
+    for source in sources(cfg) {
+        let response = iam
+            .simulate_principal_policy()
+            .policy_source_arn(&cfg.ecs.execution_role_arn)
+            .action_names(source.action())
+            .resource_arns(source.arn())
+            .send()
+            .await?;
+
+        let results = response.evaluation_results();
+        let allowed = !results.is_empty()
+            && results
+                .iter()
+                .all(|result| result.eval_decision() == &PolicyEvaluationDecisionType::Allowed);
+        if !allowed {
+            denied.push(format!("{} on {}", source.action(), source.arn()));
+        }
+    }
+
+    Ok(denied)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_ssm_and_secrets_manager_arns() {
+        let ssm = |arn: &str, name: &str| SecretSource::Ssm {
+            arn: arn.to_string(),
+            name: name.to_string(),
+        };
+        let secrets_manager = |name: &str| SecretSource::SecretsManager {
+            arn: format!(
+                "arn:aws:secretsmanager:us-east-1:123456789012:secret:{}",
+                name
+            ),
+            name: name.to_string(),
+        };
+        let cases = [
+            (
+                "arn:aws:ssm:us-east-1:123456789012:parameter/soi/bloomberg/token",
+                ssm(
+                    "arn:aws:ssm:us-east-1:123456789012:parameter/soi/bloomberg/token",
+                    "soi/bloomberg/token",
+                ),
+            ),
+            (
+                "arn:aws-us-gov:ssm:us-gov-west-1:123456789012:parameter/token",
+                ssm(
+                    "arn:aws-us-gov:ssm:us-gov-west-1:123456789012:parameter/token",
+                    "token",
+                ),
+            ),
+            (
+                "arn:aws:secretsmanager:us-east-1:123456789012:secret:soi/bloomberg-AbCdEf",
+                secrets_manager("soi/bloomberg-AbCdEf"),
+            ),
+            // The json-key, version-stage and version-id aren't part of the secret's ARN.
+            (
+                "arn:aws:secretsmanager:us-east-1:123456789012:secret:soi/bloomberg-AbCdEf:password::",
+                secrets_manager("soi/bloomberg-AbCdEf"),
+            ),
+            (
+                "arn:aws:secretsmanager:us-east-1:123456789012:secret:soi/bloomberg-AbCdEf::AWSPREVIOUS:",
+                secrets_manager("soi/bloomberg-AbCdEf"),
+            ),
+            (
+                "arn:aws:secretsmanager:us-east-1:123456789012:secret:soi/bloomberg-AbCdEf:::0b7e-42",
+                secrets_manager("soi/bloomberg-AbCdEf"),
+            ),
+        ];
+        for (value_from, source) in cases {
+            assert_eq!(
+                SecretSource::parse(value_from),
+                Ok(source),
+                "{}",
+                value_from
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_anything_but_secret_arns() {
+        let invalid = [
+            "soi/bloomberg/token",
+            "arn:aws:ssm:us-east-1:123456789012:parameter/",
+            "arn:aws:ssm:us-east-1:123456789012:document/token",
+            "arn:aws:ssm:us-east-1:12345:parameter/token",
+            "arn:aws:ssm::123456789012:parameter/token",
+            "arn:azure:ssm:us-east-1:123456789012:parameter/token",
+            "arn:aws:s3:us-east-1:123456789012:parameter/token",
+            "arn:aws:ssm:us-east-1:123456789012:parameter/to ken",
+            "arn:aws:secretsmanager:us-east-1:123456789012:soi/bloomberg",
+            "arn:aws:secretsmanager:us-east-1:123456789012:secret:",
+            "arn:aws:secretsmanager:us-east-1:123456789012:secret:soi/bloomberg:a:b:c:d",
+        ];
+        for value_from in invalid {
+            assert!(SecretSource::parse(value_from).is_err(), "{}", value_from);
+        }
+    }
+
+    #[test]
+    fn redacts_secret_arns_and_path_names_only() {
+        let cfg: AppConfig = serde_json::from_value(json!({
+            "api_key": "key",
+            "log_level": "info",
+            "vendors": { "redacted-vendor": {
+                "image": "public.ecr.aws/soi/worker:latest",
+                "secrets": [
+                    {
+                        "name": "TOKEN",
+                        "value_from": "arn:aws:ssm:us-east-1:123456789012:parameter/redact-test/token",
+                    },
+                    {
+                        "name": "PASSWORD",
+                        "value_from": "arn:aws:secretsmanager:us-east-1:123456789012:secret:redact-test-pw:password::",
+                    },
+                    {
+                        "name": "SHORT",
+                        "value_from": "arn:aws:ssm:us-east-1:123456789012:parameter/token",
+                    },
+                ],
+            } },
+        }))
+        .unwrap();
+        register_redactions(&cfg);
+
+        let cases = [
+            (
+                "ResourceInitializationError: unable to pull secrets: arn:aws:ssm:us-east-1:123456789012:parameter/redact-test/token",
+                "ResourceInitializationError: unable to pull secrets: [REDACTED]",
+            ),
+            ("parameter redact-test/token not found", "parameter [REDACTED] not found"),
+            (
+                "denied on arn:aws:secretsmanager:us-east-1:123456789012:secret:redact-test-pw",
+                "denied on [REDACTED]",
+            ),
+            (
+                "fetching arn:aws:secretsmanager:us-east-1:123456789012:secret:redact-test-pw:password:: failed",
+                "fetching [REDACTED] failed",
+            ),
+            (
+                "arn:aws:ssm:us-east-1:123456789012:parameter/token is missing",
+                "[REDACTED] is missing",
+            ),
+            ("Invalid token provided.", "Invalid token provided."),
+            ("redact-test-pw rotated", "redact-test-pw rotated"),
+        ];
+        for (text, redacted) in cases {
+            assert_eq!(redact(text), redacted);
+        }
+    }
+}
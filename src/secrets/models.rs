@@ -0,0 +1,17 @@
+use std::sync::RwLock;
+
+// Where ECS fetches a secret from, parsed from its ARN.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    // arn:aws:ssm:region:account:parameter/name
+    Ssm { arn: String, name: String },
+    // arn:aws:secretsmanager:region:account:secret:name, optionally followed by
+    // :json-key:version-stage:version-id.
+    SecretsManager { arn: String, name: String },
+}
+
+pub const REDACTED: &str = "[REDACTED]";
+
+// Secret ARNs and names of every vendor, longest first. Anything we send back to callers
+// goes through `redact` so the names of our credentials never leave the service.
+pub static REDACTIONS: RwLock<Vec<String>> = RwLock::new(Vec::new());
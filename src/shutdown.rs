@@ -23,5 +23,5 @@ pub async fn shutdown_signal() {
         _ = terminate => {},
     }
 
-    println!("Signal received, starting graceful shutdown");
+    tracing::info!("signal received, starting graceful shutdown");
 }
@@ -0,0 +1 @@
+pub use ecs_task_spawner_models::sizing::models;
@@ -0,0 +1,467 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use aws_config::SdkConfig;
+use aws_sdk_ssm::error::DisplayErrorContext;
+use config::{Config, ConfigError, Source, Value, ValueKind};
+use serde_json::json;
+use tokio::{sync::watch, task::JoinHandle};
+
+use super::models::{ConfigHandle, ParameterResolver, SSM_BATCH_SIZE, SSM_SCHEME};
+use crate::{
+    audit::{self, models::AuditAction},
+    aws_client,
+    config::models::{AppConfig, SsmConfig},
+};
+
+impl ParameterResolver {
+    pub fn new(sdk_config: &SdkConfig, endpoint_url: Option<&str>) -> Self {
+        let mut sdk_config = sdk_config.to_builder();
+        if let Some(endpoint_url) = endpoint_url {
+            sdk_config.set_endpoint_url(Some(endpoint_url.to_string()));
+        }
+
+        ParameterResolver {
+            client: aws_client!(aws_sdk_ssm, &sdk_config.build()),
+            cache: Arc::default(),
+        }
+    }
+
+    // Values of the given parameters. A missing parameter is an error, SSM being unreachable
+    // only when we have never read the parameter before.
+    pub async fn resolve(&self, names: &[String]) -> Result<HashMap<String, String>, ConfigError> {
+        let mut values = HashMap::new();
+        for chunk in names.chunks(SSM_BATCH_SIZE) {
+            let response = self
+                .client
+                .get_parameters()
+                .set_names(Some(chunk.to_vec()))
+                .with_decryption(true)
+                .send()
+                .await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    let cache = self.cache.lock().unwrap();
+                    if !chunk.iter().all(|name| cache.contains_key(name)) {
+                        return Err(ConfigError::Message(format!(
+                            "could not read SSM parameters {}: {}",
+                            chunk.join(", "),
+                            DisplayErrorContext(&err)
+                        )));
+                    }
+                    tracing::warn!(
+                        "could not read SSM parameters, keeping the last values: {}",
+                        DisplayErrorContext(&err)
+                    );
+                    for name in chunk.iter() {
+                        values.insert(name.clone(), cache[name].clone());
+                    }
+                    continue;
+                }
+            };
+
+            if !response.invalid_parameters().is_empty() {
+                return Err(ConfigError::Message(format!(
+                    "SSM parameters {} do not exist",
+                    response.invalid_parameters().join(", ")
+                )));
+            }
+            // SSM answers with the plain name, whether it was asked for by ARN or with a
+            // :version or :label selector, so map each parameter back to what we asked for.
+            for parameter in response.parameters().iter() {
+                let Some(value) = parameter.value() else {
+                    continue;
+                };
+                let selector = parameter.selector().unwrap_or_default();
+                let asked_as: Vec<String> = [parameter.name(), parameter.arn()]
+                    .into_iter()
+                    .flatten()
+                    .map(|id| format!("{}{}", id, selector))
+                    .collect();
+                for name in chunk.iter().filter(|name| asked_as.contains(name)) {
+                    values.insert(name.clone(), value.to_string());
+                }
+            }
+        }
+
+        self.cache.lock().unwrap().extend(
+            values
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone())),
+        );
+
+        Ok(values)
+    }
+}
+
+// (path, parameter name) of every ssm:// setting, with paths like ecs.subnets[0].id.
+pub fn ssm_references(config: &Config) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut references = Vec::new();
+    for (key, value) in config.collect()? {
+        collect_references(key, &value, &mut references);
+    }
+    Ok(references)
+}
+
+fn collect_references(path: String, value: &Value, references: &mut Vec<(String, String)>) {
+    match &value.kind {
+        ValueKind::String(value) => {
+            if let Some(name) = value.strip_prefix(SSM_SCHEME) {
+                references.push((path, name.to_string()));
+            }
+        }
+        ValueKind::Table(table) => {
+            for (key, value) in table.iter() {
+                collect_references(format!("{}.{}", path, key), value, references);
+            }
+        }
+        ValueKind::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                collect_references(format!("{}[{}]", path, index), value, references);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl ConfigHandle {
+    // Loads the config, failing if an ssm:// setting can't be resolved.
+    pub async fn load(sdk_config: &SdkConfig) -> Result<Self, ConfigError> {
+        // Where to find SSM is itself configured, so it can't come from SSM.
+        let sources = AppConfig::sources()?;
+        let ssm: SsmConfig = sources.get("ssm").unwrap_or_default();
+        let resolver = ParameterResolver::new(sdk_config, ssm.endpoint_url.as_deref());
+        let cfg = AppConfig::resolve(sources, &resolver).await?;
+        let (sender, _) = watch::channel(Arc::new(cfg));
+
+        Ok(ConfigHandle {
+            sender: Arc::new(sender),
+            resolver,
+            refresh_interval: Duration::from_secs(ssm.refresh_interval_secs),
+        })
+    }
+
+    pub fn current(&self) -> Arc<AppConfig> {
+        self.sender.borrow().clone()
+    }
+
+    // Receives every config a refresh switches to.
+    pub fn subscribe(&self) -> watch::Receiver<Arc<AppConfig>> {
+        self.sender.subscribe()
+    }
+
+    // Reloads the config in the background. A reload that fails keeps the current config.
+    pub fn start(&self) -> Option<JoinHandle<()>> {
+        if self.refresh_interval.is_zero() {
+            return None;
+        }
+
+        let handle = self.clone();
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(handle.refresh_interval);
+            // The first tick completes immediately and we just loaded.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let refreshed = match AppConfig::sources() {
+                    Ok(sources) => handle.refresh(sources).await,
+                    Err(err) => Err(err),
+                };
+                if let Err(err) = refreshed {
+                    tracing::warn!(
+                        "config refresh failed, keeping the current settings: {}",
+                        err
+                    );
+                }
+            }
+        }))
+    }
+
+    // Resolves the settings again and switches to them if anything changed.
+    pub async fn refresh(&self, sources: Config) -> Result<(), ConfigError> {
+        let cfg = Arc::new(AppConfig::resolve(sources, &self.resolver).await?);
+        let cluster_name = self.current().ecs.cluster_name.clone();
+        if cfg.ecs.cluster_name != cluster_name {
+            tracing::warn!(
+                "cluster_name changed from {} to {}, restart to move to the new cluster",
+                cluster_name,
+                cfg.ecs.cluster_name
+            );
+        }
+
+        let mut settings = Vec::new();
+        let changed = self.sender.send_if_modified(|current| {
+            if **current == *cfg {
+                return false;
+            }
+            settings = current.changed_settings(&cfg);
+            *current = cfg.clone();
+            true
+        });
+        if changed {
+            tracing::info!(
+                "config changed, switched to the new settings of {}",
+                settings.join(", ")
+            );
+            audit::impls::record(
+                AuditAction::ConfigChange,
+                json!({ "source": "refresh", "changed": settings }),
+                Vec::new(),
+                None,
+            )
+            .await;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    };
+
+    use aws_config::{retry::RetryConfig, BehaviorVersion};
+    use aws_sdk_ssm::config::{Credentials, Region, SharedCredentialsProvider};
+    use axum::{
+        extract::State,
+        http::{header, StatusCode},
+        response::{IntoResponse, Response},
+        routing::post,
+        Json, Router,
+    };
+    use config::{File, FileFormat};
+    use serde_json::Value as JsonValue;
+
+    use super::*;
+
+    const ARN_PREFIX: &str = "arn:aws:ssm:us-east-1:123456789012:parameter";
+
+    // Answers GetParameters like SSM does, from parameters the test sets.
+    #[derive(Default)]
+    struct StandIn {
+        parameters: Mutex<HashMap<String, String>>,
+        down: AtomicBool,
+    }
+
+    async fn get_parameters(State(stand_in): State<Arc<StandIn>>, body: String) -> Response {
+        if stand_in.down.load(Ordering::SeqCst) {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "__type": "InternalServerError", "message": "down" })),
+            )
+                .into_response();
+        }
+
+        let request: JsonValue = serde_json::from_str(&body).unwrap();
+        let parameters = stand_in.parameters.lock().unwrap();
+        let (mut found, mut invalid) = (Vec::new(), Vec::new());
+        for requested in request["Names"].as_array().unwrap() {
+            let requested = requested.as_str().unwrap();
+            let (id, selector) = match requested.rsplit_once(':') {
+                Some((id, version)) if version.chars().all(|c| c.is_ascii_digit()) => {
+                    (id, format!(":{}", version))
+                }
+                _ => (requested, String::new()),
+            };
+            let name = match id.strip_prefix(ARN_PREFIX) {
+                Some(name) => name.to_string(),
+                None => id.to_string(),
+            };
+            match parameters.get(&name) {
+                Some(value) => {
+                    let mut parameter = json!({
+                        "Name": name,
+                        "Value": value,
+                        "ARN": format!("{}{}", ARN_PREFIX, name),
+                    });
+                    if !selector.is_empty() {
+                        parameter["Selector"] = json!(selector);
+                    }
+                    found.push(parameter);
+                }
+                None => invalid.push(requested.to_string()),
+            }
+        }
+        (
+            [(header::CONTENT_TYPE, "application/x-amz-json-1.1")],
+            Json(json!({ "Parameters": found, "InvalidParameters": invalid })),
+        )
+            .into_response()
+    }
+
+    async fn stand_in(parameters: &[(&str, &str)]) -> (Arc<StandIn>, ParameterResolver) {
+        let stand_in = Arc::new(StandIn::default());
+        stand_in.parameters.lock().unwrap().extend(
+            parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        let app = Router::new()
+            .route("/", post(get_parameters))
+            .with_state(stand_in.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let sdk_config = SdkConfig::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(SharedCredentialsProvider::new(Credentials::new(
+                "AKIDTEST", "secret", None, None, "test",
+            )))
+            .retry_config(RetryConfig::disabled())
+            .build();
+        let ssm: SsmConfig =
+            serde_json::from_value(json!({ "endpoint_url": endpoint_url })).unwrap();
+        let resolver = ParameterResolver::new(&sdk_config, ssm.endpoint_url.as_deref());
+        (stand_in, resolver)
+    }
+
+    fn sources(toml: &str) -> Config {
+        Config::builder()
+            .add_source(File::from_str(toml, FileFormat::Toml))
+            .build()
+            .unwrap()
+    }
+
+    const SETTINGS: &str = r#"
+        api_key = "ssm:///soi/api-key"
+        log_level = "info"
+
+        [ecs]
+        cluster_name = "ssm:///soi/cluster"
+        subnets = [
+            { id = "subnet-a", availability_zone = "us-east-1a" },
+            { id = "ssm:///soi/subnet-b:2", availability_zone = "us-east-1b" },
+        ]
+        security_group_id = "ssm://arn:aws:ssm:us-east-1:123456789012:parameter/soi/sg"
+    "#;
+
+    #[test]
+    fn finds_references_in_nested_tables_and_arrays() {
+        let mut references = ssm_references(&sources(
+            r#"
+            api_key = "ssm:///soi/api-key"
+            log_level = "info"
+            retries = [1, 2]
+
+            [ecs]
+            cluster_name = "default"
+            subnets = [
+                { id = "subnet-a", availability_zone = "us-east-1a" },
+                { id = "ssm:///soi/subnet-b", availability_zone = "us-east-1b" },
+            ]
+
+            [vendors.bloomberg]
+            command = ["worker", "ssm:///soi/bloomberg/flag"]
+            size = { cpu = 256, memory = "ssm:///soi/bloomberg/memory" }
+            "#,
+        ))
+        .unwrap();
+        references.sort();
+
+        let expected = [
+            ("api_key", "/soi/api-key"),
+            ("ecs.subnets[1].id", "/soi/subnet-b"),
+            ("vendors.bloomberg.command[1]", "/soi/bloomberg/flag"),
+            ("vendors.bloomberg.size.memory", "/soi/bloomberg/memory"),
+        ];
+        let expected: Vec<(String, String)> = expected
+            .iter()
+            .map(|(path, name)| (path.to_string(), name.to_string()))
+            .collect();
+        assert_eq!(references, expected);
+    }
+
+    #[tokio::test]
+    async fn resolves_names_arns_and_versions() {
+        let (_, resolver) = stand_in(&[
+            ("/soi/api-key", "key"),
+            ("/soi/cluster", "workers"),
+            ("/soi/subnet-b", "subnet-b"),
+            ("/soi/sg", "sg-1"),
+        ])
+        .await;
+
+        let cfg = AppConfig::resolve(sources(SETTINGS), &resolver)
+            .await
+            .unwrap();
+        assert_eq!(cfg.api_key, "key");
+        assert_eq!(cfg.ecs.cluster_name, "workers");
+        assert_eq!(cfg.ecs.subnets[0].id, "subnet-a");
+        assert_eq!(cfg.ecs.subnets[1].id, "subnet-b");
+        assert_eq!(cfg.ecs.security_group_id, "sg-1");
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_parameters() {
+        let (_, resolver) = stand_in(&[("/soi/api-key", "key")]).await;
+
+        let err = resolver
+            .resolve(&["/soi/api-key".to_string(), "/soi/cluster".to_string()])
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "SSM parameters /soi/cluster do not exist");
+    }
+
+    #[tokio::test]
+    async fn keeps_the_last_values_while_ssm_is_unreachable() {
+        let (stand_in, resolver) = stand_in(&[("/soi/api-key", "key")]).await;
+        let names = ["/soi/api-key".to_string()];
+        resolver.resolve(&names).await.unwrap();
+
+        stand_in.down.store(true, Ordering::SeqCst);
+        let values = resolver.resolve(&names).await.unwrap();
+        assert_eq!(values["/soi/api-key"], "key");
+
+        // Never read before, so there is nothing to fall back on.
+        let err = resolver
+            .resolve(&["/soi/cluster".to_string()])
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("could not read SSM parameters /soi/cluster: "));
+    }
+
+    #[tokio::test]
+    async fn switches_to_changed_parameters_on_refresh() {
+        let (stand_in, resolver) = stand_in(&[
+            ("/soi/api-key", "key"),
+            ("/soi/cluster", "workers"),
+            ("/soi/subnet-b", "subnet-b"),
+            ("/soi/sg", "sg-1"),
+        ])
+        .await;
+        let cfg = AppConfig::resolve(sources(SETTINGS), &resolver)
+            .await
+            .unwrap();
+        let (sender, _) = watch::channel(Arc::new(cfg));
+        let handle = ConfigHandle {
+            sender: Arc::new(sender),
+            resolver,
+            refresh_interval: Duration::from_secs(60),
+        };
+        let mut updates = handle.subscribe();
+
+        handle.refresh(sources(SETTINGS)).await.unwrap();
+        assert!(!updates.has_changed().unwrap());
+
+        stand_in
+            .parameters
+            .lock()
+            .unwrap()
+            .insert("/soi/api-key".to_string(), "rotated".to_string());
+        handle.refresh(sources(SETTINGS)).await.unwrap();
+        assert!(updates.has_changed().unwrap());
+        assert_eq!(updates.borrow_and_update().api_key, "rotated");
+
+        // A refresh that fails keeps the current settings.
+        stand_in.parameters.lock().unwrap().remove("/soi/cluster");
+        assert!(handle.refresh(sources(SETTINGS)).await.is_err());
+        assert_eq!(handle.current().api_key, "rotated");
+    }
+}
@@ -0,0 +1,35 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aws_sdk_ssm::Client as SsmClient;
+use tokio::sync::watch;
+
+use crate::config::models::AppConfig;
+
+// Config values of the form ssm://<parameter name> are replaced with the parameter's value.
+pub const SSM_SCHEME: &str = "ssm://";
+
+// GetParameters takes at most 10 names per call.
+pub const SSM_BATCH_SIZE: usize = 10;
+
+// Reads SSM parameters and remembers the last value of each, so a refresh that can't reach
+// SSM keeps running on what it had.
+#[derive(Debug, Clone)]
+pub struct ParameterResolver {
+    pub client: SsmClient,
+    pub cache: Arc<Mutex<HashMap<String, String>>>,
+}
+
+// The current config. Refreshes re-resolve the SSM parameters and swap it when they changed.
+// Launches pick up the new vendor and ECS settings and the dispatcher its new settings, but
+// the cluster the repo lists tasks in and the quota settings stay as they were at startup.
+#[derive(Debug, Clone)]
+pub struct ConfigHandle {
+    pub sender: Arc<watch::Sender<Arc<AppConfig>>>,
+    pub resolver: ParameterResolver,
+    // No refreshes when zero.
+    pub refresh_interval: Duration,
+}
@@ -0,0 +1,9 @@
+use super::{handlers::get_subnets, models::SubnetPool};
+
+use axum::{routing::get, Router};
+
+pub fn router(pool: SubnetPool) -> Router {
+    Router::new()
+        .route("/admin/subnets", get(get_subnets))
+        .with_state(pool)
+}
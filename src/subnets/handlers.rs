@@ -0,0 +1,7 @@
+use axum::{extract::State, Json};
+
+use super::models::{SubnetHealth, SubnetPool};
+
+pub async fn get_subnets(State(pool): State<SubnetPool>) -> Json<Vec<SubnetHealth>> {
+    Json(pool.health())
+}
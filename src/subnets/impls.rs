@@ -0,0 +1,250 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use aws_sdk_ecs::error::ProvideErrorMetadata;
+use chrono::{Duration, Utc};
+use tokio::{sync::watch, task::JoinHandle};
+
+use super::models::{SubnetHealth, SubnetPool, SubnetPoolState, SUBNET_FAILURE_PATTERNS};
+use crate::{
+    config::models::{AppConfig, EcsConfig, SubnetStrategy},
+    errors::models::AppError,
+};
+
+impl SubnetPool {
+    pub fn new(ecs: &EcsConfig) -> Self {
+        let pool = SubnetPool {
+            strategy: ecs.subnet_strategy,
+            cooldown_secs: ecs.subnet_cooldown_secs,
+            state: Arc::new(Mutex::new(SubnetPoolState::default())),
+        };
+        pool.reload(ecs);
+        pool
+    }
+
+    // Switches to the configured subnets, keeping what we know about the ones that stay.
+    pub fn reload(&self, ecs: &EcsConfig) {
+        let mut state = self.state.lock().unwrap();
+        let mut health = HashMap::new();
+        for subnet in ecs.subnets.iter() {
+            let known = state.health.remove(&subnet.id).unwrap_or_default();
+            health.insert(
+                subnet.id.clone(),
+                SubnetHealth {
+                    subnet_id: subnet.id.clone(),
+                    availability_zone: subnet.availability_zone.clone(),
+                    ..known
+                },
+            );
+        }
+        state.health = health;
+        state.subnets = ecs.subnets.clone();
+    }
+
+    // Picks up subnets changed by config refreshes.
+    pub fn follow(&self, mut updates: watch::Receiver<Arc<AppConfig>>) -> JoinHandle<()> {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            while updates.changed().await.is_ok() {
+                let ecs = updates.borrow_and_update().ecs.clone();
+                pool.reload(&ecs);
+            }
+        })
+    }
+
+    // Every subnet, in the order a launch should try them.
+    pub fn candidates(&self) -> Vec<String> {
+        let mut state = self.state.lock().unwrap();
+        let mut subnets: Vec<SubnetHealth> = state
+            .subnets
+            .iter()
+            .filter_map(|subnet| state.health.get(&subnet.id).cloned())
+            .collect();
+        if subnets.is_empty() {
+            return Vec::new();
+        }
+
+        match self.strategy {
+            SubnetStrategy::RoundRobin => {
+                let start = state.next % subnets.len();
+                subnets.rotate_left(start);
+                state.next = state.next.wrapping_add(1);
+            }
+            SubnetStrategy::LeastRecentlyFailed => {
+                subnets.sort_by_key(|subnet| (subnet.last_failure_at, subnet.launches));
+            }
+            SubnetStrategy::AzSpread => {
+                let mut az_launches: HashMap<String, u64> = HashMap::new();
+                for subnet in subnets.iter() {
+                    *az_launches
+                        .entry(subnet.availability_zone.clone())
+                        .or_default() += subnet.launches;
+                }
+                subnets.sort_by_key(|subnet| {
+                    (
+                        az_launches[&subnet.availability_zone],
+                        subnet.launches,
+                        subnet.last_failure_at,
+                    )
+                });
+            }
+        }
+
+        // Whatever the strategy, subnets that just failed go last. The sort is stable, so
+        // the strategy's order holds within each group.
+        let cooldown_start = Utc::now() - Duration::seconds(self.cooldown_secs as i64);
+        subnets.sort_by_key(|subnet| {
+            subnet
+                .last_failure_at
+                .is_some_and(|failed_at| failed_at > cooldown_start)
+        });
+
+        subnets.into_iter().map(|subnet| subnet.subnet_id).collect()
+    }
+
+    pub fn record_success(&self, subnet_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(health) = state.health.get_mut(subnet_id) {
+            health.launches += 1;
+            health.consecutive_failures = 0;
+            health.last_success_at = Some(Utc::now());
+        }
+    }
+
+    pub fn record_failure(&self, subnet_id: &str, reason: &str) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(health) = state.health.get_mut(subnet_id) {
+            health.failures += 1;
+            health.consecutive_failures += 1;
+            health.last_failure_at = Some(Utc::now());
+            health.last_failure = Some(reason.to_string());
+        }
+    }
+
+    pub fn health(&self) -> Vec<SubnetHealth> {
+        let state = self.state.lock().unwrap();
+        state
+            .subnets
+            .iter()
+            .filter_map(|subnet| state.health.get(&subnet.id).cloned())
+            .collect()
+    }
+}
+
+// The reason a launch failed, if the failure means it could succeed in another subnet.
+pub fn subnet_failure(err: &AppError) -> Option<String> {
+    let reason = match err {
+        AppError::TaskSpawnError(reason) => reason.clone(),
+        AppError::RunTaskError(err) => err.message().unwrap_or_default().to_string(),
+        _ => return None,
+    };
+    let lowercase = reason.to_lowercase();
+    SUBNET_FAILURE_PATTERNS
+        .iter()
+        .any(|pattern| lowercase.contains(pattern))
+        .then_some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::models::SubnetConfig;
+
+    // Subnets a to d, with a and b in us-east-1a.
+    fn pool(strategy: SubnetStrategy, cooldown_secs: u64) -> SubnetPool {
+        let subnets = [("a", "1a"), ("b", "1a"), ("c", "1b"), ("d", "1c")]
+            .iter()
+            .map(|(id, az)| SubnetConfig {
+                id: id.to_string(),
+                availability_zone: format!("us-east-{}", az),
+            })
+            .collect();
+        SubnetPool::new(&EcsConfig {
+            subnets,
+            subnet_strategy: strategy,
+            subnet_cooldown_secs: cooldown_secs,
+            ..EcsConfig::default()
+        })
+    }
+
+    fn failed_minutes_ago(pool: &SubnetPool, subnet_id: &str, minutes: i64) {
+        let mut state = pool.state.lock().unwrap();
+        let health = state.health.get_mut(subnet_id).unwrap();
+        health.last_failure_at = Some(Utc::now() - Duration::minutes(minutes));
+    }
+
+    #[test]
+    fn round_robin_starts_each_launch_one_subnet_further() {
+        let pool = pool(SubnetStrategy::RoundRobin, 300);
+        assert_eq!(pool.candidates(), ["a", "b", "c", "d"]);
+        assert_eq!(pool.candidates(), ["b", "c", "d", "a"]);
+        assert_eq!(pool.candidates(), ["c", "d", "a", "b"]);
+    }
+
+    #[test]
+    fn least_recently_failed_tries_subnets_that_never_failed_first() {
+        let pool = pool(SubnetStrategy::LeastRecentlyFailed, 0);
+        failed_minutes_ago(&pool, "a", 5);
+        failed_minutes_ago(&pool, "c", 60);
+        pool.record_success("b");
+
+        // Never failed, fewest launches first; then the oldest failure.
+        assert_eq!(pool.candidates(), ["d", "b", "c", "a"]);
+    }
+
+    #[test]
+    fn az_spread_prefers_the_zone_with_the_fewest_launches() {
+        let pool = pool(SubnetStrategy::AzSpread, 300);
+        pool.record_success("a");
+        pool.record_success("d");
+        pool.record_success("d");
+
+        // us-east-1b has no launches, us-east-1a one between its two subnets.
+        assert_eq!(pool.candidates(), ["c", "b", "a", "d"]);
+    }
+
+    #[test]
+    fn tries_subnets_in_their_cooldown_last() {
+        let pool = pool(SubnetStrategy::RoundRobin, 300);
+        pool.record_failure("a", "RESOURCE:ENI");
+        failed_minutes_ago(&pool, "b", 10);
+
+        // a failed just now, b's failure is older than the 5 minute cooldown.
+        assert_eq!(pool.candidates(), ["b", "c", "d", "a"]);
+
+        let health = pool.health();
+        assert_eq!(health[0].consecutive_failures, 1);
+        assert_eq!(health[0].last_failure.as_deref(), Some("RESOURCE:ENI"));
+
+        pool.record_success("a");
+        assert_eq!(pool.health()[0].consecutive_failures, 0);
+    }
+
+    #[test]
+    fn fails_over_only_on_network_and_capacity_errors() {
+        let eni = AppError::TaskSpawnError(
+            "ResourceInitializationError: ENI limit reached for subnet".to_string(),
+        );
+        assert_eq!(
+            subnet_failure(&eni).as_deref(),
+            Some("ResourceInitializationError: ENI limit reached for subnet")
+        );
+        assert!(subnet_failure(&AppError::TaskSpawnError(
+            "Capacity is unavailable at this time.".to_string()
+        ))
+        .is_some());
+
+        assert_eq!(
+            subnet_failure(&AppError::TaskSpawnError("RESOURCE:MEMORY".to_string())),
+            None
+        );
+        assert_eq!(
+            subnet_failure(&AppError::ValidationError(
+                "no free addresses, but not from ECS".to_string()
+            )),
+            None
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+pub use ecs_task_spawner_models::subnets::models::SubnetHealth;
+
+use crate::config::models::{SubnetConfig, SubnetStrategy};
+
+// Spreads launches over the configured subnets and keeps track of which ones are failing.
+#[derive(Debug, Clone)]
+pub struct SubnetPool {
+    pub strategy: SubnetStrategy,
+    pub cooldown_secs: u64,
+    pub state: Arc<Mutex<SubnetPoolState>>,
+}
+
+#[derive(Debug, Default)]
+pub struct SubnetPoolState {
+    // Replaced when a config refresh changes them.
+    pub subnets: Vec<SubnetConfig>,
+    // Where the next round-robin pass starts.
+    pub next: usize,
+    pub health: HashMap<String, SubnetHealth>,
+}
+
+// RunTask failure reasons that mean "this subnet or AZ can't take the task right now".
+pub const SUBNET_FAILURE_PATTERNS: &[&str] = &[
+    "eni limit",
+    "network interface",
+    "free addresses",
+    "ip address",
+    "capacity is unavailable",
+    "insufficient capacity",
+    "availability zone",
+];
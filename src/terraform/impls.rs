@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use config::{ConfigError, Map, Source, Value, ValueKind};
+
+use super::models::{
+    TerraformOutput, TerraformOutputs, SUBNET_AZS_OUTPUT, SUBNET_IDS_OUTPUT, TERRAFORM_OUTPUTS,
+};
+
+impl TerraformOutputs {
+    pub fn new(path: &str) -> Self {
+        TerraformOutputs {
+            path: path.to_string(),
+        }
+    }
+
+    fn read(&self) -> Result<HashMap<String, TerraformOutput>, ConfigError> {
+        let contents = std::fs::read_to_string(&self.path).map_err(|err| {
+            ConfigError::Message(format!(
+                "could not read terraform outputs file {}: {}",
+                self.path, err
+            ))
+        })?;
+        serde_json::from_str(&contents).map_err(|err| {
+            ConfigError::Message(format!(
+                "{} is not `terraform output -json` output: {}",
+                self.path, err
+            ))
+        })
+    }
+
+    fn string(&self, name: &str, value: &serde_json::Value) -> Result<String, ConfigError> {
+        value.as_str().map(str::to_string).ok_or_else(|| {
+            ConfigError::Message(format!(
+                "terraform output {} in {} should be a string",
+                name, self.path
+            ))
+        })
+    }
+
+    fn strings(&self, name: &str, value: &serde_json::Value) -> Result<Vec<String>, ConfigError> {
+        let invalid = || {
+            ConfigError::Message(format!(
+                "terraform output {} in {} should be a list of strings",
+                name, self.path
+            ))
+        };
+        value
+            .as_array()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|item| item.as_str().map(str::to_string).ok_or_else(invalid))
+            .collect()
+    }
+}
+
+impl Source for TerraformOutputs {
+    fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+        Box::new(self.clone())
+    }
+
+    fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+        let outputs = self.read()?;
+        let origin = Some(&self.path);
+
+        let mut missing: Vec<&str> = TERRAFORM_OUTPUTS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain([SUBNET_IDS_OUTPUT])
+            .filter(|name| !outputs.contains_key(*name))
+            .collect();
+        if !missing.is_empty() {
+            missing.sort();
+            return Err(ConfigError::Message(format!(
+                "terraform outputs file {} is missing {}",
+                self.path,
+                missing.join(", ")
+            )));
+        }
+
+        let mut unknown: Vec<&String> = outputs
+            .keys()
+            .filter(|name| {
+                !TERRAFORM_OUTPUTS.iter().any(|(known, _)| known == name)
+                    && *name != SUBNET_IDS_OUTPUT
+                    && *name != SUBNET_AZS_OUTPUT
+            })
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            let unknown: Vec<&str> = unknown.iter().map(|name| name.as_str()).collect();
+            tracing::warn!(
+                "ignoring terraform outputs {} in {}",
+                unknown.join(", "),
+                self.path
+            );
+        }
+
+        let mut settings = Map::new();
+        for (name, key) in TERRAFORM_OUTPUTS.iter() {
+            let value = self.string(name, &outputs[*name].value)?;
+            settings.insert(key.to_string(), Value::new(origin, value));
+        }
+
+        let ids = self.strings(SUBNET_IDS_OUTPUT, &outputs[SUBNET_IDS_OUTPUT].value)?;
+        let azs = match outputs.get(SUBNET_AZS_OUTPUT) {
+            Some(output) => self.strings(SUBNET_AZS_OUTPUT, &output.value)?,
+            None => vec![String::new(); ids.len()],
+        };
+        if azs.len() != ids.len() {
+            return Err(ConfigError::Message(format!(
+                "terraform outputs {} and {} in {} have different lengths",
+                SUBNET_IDS_OUTPUT, SUBNET_AZS_OUTPUT, self.path
+            )));
+        }
+        let subnets: Vec<Value> = ids
+            .into_iter()
+            .zip(azs)
+            .map(|(id, availability_zone)| {
+                let mut subnet = Map::new();
+                subnet.insert("id".to_string(), Value::new(origin, id));
+                subnet.insert(
+                    "availability_zone".to_string(),
+                    Value::new(origin, availability_zone),
+                );
+                Value::new(origin, ValueKind::Table(subnet))
+            })
+            .collect();
+        settings.insert(
+            "ecs.subnets".to_string(),
+            Value::new(origin, ValueKind::Array(subnets)),
+        );
+
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as Json};
+    use uuid::Uuid;
+
+    use super::*;
+
+    // Collects the settings of a file with the given outputs, as `terraform output -json`
+    // writes them. Errors name the file outputs.json.
+    fn collect(values: Json) -> Result<Map<String, Value>, String> {
+        let outputs: Map<String, Json> = values
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(name, value)| (name.clone(), json!({ "sensitive": false, "value": value })))
+            .collect();
+        let path = std::env::temp_dir().join(format!("terraform-{}.json", Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, serde_json::to_string(&outputs).unwrap()).unwrap();
+        let settings = TerraformOutputs::new(path).collect();
+        std::fs::remove_file(path).unwrap();
+        settings.map_err(|err| err.to_string().replace(path, "outputs.json"))
+    }
+
+    fn outputs() -> Json {
+        json!({
+            "ecs_cluster_name": "soi",
+            "ecs_service_sg_id": "sg-1",
+            "ecs_log_group_name": "/ecs/soi",
+            "ecs_task_role_arn": "arn:aws:iam::123456789012:role/task",
+            "ecs_task_execution_role_arn": "arn:aws:iam::123456789012:role/execution",
+            "private_subnet_ids": ["subnet-1", "subnet-2"],
+            "private_subnet_azs": ["us-east-1a", "us-east-1b"],
+        })
+    }
+
+    fn without(names: &[&str]) -> Json {
+        let mut outputs = outputs();
+        for name in names {
+            outputs.as_object_mut().unwrap().remove(*name);
+        }
+        outputs
+    }
+
+    fn with(name: &str, value: Json) -> Json {
+        let mut outputs = outputs();
+        outputs[name] = value;
+        outputs
+    }
+
+    fn string(settings: &Map<String, Value>, key: &str) -> String {
+        settings[key].clone().into_string().unwrap()
+    }
+
+    fn subnets(settings: &Map<String, Value>) -> Vec<(String, String)> {
+        settings["ecs.subnets"]
+            .clone()
+            .into_array()
+            .unwrap()
+            .into_iter()
+            .map(|subnet| {
+                let subnet = subnet.into_table().unwrap();
+                let field = |name: &str| subnet[name].clone().into_string().unwrap();
+                (field("id"), field("availability_zone"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sets_each_ecs_setting_from_its_output() {
+        let settings = collect(outputs()).unwrap();
+        assert_eq!(string(&settings, "ecs.cluster_name"), "soi");
+        assert_eq!(string(&settings, "ecs.security_group_id"), "sg-1");
+        assert_eq!(string(&settings, "ecs.log_group"), "/ecs/soi");
+        assert_eq!(
+            string(&settings, "ecs.execution_role_arn"),
+            "arn:aws:iam::123456789012:role/execution"
+        );
+        assert_eq!(
+            subnets(&settings),
+            vec![
+                ("subnet-1".to_string(), "us-east-1a".to_string()),
+                ("subnet-2".to_string(), "us-east-1b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_the_azs_blank_without_private_subnet_azs() {
+        let settings = collect(without(&["private_subnet_azs"])).unwrap();
+        assert_eq!(
+            subnets(&settings),
+            vec![
+                ("subnet-1".to_string(), String::new()),
+                ("subnet-2".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_outputs_it_does_not_know() {
+        let settings = collect(with("vpc_id", json!("vpc-1"))).unwrap();
+        assert!(!settings.keys().any(|key| key.contains("vpc")));
+    }
+
+    #[test]
+    fn names_every_missing_output() {
+        assert_eq!(
+            collect(without(&["private_subnet_ids", "ecs_cluster_name"])).unwrap_err(),
+            "terraform outputs file outputs.json is missing ecs_cluster_name, private_subnet_ids"
+        );
+    }
+
+    #[test]
+    fn rejects_outputs_of_the_wrong_shape() {
+        assert_eq!(
+            collect(with("ecs_service_sg_id", json!(["sg-1"]))).unwrap_err(),
+            "terraform output ecs_service_sg_id in outputs.json should be a string"
+        );
+        assert_eq!(
+            collect(with("private_subnet_ids", json!("subnet-1"))).unwrap_err(),
+            "terraform output private_subnet_ids in outputs.json should be a list of strings"
+        );
+        assert_eq!(
+            collect(with("private_subnet_azs", json!(["us-east-1a"]))).unwrap_err(),
+            "terraform outputs private_subnet_ids and private_subnet_azs in outputs.json have different lengths"
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+// The ECS settings each `terraform output` sets.
+pub const TERRAFORM_OUTPUTS: &[(&str, &str)] = &[
+    ("ecs_cluster_name", "ecs.cluster_name"),
+    ("ecs_service_sg_id", "ecs.security_group_id"),
+    ("ecs_log_group_name", "ecs.log_group"),
+    ("ecs_task_role_arn", "ecs.task_role_arn"),
+    ("ecs_task_execution_role_arn", "ecs.execution_role_arn"),
+];
+
+// Lists of the same length, zipped into ecs.subnets. The AZs are optional, but without them
+// every subnet counts as the same AZ for az_spread.
+pub const SUBNET_IDS_OUTPUT: &str = "private_subnet_ids";
+pub const SUBNET_AZS_OUTPUT: &str = "private_subnet_azs";
+
+// The settings in a `terraform output -json` file, layered between the config file and the
+// environment.
+#[derive(Debug, Clone)]
+pub struct TerraformOutputs {
+    pub path: String,
+}
+
+// One entry of `terraform output -json`.
+#[derive(Debug, Deserialize)]
+pub struct TerraformOutput {
+    pub value: serde_json::Value,
+}
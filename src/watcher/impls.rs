@@ -0,0 +1,522 @@
+use std::{collections::HashMap, sync::atomic::Ordering, time::Duration};
+
+use chrono::Utc;
+use tokio::task::JoinHandle;
+
+use super::models::{JobWatcher, Verdict, SPOT_INTERRUPTION, USER_INITIATED};
+use crate::{
+    app::models::AppState,
+    config::models::VendorConfig,
+    ecs::models::{EcsTaskRepo, TaskInfo},
+    errors::models::AppError,
+    jobs::models::{Attempt, AttemptOutcome, Job, JobStatus},
+    metrics::impls::metrics,
+};
+
+impl<T: EcsTaskRepo> JobWatcher<T> {
+    pub fn new(state: AppState<T>) -> Self {
+        let interval = Duration::from_secs(state.config.current().jobs.poll_interval_secs.max(1));
+        JobWatcher { state, interval }
+    }
+
+    pub fn start(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.check_jobs().await {
+                    tracing::error!("checking jobs failed: {:?}", err);
+                }
+            }
+        })
+    }
+
+    async fn check_jobs(&self) -> Result<(), AppError> {
+        let jobs = self.state.jobs.list_unfinished().await?;
+        let waiting = jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::RetryScheduled)
+            .count();
+        metrics()
+            .retry_scheduled
+            .store(waiting as u64, Ordering::Relaxed);
+
+        let task_arns: Vec<String> = jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Running)
+            .filter_map(|job| job.attempts.last())
+            .map(|attempt| attempt.task_arn.clone())
+            .collect();
+        let tasks: HashMap<String, TaskInfo> = if task_arns.is_empty() {
+            HashMap::new()
+        } else {
+            self.state
+                .repo
+                .describe(&task_arns)
+                .await?
+                .into_iter()
+                .map(|task| (task.task_arn.clone(), task))
+                .collect()
+        };
+
+        let now = Utc::now();
+        for mut job in jobs {
+            match job.status {
+                JobStatus::Running => {
+                    let Some(attempt) = job.attempts.last() else {
+                        continue;
+                    };
+                    match tasks.get(&attempt.task_arn) {
+                        Some(task) if task.status == "STOPPED" => {
+                            self.finish_attempt(job, task).await?
+                        }
+                        Some(_) => {}
+                        // ECS only keeps stopped tasks around for about an hour.
+                        None => {
+                            job.status = JobStatus::Failed;
+                            job.last_error =
+                                Some(format!("{} is no longer known to ECS", attempt.task_arn));
+                            self.state.jobs.update(job).await?;
+                        }
+                    }
+                }
+                JobStatus::RetryScheduled if job.next_attempt_at.is_none_or(|at| at <= now) => {
+                    self.relaunch(job).await?
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn finish_attempt(&self, mut job: Job, task: &TaskInfo) -> Result<(), AppError> {
+        let config = self.state.config.current();
+        let verdict = match config.vendor(&job.request.vendor) {
+            Ok(vendor) => classify(vendor, &job, task),
+            Err(_) => Verdict::Fail(format!("vendor {} is not configured", job.request.vendor)),
+        };
+
+        let size_key = job.size_key();
+        let Some(attempt) = job.attempts.last_mut() else {
+            return Ok(());
+        };
+        attempt.record_stop(task);
+        metrics().record_task(&job.request.vendor, attempt);
+        match verdict {
+            Verdict::Succeeded => {
+                attempt.outcome = Some(AttemptOutcome::Succeeded);
+                job.status = JobStatus::Succeeded;
+                self.state
+                    .jobs
+                    .remember_size(&size_key, attempt.size)
+                    .await?;
+            }
+            Verdict::Resize { size, reason } => {
+                attempt.outcome = Some(AttemptOutcome::Resized);
+                tracing::info!(
+                    "job {} attempt {} failed with {}, retrying with cpu {} memory {}",
+                    job.id,
+                    attempt.number,
+                    reason,
+                    size.cpu,
+                    size.memory
+                );
+                job.status = JobStatus::RetryScheduled;
+                job.next_attempt_at = Some(Utc::now());
+                job.size = size;
+            }
+            Verdict::Retry { after, reason } => {
+                attempt.outcome = Some(AttemptOutcome::Retried);
+                tracing::info!(
+                    "job {} attempt {} failed with {}, retrying in {}s",
+                    job.id,
+                    attempt.number,
+                    reason,
+                    after.as_secs()
+                );
+                job.status = JobStatus::RetryScheduled;
+                job.next_attempt_at = Some(Utc::now() + after);
+            }
+            Verdict::Interrupted { on_demand, reason } => {
+                attempt.outcome = Some(AttemptOutcome::Interrupted);
+                tracing::info!(
+                    "job {} attempt {} was interrupted ({}), relaunching{}",
+                    job.id,
+                    attempt.number,
+                    reason,
+                    if on_demand { " on on-demand" } else { "" }
+                );
+                job.status = JobStatus::RetryScheduled;
+                job.next_attempt_at = Some(Utc::now());
+                job.on_demand |= on_demand;
+            }
+            Verdict::Fail(reason) => {
+                attempt.outcome = Some(AttemptOutcome::Failed);
+                tracing::info!("job {} failed with {}", job.id, reason);
+                job.status = JobStatus::Failed;
+                job.last_error = Some(reason);
+            }
+        }
+
+        self.state.jobs.update(job).await
+    }
+
+    // Launching can sit in the dispatch queue and limits for a while, so it runs in the
+    // background. The Launching status keeps the next tick from launching it again.
+    async fn relaunch(&self, mut job: Job) -> Result<(), AppError> {
+        job.status = JobStatus::Launching;
+        job.next_attempt_at = None;
+        self.state.jobs.update(job.clone()).await?;
+
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            let number = job.next_attempt();
+            match state.launch(&job).await {
+                Ok(task) => {
+                    tracing::info!("job {} attempt {} is {}", job.id, number, task.task_arn);
+                    job.attempts.push(Attempt::new(number, job.size, &task));
+                    job.status = JobStatus::Running;
+                    job.last_error = None;
+                }
+                Err(err) => {
+                    tracing::error!("relaunching job {} failed: {:?}", job.id, err);
+                    job.status = JobStatus::Failed;
+                    job.last_error = Some(err.to_string());
+                }
+            }
+            if let Err(err) = state.jobs.update(job).await {
+                tracing::error!("saving job failed: {:?}", err);
+            }
+        });
+
+        Ok(())
+    }
+}
+
+pub fn classify(vendor: &VendorConfig, job: &Job, task: &TaskInfo) -> Verdict {
+    let reasons: Vec<&str> = [
+        task.stopped_reason.as_deref(),
+        task.container_reason.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let summary = match task.exit_code {
+        Some(code) => format!("exit code {}: {}", code, reasons.join("; ")),
+        None => format!("no exit code: {}", reasons.join("; ")),
+    };
+
+    // Someone meant the task to stop, so however it exited there's nothing to recover from,
+    // and relaunching a replaced task would have it replace its replacement in turn.
+    if task.stop_code.as_deref() == Some(USER_INITIATED) {
+        return Verdict::Fail(format!("{} (stopped on request)", summary));
+    }
+
+    if task.stop_code.as_deref() == Some(SPOT_INTERRUPTION) {
+        // This attempt isn't recorded as interrupted yet, hence the + 1.
+        let interruptions = job.interruptions() + 1;
+        if interruptions > vendor.spot.max_interruptions {
+            return Verdict::Fail(format!(
+                "{} (gave up after {} Spot interruptions)",
+                summary, vendor.spot.max_interruptions
+            ));
+        }
+        return Verdict::Interrupted {
+            on_demand: vendor
+                .spot
+                .on_demand_after
+                .is_some_and(|after| interruptions >= after),
+            reason: summary,
+        };
+    }
+
+    // Checked after the stop code, workers that handle SIGTERM exit 0 when they are stopped.
+    if task.exit_code == Some(0) {
+        return Verdict::Succeeded;
+    }
+
+    if reasons.iter().any(|reason| reason.contains("OutOfMemory")) {
+        let next = vendor
+            .max_size
+            .and_then(|max_size| job.size.next_step(&max_size));
+        return match next {
+            Some(size) => Verdict::Resize {
+                size,
+                reason: summary,
+            },
+            None => Verdict::Fail(format!(
+                "{} (out of memory at cpu {} memory {}, the largest allowed size)",
+                summary, job.size.cpu, job.size.memory
+            )),
+        };
+    }
+
+    let policy = &vendor.retry;
+    let attempts = job.retry_attempts();
+    if !policy.is_retryable(task.exit_code, &reasons) {
+        return Verdict::Fail(summary);
+    }
+    if attempts >= policy.max_attempts {
+        return Verdict::Fail(format!("{} (gave up after {} attempts)", summary, attempts));
+    }
+    Verdict::Retry {
+        after: policy.backoff(attempts),
+        reason: summary,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::sizing::models::TaskSize;
+
+    fn job() -> Job {
+        let request = serde_json::from_value(json!({
+            "data_location": "s3://bucket/key",
+            "soiid": "soi-1",
+            "clientid": "client-1",
+            "vendor": "bloomberg",
+        }))
+        .unwrap();
+        Job::new(
+            request,
+            TaskSize {
+                cpu: 512,
+                memory: 1024,
+            },
+        )
+    }
+
+    fn stopped(
+        exit_code: Option<i32>,
+        stop_code: &str,
+        container_reason: Option<&str>,
+    ) -> TaskInfo {
+        serde_json::from_value(json!({
+            "task_arn": "arn:aws:ecs:us-east-1:123456789012:task/default/abc",
+            "status": "STOPPED",
+            "created_at": "2026-01-01T00:00:00Z",
+            "running_duration": null,
+            "image": "worker:latest",
+            "cpu_usage": null,
+            "memory_usage": null,
+            "tags": [],
+            "job_id": null,
+            "request_id": null,
+            "capacity_provider": null,
+            "stopped_at": "2026-01-01T00:05:00Z",
+            "stop_code": stop_code,
+            "stopped_reason": null,
+            "exit_code": exit_code,
+            "container_reason": container_reason,
+            "containers": [],
+        }))
+        .unwrap()
+    }
+
+    // A job whose earlier attempts ended the given ways.
+    fn job_after(outcomes: &[AttemptOutcome]) -> Job {
+        let mut job = job();
+        for outcome in outcomes {
+            let task = stopped(None, SPOT_INTERRUPTION, None);
+            let mut attempt = Attempt::new(job.next_attempt(), job.size, &task);
+            attempt.outcome = Some(*outcome);
+            job.attempts.push(attempt);
+        }
+        job
+    }
+
+    #[test]
+    fn moves_interrupted_workers_to_on_demand_then_gives_up() {
+        let vendor: VendorConfig = serde_json::from_value(json!({
+            "image": "worker:latest",
+            "spot": { "max_interruptions": 3, "on_demand_after": 2 },
+        }))
+        .unwrap();
+        let reclaimed = stopped(
+            None,
+            SPOT_INTERRUPTION,
+            Some("Your Spot Task was interrupted."),
+        );
+        let interrupted = |earlier: usize| {
+            classify(
+                &vendor,
+                &job_after(&vec![AttemptOutcome::Interrupted; earlier]),
+                &reclaimed,
+            )
+        };
+
+        assert!(matches!(
+            interrupted(0),
+            Verdict::Interrupted {
+                on_demand: false,
+                ..
+            }
+        ));
+        // A worker that exits cleanly on SIGTERM was still interrupted.
+        assert!(matches!(
+            classify(
+                &vendor,
+                &job_after(&[]),
+                &stopped(Some(0), SPOT_INTERRUPTION, None)
+            ),
+            Verdict::Interrupted { .. }
+        ));
+        assert!(matches!(
+            interrupted(1),
+            Verdict::Interrupted {
+                on_demand: true,
+                ..
+            }
+        ));
+        assert_eq!(
+            interrupted(3),
+            Verdict::Fail(
+                "no exit code: Your Spot Task was interrupted. (gave up after 3 Spot interruptions)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn counts_interruptions_apart_from_retries() {
+        let job = job_after(&[
+            AttemptOutcome::Interrupted,
+            AttemptOutcome::Retried,
+            AttemptOutcome::Interrupted,
+            AttemptOutcome::Resized,
+        ]);
+        assert_eq!(job.interruptions(), 2);
+        assert_eq!(job.retry_attempts(), 1);
+        assert_eq!(job.next_attempt(), 5);
+    }
+
+    #[test]
+    fn retries_with_backoff_until_the_attempts_run_out() {
+        let vendor: VendorConfig = serde_json::from_value(json!({
+            "image": "worker:latest",
+            "retry": {
+                "max_attempts": 3,
+                "initial_backoff_secs": 30,
+                "retryable_exit_codes": [2],
+            },
+        }))
+        .unwrap();
+        let failed = stopped(Some(2), "EssentialContainerExited", Some("tmp failure"));
+        let retried = |earlier: usize| {
+            classify(
+                &vendor,
+                &job_after(&vec![AttemptOutcome::Retried; earlier]),
+                &failed,
+            )
+        };
+
+        assert_eq!(
+            retried(1),
+            Verdict::Retry {
+                after: Duration::from_secs(30),
+                reason: "exit code 2: tmp failure".to_string()
+            }
+        );
+        assert_eq!(
+            retried(2),
+            Verdict::Retry {
+                after: Duration::from_secs(60),
+                reason: "exit code 2: tmp failure".to_string()
+            }
+        );
+        assert_eq!(
+            retried(3),
+            Verdict::Fail("exit code 2: tmp failure (gave up after 3 attempts)".to_string())
+        );
+        // Interruptions don't use up attempts.
+        assert!(matches!(
+            classify(
+                &vendor,
+                &job_after(&[AttemptOutcome::Interrupted, AttemptOutcome::Retried]),
+                &failed
+            ),
+            Verdict::Retry { .. }
+        ));
+
+        assert_eq!(
+            classify(
+                &vendor,
+                &job_after(&[AttemptOutcome::Retried]),
+                &stopped(Some(1), "EssentialContainerExited", Some("bad input"))
+            ),
+            Verdict::Fail("exit code 1: bad input".to_string())
+        );
+        assert_eq!(
+            classify(
+                &vendor,
+                &job_after(&[AttemptOutcome::Retried]),
+                &stopped(Some(0), "EssentialContainerExited", None)
+            ),
+            Verdict::Succeeded
+        );
+    }
+
+    #[test]
+    fn resizes_only_workers_that_ran_out_of_memory() {
+        let vendor: VendorConfig = serde_json::from_value(json!({
+            "image": "worker:latest",
+            "max_size": { "cpu": 1024, "memory": 8192 },
+            "retry": { "max_attempts": 3, "retryable_exit_codes": [137] },
+        }))
+        .unwrap();
+        let job = job_after(&[AttemptOutcome::Retried]);
+
+        assert!(matches!(
+            classify(
+                &vendor,
+                &job,
+                &stopped(
+                    Some(137),
+                    "EssentialContainerExited",
+                    Some("OutOfMemoryError: Container killed due to memory usage")
+                )
+            ),
+            Verdict::Resize { .. }
+        ));
+        // A bare SIGKILL, e.g. from a stop timing out, is left to the retry policy.
+        assert!(matches!(
+            classify(
+                &vendor,
+                &job,
+                &stopped(Some(137), "EssentialContainerExited", None)
+            ),
+            Verdict::Retry { .. }
+        ));
+    }
+
+    #[test]
+    fn never_relaunches_tasks_stopped_on_request() {
+        let vendor: VendorConfig = serde_json::from_value(json!({
+            "image": "worker:latest",
+            "max_size": { "cpu": 1024, "memory": 8192 },
+            "retry": { "max_attempts": 3, "retryable_exit_codes": [137] },
+        }))
+        .unwrap();
+        let mut replaced = stopped(Some(137), USER_INITIATED, None);
+        replaced.stopped_reason =
+            Some("Replaced by a newer request for bloomberg:soi-1".to_string());
+        assert_eq!(
+            classify(&vendor, &job_after(&[AttemptOutcome::Retried]), &replaced),
+            Verdict::Fail(
+                "exit code 137: Replaced by a newer request for bloomberg:soi-1 (stopped on request)"
+                    .to_string()
+            )
+        );
+
+        replaced.container_reason =
+            Some("OutOfMemoryError: Container killed due to memory usage".to_string());
+        assert!(matches!(
+            classify(&vendor, &job_after(&[]), &replaced),
+            Verdict::Fail(_)
+        ));
+    }
+}
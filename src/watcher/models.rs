@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use crate::{app::models::AppState, ecs::models::EcsTaskRepo, sizing::models::TaskSize};
+
+// Stop code ECS gives tasks that Fargate Spot reclaimed.
+pub const SPOT_INTERRUPTION: &str = "SpotInterruption";
+// Stop code of tasks stopped through StopTask, by us (e.g. when replacing) or anyone else.
+pub const USER_INITIATED: &str = "UserInitiated";
+
+// Follows running jobs to completion and relaunches the ones whose failure is retryable.
+pub struct JobWatcher<T: EcsTaskRepo> {
+    pub state: AppState<T>,
+    pub interval: Duration,
+}
+
+// What happens to a job once its current attempt has stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Succeeded,
+    Retry { after: Duration, reason: String },
+    Resize { size: TaskSize, reason: String },
+    Interrupted { on_demand: bool, reason: String },
+    Fail(String),
+}
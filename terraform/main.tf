@@ -92,6 +92,35 @@ resource "aws_iam_role_policy_attachment" "cloudwatch_logs_policy_attachment" {
   policy_arn = aws_iam_policy.cloudwatch_logs_policy.arn
 }
 
+# allow ECS to inject vendor credentials kept under soi/ into worker containers
+data "aws_caller_identity" "current" {}
+data "aws_region" "current" {}
+
+resource "aws_iam_policy" "worker_secrets_policy" {
+  name        = "WorkerSecretsPolicy"
+  description = "Policy to allow ECS to read worker secrets from SSM and Secrets Manager"
+  policy = jsonencode({
+    Version = "2012-10-17"
+    Statement = [
+      {
+        Effect   = "Allow"
+        Action   = ["ssm:GetParameters"]
+        Resource = "arn:aws:ssm:${data.aws_region.current.name}:${data.aws_caller_identity.current.account_id}:parameter/soi/*"
+      },
+      {
+        Effect   = "Allow"
+        Action   = ["secretsmanager:GetSecretValue"]
+        Resource = "arn:aws:secretsmanager:${data.aws_region.current.name}:${data.aws_caller_identity.current.account_id}:secret:soi/*"
+      }
+    ]
+  })
+}
+
+resource "aws_iam_role_policy_attachment" "worker_secrets_policy_attachment" {
+  role       = aws_iam_role.ecsTaskExecutionRole.name
+  policy_arn = aws_iam_policy.worker_secrets_policy.arn
+}
+
 # Create CloudWatch log group for ECS tasks
 resource "aws_cloudwatch_log_group" "ecs_log_group" {
   name              = "/ecs/test-ecs-cluster"
@@ -103,6 +132,12 @@ resource "aws_ecs_cluster" "my_cluster" {
   name = "test-ecs-cluster"
 }
 
+# Let workers run on Fargate Spot as well as on-demand Fargate
+resource "aws_ecs_cluster_capacity_providers" "my_cluster" {
+  cluster_name       = aws_ecs_cluster.my_cluster.name
+  capacity_providers = ["FARGATE", "FARGATE_SPOT"]
+}
+
 resource "aws_ecr_repository" "ecr_repo" {
   name                 = "test-ecs-repo"
   image_tag_mutability = "MUTABLE"
@@ -112,3 +147,87 @@ resource "aws_ecr_repository" "ecr_repo" {
     scan_on_push = true
   }
 }
+
+# The spawner reads these through ssm:// settings, e.g. cluster_name = "ssm:///soi/ecs/cluster_name"
+resource "aws_ssm_parameter" "cluster_name" {
+  name  = "/soi/ecs/cluster_name"
+  type  = "String"
+  value = aws_ecs_cluster.my_cluster.name
+}
+
+resource "aws_ssm_parameter" "security_group_id" {
+  name  = "/soi/ecs/security_group_id"
+  type  = "String"
+  value = aws_security_group.ecs_service_sg.id
+}
+
+resource "aws_ssm_parameter" "log_group" {
+  name  = "/soi/ecs/log_group"
+  type  = "String"
+  value = aws_cloudwatch_log_group.ecs_log_group.name
+}
+
+resource "aws_ssm_parameter" "execution_role_arn" {
+  name  = "/soi/ecs/execution_role_arn"
+  type  = "String"
+  value = aws_iam_role.ecsTaskExecutionRole.arn
+}
+
+# Workers don't have a role of their own yet and run as the execution role
+resource "aws_ssm_parameter" "task_role_arn" {
+  name  = "/soi/ecs/task_role_arn"
+  type  = "String"
+  value = aws_iam_role.ecsTaskExecutionRole.arn
+}
+
+data "aws_subnet" "private" {
+  count = length(var.private_subnet_ids)
+  id    = var.private_subnet_ids[count.index]
+}
+
+# Each subnet is read with its AZ, e.g.
+# { id = "ssm:///soi/ecs/subnets/0/id", availability_zone = "ssm:///soi/ecs/subnets/0/availability_zone" }
+resource "aws_ssm_parameter" "subnet_ids" {
+  count = length(var.private_subnet_ids)
+  name  = "/soi/ecs/subnets/${count.index}/id"
+  type  = "String"
+  value = var.private_subnet_ids[count.index]
+}
+
+resource "aws_ssm_parameter" "subnet_azs" {
+  count = length(var.private_subnet_ids)
+  name  = "/soi/ecs/subnets/${count.index}/availability_zone"
+  type  = "String"
+  value = data.aws_subnet.private[count.index].availability_zone
+}
+
+# `terraform output -json > outputs.json` and point terraform.outputs_file at it
+
+output "ecs_cluster_name" {
+  value = aws_ecs_cluster.my_cluster.name
+}
+
+output "private_subnet_ids" {
+  value = var.private_subnet_ids
+}
+
+output "private_subnet_azs" {
+  value = data.aws_subnet.private[*].availability_zone
+}
+
+output "ecs_service_sg_id" {
+  value = aws_security_group.ecs_service_sg.id
+}
+
+output "ecs_log_group_name" {
+  value = aws_cloudwatch_log_group.ecs_log_group.name
+}
+
+# Workers don't have a role of their own yet and run as the execution role
+output "ecs_task_role_arn" {
+  value = aws_iam_role.ecsTaskExecutionRole.arn
+}
+
+output "ecs_task_execution_role_arn" {
+  value = aws_iam_role.ecsTaskExecutionRole.arn
+}